@@ -0,0 +1,173 @@
+//! Costs of the innermost operations: vector math, ray-sphere tests, the
+//! linear `HittableList` walk, the BVH of a large `TriangleMesh` and
+//! `LightBvh` traversal. Groups over many rays report their throughput as
+//! rays per second.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rstracer::domain::{
+    hittable::Hittable,
+    hittable_list::HittableList,
+    interval::Interval,
+    light_bvh::LightBvh,
+    material::{DiffuseLight, Lambertian},
+    mesh::TriangleMesh,
+    ray::Ray,
+    scenes,
+    sphere::Sphere,
+    utils::{INFINITY, PI},
+    vec3::Vec3,
+};
+
+fn vec3(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vec3");
+    let (a, b) = (Vec3::new(0.3, -1.2, 2.5), Vec3::new(-0.7, 0.4, 1.1));
+    group.bench_function("add", |bench| bench.iter(|| black_box(a) + black_box(b)));
+    group.bench_function("mul", |bench| bench.iter(|| black_box(a) * black_box(b)));
+    group.bench_function("dot", |bench| bench.iter(|| black_box(a).dot(black_box(b))));
+    group.bench_function("cross", |bench| {
+        bench.iter(|| Vec3::cross(black_box(a), black_box(b)))
+    });
+    group.bench_function("unit_vector", |bench| {
+        bench.iter(|| Vec3::unit_vector(black_box(a)))
+    });
+    group.bench_function("reflect", |bench| {
+        bench.iter(|| Vec3::reflect(black_box(a), black_box(b)))
+    });
+    group.finish();
+}
+
+fn sphere(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sphere");
+    let sphere = Sphere::new(
+        Vec3::new(0., 0., -2.),
+        0.5,
+        Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+    );
+    let interval = Interval::new(0.001, INFINITY);
+    let origin = Vec3::new(0., 0., 0.);
+    let hit = Ray::new(origin, Vec3::new(0.1, 0.05, -1.));
+    let miss = Ray::new(origin, Vec3::new(1., 0.05, -1.));
+    group.bench_function("hit", |bench| {
+        bench.iter(|| sphere.hit(black_box(&hit), &interval).is_some())
+    });
+    group.bench_function("miss", |bench| {
+        bench.iter(|| sphere.hit(black_box(&miss), &interval).is_some())
+    });
+    group.finish();
+}
+
+/// Camera rays on a grid over the image of `random_spheres`, about 490
+/// spheres tested one by one.
+fn hittable_list(c: &mut Criterion) {
+    let description = scenes::random_spheres();
+    let camera = &description.camera;
+    let rays: Vec<Ray> = (0..32)
+        .flat_map(|y| (0..32).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            camera.get_ray(
+                x as f32 * camera.image_width as f32 / 32.,
+                y as f32 * camera.image_height() as f32 / 32.,
+            )
+        })
+        .collect();
+    let interval = Interval::new(0.001, INFINITY);
+
+    let mut group = c.benchmark_group("hittable_list");
+    group.throughput(Throughput::Elements(rays.len() as u64));
+    group.bench_function("random_spheres", |bench| {
+        bench.iter(|| {
+            rays.iter()
+                .filter(|ray| description.scene.world.hit(ray, &interval).is_some())
+                .count()
+        })
+    });
+    group.finish();
+}
+
+/// Camera rays over a sphere of 256 by 256 quads, 131072 triangles in the
+/// mesh's BVH. A little over a third of the rays hit it.
+fn mesh_bvh(c: &mut Criterion) {
+    let n = 256;
+    let positions = (0..=n)
+        .flat_map(|i| (0..=n).map(move |j| (i, j)))
+        .map(|(i, j)| {
+            let theta = PI * i as f32 / n as f32;
+            let phi = 2. * PI * j as f32 / n as f32;
+            Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin() - 3.,
+            )
+        })
+        .collect();
+    let indices = (0..n)
+        .flat_map(|i| (0..n).map(move |j| i * (n + 1) + j))
+        .flat_map(|a| [[a, a + n + 1, a + n + 2], [a, a + n + 2, a + 1]])
+        .collect();
+    let mesh = TriangleMesh::new(
+        positions,
+        indices,
+        Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+    );
+    let rays: Vec<Ray> = (0..32)
+        .flat_map(|y| (0..32).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let target = Vec3::new(x as f32 / 31. - 0.5, y as f32 / 31. - 0.5, -1.);
+            Ray::new(Vec3::new(0., 0., 0.), target)
+        })
+        .collect();
+    let interval = Interval::new(0.001, INFINITY);
+
+    let mut group = c.benchmark_group("mesh_bvh");
+    group.throughput(Throughput::Elements(rays.len() as u64));
+    group.bench_function("sphere_131k", |bench| {
+        bench.iter(|| {
+            rays.iter()
+                .filter(|ray| mesh.hit(black_box(ray), &interval).is_some())
+                .count()
+        })
+    });
+    group.finish();
+}
+
+/// Rays from the floor towards a 16 by 16 grid of lamps, traced through the
+/// `LightBvh` built over them. A quarter of the rays are aimed at a lamp, the
+/// rest pass between them.
+fn light_bvh(c: &mut Criterion) {
+    let mut lights = HittableList::new();
+    for i in 0..256 {
+        let position = Vec3::new((i % 16) as f32 - 7.5, 4., (i / 16) as f32 - 7.5);
+        let power = 1. + (i % 7) as f32;
+        lights.push(Box::new(Sphere::new(
+            position,
+            0.1,
+            DiffuseLight::new(Vec3::new(power, power, power)),
+        )));
+    }
+    let bvh = LightBvh::new(lights);
+    let rays: Vec<Ray> = (0..32)
+        .flat_map(|y| (0..32).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let origin = Vec3::new((x % 8) as f32 - 3.5, 0., (y % 8) as f32 - 3.5);
+            let target = Vec3::new(x as f32 * 0.5 - 7.5, 4., y as f32 * 0.5 - 7.5);
+            Ray::new(origin, target - origin)
+        })
+        .collect();
+    let interval = Interval::new(0.001, INFINITY);
+
+    let mut group = c.benchmark_group("light_bvh");
+    group.throughput(Throughput::Elements(rays.len() as u64));
+    group.bench_function("hit", |bench| {
+        bench.iter(|| {
+            rays.iter()
+                .filter(|ray| bvh.hit(black_box(ray), &interval).is_some())
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, vec3, sphere, hittable_list, mesh_bvh, light_bvh);
+criterion_main!(benches);
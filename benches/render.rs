@@ -0,0 +1,29 @@
+//! Whole frames of the built-in scenes at a low resolution and sample
+//! count, with each scene's own integrator. Throughput is in camera rays
+//! (paths) per second.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rstracer::domain::{camera::Camera, scenes::BuiltinScene};
+
+const WIDTH: i32 = 64;
+const SPP: i32 = 4;
+
+fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    for builtin in BuiltinScene::ALL {
+        let description = builtin.build();
+        let camera = Camera::new(description.camera.aspect_ratio, WIDTH, SPP)
+            .with_vfov(description.camera.vfov);
+        let integrator = description.render.integrator(&camera);
+        let rays = camera.image_width * camera.image_height() * SPP;
+        group.throughput(Throughput::Elements(rays as u64));
+        group.bench_function(builtin.name(), |bench| {
+            bench.iter(|| camera.render_frame(&description.scene, integrator.as_ref()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
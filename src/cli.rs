@@ -0,0 +1,336 @@
+use std::{
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Instant,
+};
+
+use clap::{Parser, ValueEnum};
+use rstracer::domain::{
+    aov::Aov,
+    camera::{Camera, Frame},
+    pbrt,
+    scene_file::{self, IntegratorKind, SceneDescription, SceneError},
+    scenes::BuiltinScene,
+};
+
+const EXIT_CODES: &str = "Exit codes:
+  0  the image was written
+  2  bad command line
+  3  the scene could not be loaded
+  4  the image could not be written";
+
+/// Path traces a TOML or pbrt scene, or a built-in one, to an image.
+#[derive(Parser, Debug)]
+#[command(name = "rstracer", version, after_help = EXIT_CODES)]
+pub struct Args {
+    /// TOML or pbrt (by extension) scene file, the two spheres demo if
+    /// neither this nor --builtin is given
+    pub scene: Option<PathBuf>,
+
+    /// Built-in scene to render instead of a file (two_spheres,
+    /// random_spheres, cornell_box, material_board, earth)
+    #[arg(short, long, value_parser = builtin_scene, conflicts_with = "scene")]
+    pub builtin: Option<BuiltinScene>,
+
+    /// Image to write, AOVs go next to it as <name>_<aov>.pfm
+    #[arg(short, long, default_value = "render.png")]
+    pub output: PathBuf,
+
+    /// Image format, by default from the extension of the output
+    #[arg(short, long, value_enum)]
+    pub format: Option<Format>,
+
+    /// Image width in pixels, keeping the scene's aspect ratio unless
+    /// the height is given too
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub width: Option<i32>,
+
+    /// Image height in pixels
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub height: Option<i32>,
+
+    /// Samples per pixel (passes for sppm)
+    #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub spp: Option<i32>,
+
+    /// Longest path in bounces
+    #[arg(short = 'd', long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub max_depth: Option<i32>,
+
+    /// Seed of the random sequence, the same seed gives the same image
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Worker threads, 0 for one per core
+    #[arg(short = 'j', long, default_value_t = 0)]
+    pub threads: usize,
+
+    /// Integrator to render with instead of the scene's
+    #[arg(short, long, value_parser = integrator_kind)]
+    pub integrator: Option<IntegratorKind>,
+
+    /// Vertical field of view in degrees
+    #[arg(long, value_parser = vfov)]
+    pub vfov: Option<f32>,
+
+    /// Extra layer to write, may be repeated (normal, geometric_normal,
+    /// depth, albedo, uv, object_id, material_id)
+    #[arg(long = "aov", value_parser = aov)]
+    pub aovs: Vec<Aov>,
+
+    /// Quick look at half the resolution with 4 samples and 4 bounces,
+    /// unless those are set explicitly
+    #[arg(long)]
+    pub preview: bool,
+}
+
+fn integrator_kind(name: &str) -> Result<IntegratorKind, String> {
+    IntegratorKind::from_name(name).ok_or_else(|| {
+        let names: Vec<_> = IntegratorKind::ALL.iter().map(|k| k.name()).collect();
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+fn builtin_scene(name: &str) -> Result<BuiltinScene, String> {
+    BuiltinScene::from_name(name).ok_or_else(|| {
+        let names: Vec<_> = BuiltinScene::ALL.iter().map(|s| s.name()).collect();
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+fn vfov(degrees: &str) -> Result<f32, String> {
+    match degrees.parse::<f32>() {
+        Ok(vfov) if vfov > 0. && vfov < 180. => Ok(vfov),
+        _ => Err("expected degrees between 0 and 180".to_string()),
+    }
+}
+
+fn aov(name: &str) -> Result<Aov, String> {
+    Aov::from_name(name).ok_or_else(|| {
+        let names: Vec<_> = Aov::ALL.iter().map(|a| a.name()).collect();
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    Png,
+    #[value(alias = "jpg")]
+    Jpeg,
+    Ppm,
+    /// Linear floats, nothing clipped
+    Pfm,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Format::from_str(&extension, true).ok()
+    }
+}
+
+/// Why a run failed, each with its own exit code.
+#[derive(Debug)]
+pub enum Failure {
+    Usage(String),
+    Scene(SceneError),
+    Output(PathBuf, io::Error),
+}
+
+impl Failure {
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Failure::Usage(_) => 2,
+            Failure::Scene(_) => 3,
+            Failure::Output(..) => 4,
+        })
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Usage(message) => write!(f, "{message}"),
+            Failure::Scene(e) => write!(f, "{e}"),
+            Failure::Output(path, e) => write!(f, "{}: {e}", path.display()),
+        }
+    }
+}
+
+impl Error for Failure {}
+
+pub fn run(args: &Args) -> Result<(), Failure> {
+    let format = match args.format {
+        Some(format) => format,
+        None => Format::from_path(&args.output).ok_or_else(|| {
+            Failure::Usage(format!(
+                "can't tell the format of {}, pass --format",
+                args.output.display()
+            ))
+        })?,
+    };
+    let threads = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build()
+        .map_err(|e| Failure::Usage(format!("can't start {} threads: {e}", args.threads)))?;
+
+    let start = Instant::now();
+    let mut description = match &args.scene {
+        Some(path) => load(path).map_err(Failure::Scene)?,
+        None => args.builtin.unwrap_or(BuiltinScene::TwoSpheres).build(),
+    };
+    args.apply(&mut description);
+    let SceneDescription {
+        camera,
+        scene,
+        render,
+    } = description;
+
+    let integrator = render.integrator(&camera);
+    let load = start.elapsed();
+
+    let mut frame = threads.install(|| camera.render_frame(&scene, integrator.as_ref()));
+    let start = Instant::now();
+    write(&frame, &camera, &args.output, format)?;
+    let stats = &mut frame.stats;
+    stats.phases.insert(0, ("load", load));
+    stats.phases.push(("write", start.elapsed()));
+    eprintln!(
+        "rendered {}x{} at {} spp with {}\n{stats}",
+        camera.image_width,
+        camera.image_height(),
+        camera.samples_per_pixel,
+        render.integrator.name(),
+    );
+    Ok(())
+}
+
+/// Reads `path` as a pbrt scene if it ends in `.pbrt`, TOML otherwise.
+fn load(path: &Path) -> Result<SceneDescription, SceneError> {
+    if path.extension().is_some_and(|e| e == "pbrt") {
+        let import = pbrt::load(path)?;
+        for skipped in &import.unsupported {
+            eprintln!("warning: {skipped}");
+        }
+        return Ok(import.description);
+    }
+    scene_file::load(path)
+}
+
+impl Args {
+    /// Overrides what the scene asks for with the command line.
+    pub fn apply(&self, description: &mut SceneDescription) {
+        let camera = &description.camera;
+        let mut width = camera.image_width;
+        let mut aspect_ratio = camera.aspect_ratio;
+        if self.preview {
+            width = (width / 2).max(1);
+        }
+        match (self.width, self.height) {
+            (Some(w), Some(h)) => {
+                width = w;
+                aspect_ratio = w as f32 / h as f32;
+            }
+            (Some(w), None) => width = w,
+            (None, Some(h)) => width = ((h as f32 * aspect_ratio).round() as i32).max(1),
+            (None, None) => {}
+        }
+        let preview = |value: i32| self.preview.then_some(value);
+        let spp = self.spp.or(preview(4)).unwrap_or(camera.samples_per_pixel);
+
+        let mut new =
+            Camera::new(aspect_ratio, width, spp).with_vfov(self.vfov.unwrap_or(camera.vfov));
+        new.aovs = if self.aovs.is_empty() {
+            camera.aovs.clone()
+        } else {
+            self.aovs.clone()
+        };
+        new.seed = self.seed;
+        description.camera = new;
+
+        let render = &mut description.render;
+        render.max_depth = self.max_depth.or(preview(4)).unwrap_or(render.max_depth);
+        render.integrator = self.integrator.unwrap_or(render.integrator);
+    }
+}
+
+fn write(frame: &Frame, camera: &Camera, path: &Path, format: Format) -> Result<(), Failure> {
+    let spp = camera.samples_per_pixel;
+    let failed = |path: &Path| {
+        let path = path.to_path_buf();
+        move |e| Failure::Output(path, e)
+    };
+    let name = path.to_string_lossy();
+    match format {
+        Format::Png => frame
+            .beauty
+            .write_image(&name, spp, image::ImageFormat::Png),
+        Format::Jpeg => frame
+            .beauty
+            .write_image(&name, spp, image::ImageFormat::Jpeg),
+        Format::Ppm => frame.beauty.write_ppm(&name, spp),
+        Format::Pfm => frame.beauty.write_pfm(&name, spp),
+    }
+    .map_err(failed(path))?;
+
+    for (aov, layer) in &frame.layers {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let layer_path = path.with_file_name(format!("{stem}_{}.pfm", aov.name()));
+        layer
+            .write_pfm(&layer_path.to_string_lossy(), 1)
+            .map_err(failed(&layer_path))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstracer::domain::scenes;
+
+    fn args(line: &[&str]) -> Args {
+        Args::try_parse_from([&["rstracer"], line].concat()).unwrap()
+    }
+
+    #[test]
+    fn overrides_apply_on_top_of_the_scene() {
+        let mut description = scenes::two_spheres();
+        args(&[
+            "--height", "90", "--spp", "3", "-d", "7", "-i", "bdpt", "--vfov", "39.3",
+        ])
+        .apply(&mut description);
+        let camera = &description.camera;
+        assert_eq!((camera.image_width, camera.image_height()), (160, 90));
+        assert_eq!((camera.samples_per_pixel, camera.vfov), (3, 39.3));
+        assert_eq!(description.render.max_depth, 7);
+        assert_eq!(description.render.integrator, IntegratorKind::Bdpt);
+    }
+
+    #[test]
+    fn preview_yields_to_explicit_options() {
+        let mut description = scenes::two_spheres();
+        args(&["--preview", "--spp", "2"]).apply(&mut description);
+        let camera = &description.camera;
+        assert_eq!((camera.image_width, camera.image_height()), (200, 113));
+        assert_eq!(camera.samples_per_pixel, 2);
+        assert_eq!(description.render.max_depth, 4);
+    }
+
+    #[test]
+    fn bad_input_has_its_own_exit_code() {
+        let bad = Args::try_parse_from(["rstracer", "--integrator", "magic"]).unwrap_err();
+        assert_eq!(bad.exit_code(), 2);
+        assert!(bad.to_string().contains("expected one of path, spectral"));
+
+        let unknown_format = run(&args(&["-o", "out.tga"])).unwrap_err();
+        assert_eq!(unknown_format.exit_code(), ExitCode::from(2));
+        let missing = run(&args(&["missing.toml", "-o", "out.png"])).unwrap_err();
+        assert_eq!(missing.exit_code(), ExitCode::from(3));
+        assert_eq!(Format::from_path(Path::new("a/b.JPG")), Some(Format::Jpeg));
+
+        let both = Args::try_parse_from(["rstracer", "a.toml", "-b", "earth"]).unwrap_err();
+        assert_eq!(both.exit_code(), 2);
+    }
+}
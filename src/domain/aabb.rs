@@ -0,0 +1,143 @@
+use super::{
+    interval::Interval,
+    ray::Ray,
+    stats::{self, Counter},
+    vec3::Vec3,
+};
+
+/// Axis aligned bounding box, one interval per axis.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    /// Box with corners `a` and `b`, in any order.
+    pub fn new(a: Vec3, b: Vec3) -> Self {
+        Self {
+            x: Interval::new(a.x().min(b.x()), a.x().max(b.x())),
+            y: Interval::new(a.y().min(b.y()), a.y().max(b.y())),
+            z: Interval::new(a.z().min(b.z()), a.z().max(b.z())),
+        }
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
+        Self {
+            x: Interval::enclosing(&a.x, &b.x),
+            y: Interval::enclosing(&a.y, &b.y),
+            z: Interval::enclosing(&a.z, &b.z),
+        }
+    }
+
+    /// Box grown a little on every axis, so flat ones still have a volume
+    /// for the slab test.
+    pub fn padded(&self) -> Self {
+        let pad = |i: &Interval| i.expand(1e-4 * i.size().max(1.));
+        Self {
+            x: pad(&self.x),
+            y: pad(&self.y),
+            z: pad(&self.z),
+        }
+    }
+
+    pub fn axis(&self, n: usize) -> &Interval {
+        match n {
+            0 => &self.x,
+            1 => &self.y,
+            _ => &self.z,
+        }
+    }
+
+    pub fn min(&self) -> Vec3 {
+        Vec3::new(self.x.min, self.y.min, self.z.min)
+    }
+
+    pub fn max(&self) -> Vec3 {
+        Vec3::new(self.x.max, self.y.max, self.z.max)
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min() + self.max()) * 0.5
+    }
+
+    pub fn diagonal(&self) -> Vec3 {
+        self.max() - self.min()
+    }
+
+    /// Index of the longest axis.
+    pub fn longest_axis(&self) -> usize {
+        let d = self.diagonal();
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn contains(&self, p: Vec3) -> bool {
+        self.x.contains(p.x()) && self.y.contains(p.y()) && self.z.contains(p.z())
+    }
+
+    /// Slab test, whether `r` passes through the box within `ray_t`.
+    pub fn hit(&self, r: &Ray, ray_t: &Interval) -> bool {
+        stats::count(Counter::BoxTests);
+        let (origin, direction) = (r.origin(), r.direction());
+        let mut t_min = ray_t.min;
+        let mut t_max = ray_t.max;
+        for a in 0..3 {
+            let inv_d = 1. / direction[a];
+            let mut t0 = (self.axis(a).min - origin[a]) * inv_d;
+            let mut t1 = (self.axis(a).max - origin[a]) * inv_d;
+            if inv_d < 0. {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN from 0 * inf compares false and leaves the bounds alone
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slab_test() {
+        let b = Aabb::new(Vec3::new(1., 1., 1.), Vec3::new(-1., -1., -1.));
+        let forever = Interval::new(0., f32::INFINITY);
+        assert!(b.hit(
+            &Ray::new(Vec3::new(0., 0., 5.), Vec3::new(0., 0., -1.)),
+            &forever
+        ));
+        assert!(!b.hit(
+            &Ray::new(Vec3::new(0., 0., 5.), Vec3::new(0., 0., 1.)),
+            &forever
+        ));
+        assert!(!b.hit(
+            &Ray::new(Vec3::new(2., 0., 5.), Vec3::new(0., 0., -1.)),
+            &forever
+        ));
+        // Axis parallel rays grazing the box
+        assert!(b.hit(
+            &Ray::new(Vec3::new(1., 0., 5.), Vec3::new(0., 0., -1.)),
+            &forever
+        ));
+        assert!(!b.hit(
+            &Ray::new(Vec3::new(0., 0., 5.), Vec3::new(0., 0., -1.)),
+            &Interval::new(0., 3.)
+        ));
+    }
+}
@@ -0,0 +1,148 @@
+use super::{
+    hittable::Hit, material::Material, medium::Medium, ray::Ray, texture::Texture, vec3::Vec3,
+};
+
+/// Cuts a material out by a mask texture, e.g. leaves on a quad. Opacity
+/// is read from the first channel, zero lets rays through untouched.
+pub struct AlphaMask<M: Material, T: Texture> {
+    material: M,
+    mask: T,
+}
+
+impl<M: Material, T: Texture> AlphaMask<M, T> {
+    pub fn new(material: M, mask: T) -> Self {
+        Self { material, mask }
+    }
+}
+
+impl<M: Material, T: Texture> Material for AlphaMask<M, T> {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        self.material.scatter(ray, hit)
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        self.material.emitted(ray, hit)
+    }
+
+    fn interior(&self) -> Option<Medium> {
+        self.material.interior()
+    }
+
+    fn alpha(&self, hit: &Hit) -> f32 {
+        let mask = self.mask.value(hit.u, hit.v, hit.p).x().clamp(0., 1.);
+        mask * self.material.alpha(hit)
+    }
+
+    fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        self.material.shading_normal(hit)
+    }
+
+    fn albedo(&self, hit: &Hit) -> Vec3 {
+        self.material.albedo(hit)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        self.material.eval(ray, hit, scattered)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        self.material.scattering_pdf(ray, hit, scattered)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::{
+        hittable::Hittable,
+        hittable_list::HittableList,
+        interval::Interval,
+        material::Lambertian,
+        mesh::TriangleMesh,
+        texture::{ImageTexture, SolidColor},
+        utils::INFINITY,
+    };
+
+    fn gray() -> Lambertian {
+        Lambertian::new(Vec3::new(0.5, 0.5, 0.5))
+    }
+
+    /// Masked unit quad at z = 0 in front of an opaque one at z = -1.
+    fn scene(mask: impl Texture + 'static) -> HittableList {
+        let mut world = HittableList::new();
+        let (u, v) = (Vec3::new(1., 0., 0.), Vec3::new(0., 1., 0.));
+        let masked = AlphaMask::new(gray(), mask);
+        world.push(Box::new(TriangleMesh::quad(
+            Vec3::new(0., 0., 0.),
+            u,
+            v,
+            masked,
+        )));
+        world.push(Box::new(TriangleMesh::quad(
+            Vec3::new(0., 0., -1.),
+            u,
+            v,
+            gray(),
+        )));
+        world
+    }
+
+    fn depth(world: &HittableList, x: f32, y: f32) -> Option<f32> {
+        let ray = Ray::new(Vec3::new(x, y, 1.), Vec3::new(0., 0., -1.));
+        world
+            .hit(&ray, &Interval::new(0.001, INFINITY))
+            .map(|h| h.t)
+    }
+
+    #[test]
+    fn cutout_lets_rays_through() {
+        // Left half transparent, right half opaque
+        let mask = ImageTexture::new(2, 1, vec![Vec3::new(0., 0., 0.), Vec3::new(1., 1., 1.)]);
+        let world = scene(mask);
+        assert_eq!(depth(&world, 0.25, 0.5), Some(2.));
+        assert_eq!(depth(&world, 0.75, 0.5), Some(1.));
+        assert_eq!(depth(&world, 1.5, 0.5), None);
+    }
+
+    #[test]
+    fn mesh_traversal_skips_cutouts() {
+        // One mesh: the masked quad at z = 0, where u runs across the mask,
+        // and one at z = -1 whose uvs all fall on the opaque half
+        let mask = ImageTexture::new(2, 1, vec![Vec3::new(0., 0., 0.), Vec3::new(1., 1., 1.)]);
+        let mut positions = vec![];
+        let mut uvs = vec![];
+        for (z, u) in [(0., [0., 1., 1., 0.]), (-1., [0.75; 4])] {
+            for (i, (x, y)) in [(0., 0.), (1., 0.), (1., 1.), (0., 1.)]
+                .into_iter()
+                .enumerate()
+            {
+                positions.push(Vec3::new(x, y, z));
+                uvs.push((u[i], y));
+            }
+        }
+        let indices = vec![[0, 1, 2], [0, 2, 3], [4, 5, 6], [4, 6, 7]];
+        let mesh =
+            TriangleMesh::new(positions, indices, AlphaMask::new(gray(), mask)).with_uvs(uvs);
+
+        let depth = |x: f32| {
+            let ray = Ray::new(Vec3::new(x, 0.5, 1.), Vec3::new(0., 0., -1.));
+            mesh.hit(&ray, &Interval::new(0.001, INFINITY)).map(|h| h.t)
+        };
+        assert_eq!(depth(0.25), Some(2.));
+        assert_eq!(depth(0.75), Some(1.));
+    }
+
+    #[test]
+    fn partial_alpha_is_a_coverage_fraction() {
+        let world = scene(SolidColor::new(Vec3::new(0.3, 0.3, 0.3)));
+        let n = 10_000;
+        let front = (0..n)
+            .filter(|i| {
+                let x = (*i as f32 + 0.5) / n as f32;
+                depth(&world, x, 0.5) == Some(1.)
+            })
+            .count();
+        let coverage = front as f32 / n as f32;
+        assert!((coverage - 0.3).abs() < 0.02, "{coverage}");
+    }
+}
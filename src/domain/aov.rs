@@ -0,0 +1,94 @@
+use super::{hittable::Hit, vec3::Vec3};
+
+/// Arbitrary output variables: per pixel data about the first hit, written
+/// as extra layers next to the beauty pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    ShadingNormal,
+    GeometricNormal,
+    /// Hit distance along the camera ray. Camera rays span the image plane at
+    /// unit focal length, so this is linear depth.
+    Depth,
+    Albedo,
+    Uv,
+    ObjectId,
+    MaterialId,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 7] = [
+        Aov::ShadingNormal,
+        Aov::GeometricNormal,
+        Aov::Depth,
+        Aov::Albedo,
+        Aov::Uv,
+        Aov::ObjectId,
+        Aov::MaterialId,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::ShadingNormal => "normal",
+            Aov::GeometricNormal => "geometric_normal",
+            Aov::Depth => "depth",
+            Aov::Albedo => "albedo",
+            Aov::Uv => "uv",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Aov> {
+        Aov::ALL.into_iter().find(|aov| aov.name() == name)
+    }
+
+    /// Raw value of the layer, zero where the camera ray escaped.
+    pub fn value(self, hit: Option<&Hit>) -> Vec3 {
+        let Some(h) = hit else {
+            return Vec3::new(0., 0., 0.);
+        };
+        match self {
+            Aov::ShadingNormal => h.material.shading_normal(h),
+            Aov::GeometricNormal => h.geometric_normal,
+            Aov::Depth => Vec3::new(h.t, h.t, h.t),
+            Aov::Albedo => h.material.albedo(h),
+            Aov::Uv => Vec3::new(h.u, h.v, 0.),
+            Aov::ObjectId => {
+                let id = h.object_id as f32;
+                Vec3::new(id, id, id)
+            }
+            Aov::MaterialId => {
+                let id = h.material_id as f32;
+                Vec3::new(id, id, id)
+            }
+        }
+    }
+
+    /// Maps a raw value into the displayable 0..1 range.
+    pub fn visualize(self, value: Vec3) -> Vec3 {
+        match self {
+            Aov::ShadingNormal | Aov::GeometricNormal => (value + Vec3::new(1., 1., 1.)) * 0.5,
+            Aov::Depth if value.x() > 0. => {
+                let d = 1. / (1. + value.x());
+                Vec3::new(d, d, d)
+            }
+            Aov::ObjectId | Aov::MaterialId => id_color(value.x() as u32),
+            _ => value,
+        }
+    }
+}
+
+/// Stable pseudo random color per id, black for id 0.
+fn id_color(id: u32) -> Vec3 {
+    if id == 0 {
+        return Vec3::new(0., 0., 0.);
+    }
+    let mut h = id.wrapping_mul(0x9E37_79B9);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85EB_CA6B);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xC2B2_AE35);
+    h ^= h >> 16;
+    let channel = |shift: u32| ((h >> shift) & 0xFF) as f32 / 255.;
+    Vec3::new(channel(0), channel(8), channel(16))
+}
@@ -0,0 +1,514 @@
+use std::cell::RefCell;
+
+use super::{
+    camera::Camera,
+    hittable::{Hit, Hittable},
+    integrator::{analytic_light, Integrator},
+    interval::Interval,
+    onb::Onb,
+    ray::Ray,
+    sampler::Sampler,
+    scene::Scene,
+    stats::{self, Counter},
+    utils::{INFINITY, PI},
+    vec3::Vec3,
+};
+
+thread_local! {
+    /// Splats of the camera sample being rendered on this thread.
+    static SPLATS: RefCell<Vec<(i32, i32, Vec3)>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+/// Subpath vertex with the forward and reverse area pdfs MIS needs.
+#[derive(Clone, Copy)]
+struct Vertex<'a> {
+    kind: VertexKind,
+    p: Vec3,
+    /// Geometric normal. Faces the arriving ray on surfaces, points out of
+    /// emitters and along the view direction for the camera.
+    n: Vec3,
+    hit: Option<Hit<'a>>,
+    /// Ray the vertex was reached by.
+    ray_in: Ray,
+    beta: Vec3,
+    delta: bool,
+    pdf_fwd: f32,
+    pdf_rev: f32,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(camera: &Camera, beta: Vec3) -> Self {
+        Self {
+            kind: VertexKind::Camera,
+            p: camera.origin(),
+            n: camera.forward(),
+            hit: None,
+            ray_in: Ray::default(),
+            beta,
+            delta: false,
+            pdf_fwd: 0.,
+            pdf_rev: 0.,
+        }
+    }
+
+    fn light(hit: Hit<'a>, beta: Vec3, pdf_fwd: f32) -> Self {
+        Self {
+            kind: VertexKind::Light,
+            p: hit.p,
+            n: hit.outward_normal(),
+            hit: Some(hit),
+            ray_in: Ray::default(),
+            beta,
+            delta: false,
+            pdf_fwd,
+            pdf_rev: 0.,
+        }
+    }
+
+    fn surface(hit: Hit<'a>, ray_in: Ray, beta: Vec3) -> Self {
+        Self {
+            kind: VertexKind::Surface,
+            p: hit.p,
+            n: hit.geometric_normal,
+            hit: Some(hit),
+            ray_in,
+            beta,
+            delta: false,
+            pdf_fwd: 0.,
+            pdf_rev: 0.,
+        }
+    }
+
+    fn on_surface(&self) -> bool {
+        self.kind != VertexKind::Camera
+    }
+
+    /// BSDF times cosine from this vertex towards `next`.
+    fn f(&self, next: &Vertex) -> Vec3 {
+        match (self.kind, self.hit) {
+            (VertexKind::Surface, Some(h)) => {
+                h.material
+                    .eval(&self.ray_in, &h, &Ray::new(self.p, next.p - self.p))
+            }
+            _ => Vec3::new(0., 0., 0.),
+        }
+    }
+
+    /// Radiance the surface at this vertex emits towards `to`.
+    fn le(&self, to: Vec3) -> Vec3 {
+        let Some(mut h) = self.hit else {
+            return Vec3::new(0., 0., 0.);
+        };
+        let ray = Ray::new(to, self.p - to);
+        h.set_face_normal(&ray, self.hit_outward_normal());
+        h.material.emitted(&ray, &h)
+    }
+
+    fn hit_outward_normal(&self) -> Vec3 {
+        self.hit.map(|h| h.outward_normal()).unwrap_or(self.n)
+    }
+
+    /// Turns a solid angle pdf at this vertex into an area pdf at `next`.
+    fn convert_density(&self, pdf: f32, next: &Vertex) -> f32 {
+        let w = next.p - self.p;
+        let distance_squared = w.length_squared();
+        if distance_squared == 0. {
+            return 0.;
+        }
+        let mut pdf = pdf / distance_squared;
+        if next.on_surface() {
+            pdf *= next.n.dot(w).abs() / distance_squared.sqrt();
+        }
+        pdf
+    }
+
+    /// Area pdf of the path continuing to `next` after arriving from `prev`.
+    fn pdf(&self, camera: &Camera, prev: Option<&Vertex>, next: &Vertex) -> f32 {
+        match self.kind {
+            VertexKind::Light => self.pdf_light(next),
+            VertexKind::Camera => {
+                let (_, pdf) = camera.importance(next.p - self.p);
+                self.convert_density(pdf, next)
+            }
+            VertexKind::Surface => {
+                let (Some(h), Some(prev)) = (self.hit, prev) else {
+                    return 0.;
+                };
+                let ray_in = Ray::new(prev.p, self.p - prev.p);
+                let scattered = Ray::new(self.p, next.p - self.p);
+                self.convert_density(h.material.scattering_pdf(&ray_in, &h, &scattered), next)
+            }
+        }
+    }
+
+    /// Area pdf at `next` of light leaving this emitter towards it.
+    fn pdf_light(&self, next: &Vertex) -> f32 {
+        let w = Vec3::unit_vector(next.p - self.p);
+        let cosine = self.hit_outward_normal().dot(w);
+        if cosine <= 0. {
+            return 0.;
+        }
+        self.convert_density(cosine / PI, next)
+    }
+
+    /// Area pdf of a light subpath starting at this vertex.
+    fn pdf_light_origin(&self, scene: &Scene) -> f32 {
+        scene.lights.surface_pdf(self.p)
+    }
+}
+
+/// Bidirectional path tracer. Every camera sample also traces a subpath
+/// from the lights and combines all connections between the two with the
+/// balance heuristic. Light paths seen directly by the camera are splatted.
+pub struct BdptIntegrator {
+    pub max_depth: i32,
+    camera: Camera,
+}
+
+impl BdptIntegrator {
+    pub fn new(camera: &Camera, max_depth: i32) -> Self {
+        Self {
+            max_depth,
+            camera: camera.clone(),
+        }
+    }
+
+    /// Camera subpath plus the throughput and ray of a path that escaped.
+    fn camera_subpath<'a>(
+        &self,
+        ray: Ray,
+        scene: &'a Scene,
+    ) -> (Vec<Vertex<'a>>, Option<(Vec3, Ray)>) {
+        let beta = Vec3::new(1., 1., 1.);
+        let mut path = vec![Vertex::camera(&self.camera, beta)];
+        let (_, pdf_dir) = self.camera.importance(ray.direction());
+        let escaped = Self::random_walk(scene, ray, beta, pdf_dir, self.max_depth + 1, &mut path);
+        (path, escaped)
+    }
+
+    fn light_subpath<'a>(&self, scene: &'a Scene) -> Vec<Vertex<'a>> {
+        let mut path = vec![];
+        let Some((hit, pdf_pos)) = scene.lights.sample_surface() else {
+            return path;
+        };
+        let normal = hit.outward_normal();
+        let direction = Onb::build_from_w(normal).local(Vec3::random_cosine_direction());
+        let cosine = normal.dot(direction);
+        let pdf_dir = cosine / PI;
+
+        let mut light = Vertex::light(hit, Vec3::default(), pdf_pos);
+        let le = light.le(hit.p + direction);
+        if le == Vec3::default() || pdf_pos <= 0. || pdf_dir <= 0. {
+            return path;
+        }
+        light.beta = le / pdf_pos;
+        path.push(light);
+
+        let beta = le * (cosine / (pdf_pos * pdf_dir));
+        Self::random_walk(
+            scene,
+            Ray::new(hit.p, direction),
+            beta,
+            pdf_dir,
+            self.max_depth,
+            &mut path,
+        );
+        path
+    }
+
+    /// Extends `path` by at most `max_vertices` scattering vertices. Returns
+    /// the throughput and ray if the walk left the scene.
+    fn random_walk<'a>(
+        scene: &'a Scene,
+        ray: Ray,
+        beta: Vec3,
+        pdf: f32,
+        max_vertices: i32,
+        path: &mut Vec<Vertex<'a>>,
+    ) -> Option<(Vec3, Ray)> {
+        let mut ray = ray;
+        let mut beta = beta;
+        let mut pdf_fwd = pdf;
+        let mut vertices = 0;
+        stats::count(Counter::Paths);
+
+        loop {
+            let Some(h) = scene.hit(&ray, &Interval::new(0.001, INFINITY)) else {
+                return Some((beta, ray));
+            };
+            let prev = *path.last()?;
+            let mut vertex = Vertex::surface(h, ray, beta);
+            vertex.pdf_fwd = prev.convert_density(pdf_fwd, &vertex);
+
+            vertices += 1;
+            if vertices >= max_vertices {
+                path.push(vertex);
+                return None;
+            }
+            let Some((att, scattered)) = h.material.scatter(&ray, &h) else {
+                path.push(vertex);
+                return None;
+            };
+
+            let pdf_rev;
+            pdf_fwd = h.material.scattering_pdf(&ray, &h, &scattered);
+            if pdf_fwd <= 0. {
+                vertex.delta = true;
+                pdf_fwd = 0.;
+                pdf_rev = 0.;
+            } else {
+                let reversed = Ray::new(scattered.at(1.), -scattered.direction());
+                pdf_rev = h
+                    .material
+                    .scattering_pdf(&reversed, &h, &Ray::new(h.p, prev.p - h.p));
+            }
+            let last = path.len() - 1;
+            path[last].pdf_rev = vertex.convert_density(pdf_rev, &prev);
+            path.push(vertex);
+
+            beta = beta * att;
+            ray = scattered;
+            stats::count(Counter::Bounces);
+        }
+    }
+
+    /// Unweighted contribution of the strategy using `s` light and `t`
+    /// camera vertices, times its MIS weight.
+    fn connect<'a>(
+        &self,
+        scene: &'a Scene,
+        light: &[Vertex<'a>],
+        camera: &[Vertex<'a>],
+        s: usize,
+        t: usize,
+    ) -> Vec3 {
+        let zero = Vec3::new(0., 0., 0.);
+        let pt = camera[t - 1];
+        let mut sampled = None;
+
+        let l = if s == 0 {
+            // Camera path hit an emitter by itself
+            pt.beta * pt.le(camera[t - 2].p)
+        } else if s == 1 {
+            // Fresh point on a light, like next-event estimation
+            if pt.delta {
+                return zero;
+            }
+            let Some((hit, pdf_pos)) = scene.lights.sample_surface() else {
+                return zero;
+            };
+            let mut light_vertex = Vertex::light(hit, zero, pdf_pos);
+            let w = pt.p - light_vertex.p;
+            let distance_squared = w.length_squared();
+            let cosine = light_vertex.n.dot(Vec3::unit_vector(w));
+            if cosine <= 0. || pdf_pos <= 0. {
+                return zero;
+            }
+            light_vertex.beta = light_vertex.le(pt.p) * (cosine / (pdf_pos * distance_squared));
+            sampled = Some(light_vertex);
+
+            let l = pt.beta * pt.f(&light_vertex) * light_vertex.beta;
+            if l == zero || !Self::visible(scene, pt.p, light_vertex.p) {
+                return zero;
+            }
+            l
+        } else {
+            let qs = light[s - 1];
+            if qs.delta || pt.delta {
+                return zero;
+            }
+            let distance_squared = (qs.p - pt.p).length_squared();
+            let l = qs.beta * qs.f(&pt) * pt.f(&qs) * pt.beta / distance_squared;
+            if l == zero || !Self::visible(scene, qs.p, pt.p) {
+                return zero;
+            }
+            l
+        };
+
+        if l == zero {
+            return zero;
+        }
+        l * self.mis_weight(scene, light, camera, sampled, s, t)
+    }
+
+    /// Connects light vertex `s - 1` straight to the camera and splats the
+    /// result onto the pixel it lands on.
+    fn splat_to_camera<'a>(&self, scene: &'a Scene, light: &[Vertex<'a>], s: usize) {
+        let qs = light[s - 1];
+        if qs.delta {
+            return;
+        }
+        let Some((x, y)) = self.camera.raster_position(qs.p) else {
+            return;
+        };
+        let to_point = qs.p - self.camera.origin();
+        let (we, _) = self.camera.importance(to_point);
+        if we <= 0. {
+            return;
+        }
+
+        // pdf of picking the pinhole from qs is distance^2 / cos
+        let cosine = Vec3::unit_vector(to_point).dot(self.camera.forward());
+        let beta = we * cosine / to_point.length_squared();
+        let camera_vertex = Vertex::camera(&self.camera, Vec3::new(beta, beta, beta));
+
+        let l = qs.beta * qs.f(&camera_vertex) * camera_vertex.beta;
+        if l == Vec3::default() || !Self::visible(scene, qs.p, camera_vertex.p) {
+            return;
+        }
+        let weight = self.mis_weight(scene, light, &[camera_vertex], Some(camera_vertex), s, 1);
+        SPLATS.with(|splats| splats.borrow_mut().push((x, y, l * weight)));
+    }
+
+    fn visible(scene: &Scene, a: Vec3, b: Vec3) -> bool {
+        scene
+            .hit(&Ray::new(a, b - a), &Interval::new(0.001, 0.999))
+            .is_none()
+    }
+
+    /// Balance heuristic over every other (s, t) split that could have
+    /// produced the same path.
+    fn mis_weight<'a>(
+        &self,
+        scene: &Scene,
+        light: &[Vertex<'a>],
+        camera: &[Vertex<'a>],
+        sampled: Option<Vertex<'a>>,
+        s: usize,
+        t: usize,
+    ) -> f32 {
+        if s + t == 2 {
+            return 1.;
+        }
+        let mut light = light[..s].to_vec();
+        let mut camera = camera[..t].to_vec();
+        if let Some(v) = sampled {
+            if s == 1 {
+                light[0] = v;
+            } else if t == 1 {
+                camera[0] = v;
+            }
+        }
+
+        // Connection endpoints are never specular
+        camera[t - 1].delta = false;
+        if s > 0 {
+            light[s - 1].delta = false;
+        }
+
+        // Reverse pdfs across the connection
+        let pt = camera[t - 1];
+        let pt_minus = if t > 1 { Some(camera[t - 2]) } else { None };
+        let qs = if s > 0 { Some(light[s - 1]) } else { None };
+        let qs_minus = if s > 1 { Some(light[s - 2]) } else { None };
+        camera[t - 1].pdf_rev = match qs {
+            Some(qs) => qs.pdf(&self.camera, qs_minus.as_ref(), &pt),
+            None => pt.pdf_light_origin(scene),
+        };
+        if let Some(pt_minus) = pt_minus {
+            camera[t - 2].pdf_rev = match qs {
+                Some(qs) => pt.pdf(&self.camera, Some(&qs), &pt_minus),
+                None => pt.pdf_light(&pt_minus),
+            };
+        }
+        if let Some(qs) = qs {
+            light[s - 1].pdf_rev = pt.pdf(&self.camera, pt_minus.as_ref(), &qs);
+            if let Some(qs_minus) = qs_minus {
+                light[s - 2].pdf_rev = qs.pdf(&self.camera, Some(&pt), &qs_minus);
+            }
+        }
+
+        let remap0 = |f: f32| if f != 0. { f } else { 1. };
+        let mut sum_ri = 0.;
+        let mut ri = 1.;
+        for i in (1..t).rev() {
+            ri *= remap0(camera[i].pdf_rev) / remap0(camera[i].pdf_fwd);
+            if !camera[i].delta && !camera[i - 1].delta {
+                sum_ri += ri;
+            }
+        }
+        ri = 1.;
+        for i in (0..s).rev() {
+            ri *= remap0(light[i].pdf_rev) / remap0(light[i].pdf_fwd);
+            let delta_light_vertex = i > 0 && light[i - 1].delta;
+            if !light[i].delta && !delta_light_vertex {
+                sum_ri += ri;
+            }
+        }
+        1. / (1. + sum_ri)
+    }
+}
+
+impl Integrator for BdptIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Vec3 {
+        let (camera_path, escaped) = self.camera_subpath(ray, scene);
+        let light_path = self.light_subpath(scene);
+
+        // Nothing but camera paths reach the background, so it needs no MIS
+        let mut color = match escaped {
+            Some((beta, ray)) => beta * scene.background(&ray),
+            None => Vec3::new(0., 0., 0.),
+        };
+
+        // Analytic lights can't start light paths, they only light camera vertices
+        for v in camera_path.iter().take(self.max_depth as usize + 1) {
+            if let (VertexKind::Surface, Some(h), false) = (v.kind, v.hit, v.delta) {
+                color += v.beta * analytic_light(&v.ray_in, &h, scene);
+            }
+        }
+
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                let depth = s as i32 + t as i32 - 2;
+                if (s == 1 && t == 1) || depth < 0 || depth > self.max_depth {
+                    continue;
+                }
+                if t == 1 {
+                    self.splat_to_camera(scene, &light_path, s);
+                } else {
+                    color += self.connect(scene, &light_path, &camera_path, s, t);
+                }
+            }
+        }
+        color
+    }
+
+    fn take_splats(&self) -> Vec<(i32, i32, Vec3)> {
+        SPLATS.with(|splats| splats.take())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::scenes;
+
+    #[test]
+    fn splats_are_the_same_on_any_number_of_threads() {
+        let description = scenes::cornell_box();
+        let mut camera = Camera::new(1., 12, 4).with_vfov(40.);
+        camera.seed = 3;
+        let render = |threads: usize| {
+            let integrator = BdptIntegrator::new(&camera, 5);
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| camera.render_frame(&description.scene, &integrator))
+                .beauty
+        };
+        let (one, four) = (render(1), render(4));
+        for y in 0..one.height {
+            for x in 0..one.width {
+                assert_eq!(one.get(x, y), four.get(x, y), "pixel {x}, {y}");
+            }
+        }
+    }
+}
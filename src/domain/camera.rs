@@ -1,21 +1,47 @@
-use std::{fs::File, io::Write};
+use std::time::Instant;
+
+use rayon::prelude::*;
 
 use super::{
-    color::write_color,
-    hittable::Hittable,
+    aov::Aov,
+    framebuffer::Framebuffer,
+    integrator::Integrator,
     interval::Interval,
     ray::Ray,
-    utils::INFINITY,
-    utils::{degrees_to_radians, random_f32},
+    sampler::RandomSampler,
+    scene::Scene,
+    stats::{self, Counter, Counts, RenderStats},
+    utils::{degrees_to_radians, random_f32, seed_random, INFINITY},
     vec3::Vec3,
 };
 
+/// A finished render, every pixel summed over its samples.
+pub struct Frame {
+    pub beauty: Framebuffer,
+    /// One layer per entry of `Camera::aovs`, a single sample per pixel.
+    pub layers: Vec<(Aov, Framebuffer)>,
+    /// Counters and timings of the render.
+    pub stats: RenderStats,
+}
+
+/// What one row of the image produced, merged in row order.
+struct Row {
+    beauty: Vec<Vec3>,
+    layers: Vec<Vec<Vec3>>,
+    splats: Vec<(i32, i32, Vec3)>,
+    counts: Counts,
+}
+
+#[derive(Clone)]
 pub struct Camera {
     pub aspect_ratio: f32,
     pub image_width: i32,
     pub samples_per_pixel: i32,
-    pub max_depth: i32,
-    pub vfov: i32,
+    pub vfov: f32,
+    /// Extra layers written next to the beauty pass.
+    pub aovs: Vec<Aov>,
+    /// Starts the random sequence of every row, 0 by default.
+    pub seed: u64,
     image_height: i32,
     center: Vec3,
     pixel00_loc: Vec3,
@@ -24,49 +50,179 @@ pub struct Camera {
 }
 
 impl Camera {
-    pub fn new(ar: f32, iw: i32, spp: i32, md: i32) -> Self {
-        let mut s = Self::initialize(iw, ar, md);
+    pub fn new(ar: f32, iw: i32, spp: i32) -> Self {
+        let mut s = Self::initialize(iw, ar, 90.);
         s.samples_per_pixel = spp;
         s
     }
 
-    pub fn render(&self, world: &dyn Hittable) -> () {
-        // setup P3 file
-        let mut ppm_file = String::new();
-        let setup_ppm = format!(
-            "{}{} {}{}",
-            "P3\n", self.image_width, self.image_height, "\n255\n"
-        );
-        ppm_file.push_str(&setup_ppm.to_string());
+    /// Vertical field of view in degrees, 90 by default.
+    pub fn with_vfov(self, vfov: f32) -> Self {
+        Self {
+            samples_per_pixel: self.samples_per_pixel,
+            aovs: self.aovs,
+            seed: self.seed,
+            ..Self::initialize(self.image_width, self.aspect_ratio, vfov)
+        }
+    }
+
+    /// Renders rows in parallel on the current rayon pool. Each row draws
+    /// from its own stream of `seed`, so the image doesn't depend on the
+    /// number of threads. Neither do splats and stats, each row takes what
+    /// its thread deposited and the rows are merged in order.
+    pub fn render_frame(&self, scene: &Scene, integrator: &dyn Integrator) -> Frame {
+        let mut stats = RenderStats {
+            threads: rayon::current_num_threads(),
+            ..RenderStats::default()
+        };
+        let mut beauty = Framebuffer::new(self.image_width, self.image_height);
+        let mut layers: Vec<(Aov, Framebuffer)> = self
+            .aovs
+            .iter()
+            .map(|aov| (*aov, Framebuffer::new(self.image_width, self.image_height)))
+            .collect();
 
         // Render
-        for i in 0..(self.image_height as i32) {
-            for j in 0..(self.image_width as i32) {
-                let mut pixel_color = Vec3::new(0., 0., 0.);
-                for _samples in 0..self.samples_per_pixel {
-                    let r = self.get_ray(j as f32, i as f32);
-                    pixel_color += Self::ray_color(r, self.max_depth, world);
+        seed_random(self.seed);
+        let start = Instant::now();
+        stats::take();
+        let image = integrator.render_image(self, scene);
+        stats.counts = stats::take();
+        if image.is_some() {
+            stats.phases.push(("image", start.elapsed()));
+        }
+
+        let start = Instant::now();
+        let rows: Vec<Row> = (0..self.image_height)
+            .into_par_iter()
+            .map(|i| {
+                seed_random(self.seed.wrapping_add((i as u64 + 1) << 32));
+                stats::take();
+                integrator.take_splats();
+                let mut sampler = RandomSampler;
+                let mut row = vec![Vec3::default(); self.image_width as usize];
+                let mut row_layers = vec![row.clone(); self.aovs.len()];
+                for j in 0..self.image_width {
+                    if image.is_none() {
+                        for _samples in 0..self.samples_per_pixel {
+                            let r = self.get_ray(j as f32, i as f32);
+                            stats::count(Counter::PrimaryRays);
+                            row[j as usize] += integrator.li(r, scene, &mut sampler);
+                        }
+                    }
+
+                    if !self.aovs.is_empty() {
+                        // AOVs use a single unjittered ray so ids don't blend at edges
+                        let r = self.get_center_ray(j as f32, i as f32);
+                        stats::count(Counter::PrimaryRays);
+                        let hit = scene.hit(&r, &Interval::new(0.001, INFINITY));
+                        for (aov, layer) in self.aovs.iter().zip(row_layers.iter_mut()) {
+                            layer[j as usize] = aov.value(hit.as_ref());
+                        }
+                    }
+                }
+                Row {
+                    beauty: row,
+                    layers: row_layers,
+                    splats: integrator.take_splats(),
+                    counts: stats::take(),
+                }
+            })
+            .collect();
+        stats.phases.push(("pixels", start.elapsed()));
+
+        let start = Instant::now();
+        let mut splats = Framebuffer::new(self.image_width, self.image_height);
+        for (i, row) in rows.into_iter().enumerate() {
+            stats.counts.merge(&row.counts);
+            for (j, value) in row.beauty.into_iter().enumerate() {
+                beauty.add(j as i32, i as i32, value);
+            }
+            for (x, y, value) in row.splats {
+                splats.add(x, y, value);
+            }
+            for ((_, layer), values) in layers.iter_mut().zip(row.layers) {
+                for (j, value) in values.into_iter().enumerate() {
+                    layer.add(j as i32, i as i32, value);
                 }
-                ppm_file.push_str(&write_color(pixel_color, self.samples_per_pixel));
             }
         }
 
-        let mut file = File::create("./images/test.ppm").expect("unable to read file");
-        file.write(ppm_file.as_bytes())
-            .expect("unable to write to file");
+        if let Some(image) = image {
+            beauty.merge(&image);
+        }
+        beauty.merge(&splats);
+        stats.phases.push(("gather", start.elapsed()));
+        Frame {
+            beauty,
+            layers,
+            stats,
+        }
+    }
+
+    pub fn image_height(&self) -> i32 {
+        self.image_height
+    }
+
+    pub fn origin(&self) -> Vec3 {
+        self.center
     }
 
-    fn initialize(width: i32, aspect_ratio: f32, max_depth: i32) -> Self {
+    /// Unit view direction through the middle of the image.
+    pub fn forward(&self) -> Vec3 {
+        Vec3::unit_vector(Vec3::cross(self.pixel_delta_u, self.pixel_delta_v))
+    }
+
+    /// Pixel that `p` projects to, `None` when it is behind the camera or
+    /// outside the image.
+    pub fn raster_position(&self, p: Vec3) -> Option<(i32, i32)> {
+        let forward = self.forward();
+        let direction = p - self.center;
+        let cos_theta = direction.dot(forward);
+        if cos_theta <= 0. {
+            return None;
+        }
+
+        // Intersect the image plane, then measure against the pixel grid
+        let focal_length = (self.pixel00_loc - self.center).dot(forward);
+        let on_plane = self.center + direction * (focal_length / cos_theta);
+        let upper_left = self.pixel00_loc - (self.pixel_delta_u + self.pixel_delta_v) * 0.5;
+        let offset = on_plane - upper_left;
+        let x = offset.dot(self.pixel_delta_u) / self.pixel_delta_u.length_squared();
+        let y = offset.dot(self.pixel_delta_v) / self.pixel_delta_v.length_squared();
+        if x < 0. || y < 0. || x >= self.image_width as f32 || y >= self.image_height as f32 {
+            return None;
+        }
+        Some((x as i32, y as i32))
+    }
+
+    /// Importance `We` and solid angle pdf of a camera ray leaving along
+    /// `direction`, normalized so that a whole image of samples sums to one.
+    pub fn importance(&self, direction: Vec3) -> (f32, f32) {
+        let cos_theta = Vec3::unit_vector(direction).dot(self.forward());
+        if cos_theta <= 0. || self.raster_position(self.center + direction).is_none() {
+            return (0., 0.);
+        }
+
+        // Image area on a plane at unit distance
+        let focal_length = (self.pixel00_loc - self.center).dot(self.forward());
+        let width = self.pixel_delta_u.length() * self.image_width as f32;
+        let height = self.pixel_delta_v.length() * self.image_height as f32;
+        let area = width * height / (focal_length * focal_length);
+
+        let cos2 = cos_theta * cos_theta;
+        (1. / (area * cos2 * cos2), 1. / (area * cos2 * cos_theta))
+    }
+
+    fn initialize(width: i32, aspect_ratio: f32, vfov: f32) -> Self {
         let image_width = width;
-        let aspect_ratio = aspect_ratio;
         let samples_per_pixel = 10;
-        let image_height = image_width / aspect_ratio as i32;
+        let image_height = ((image_width as f32 / aspect_ratio).round() as i32).max(1);
         let focal_length = 1.;
-        let vfov = 90.;
         let theta = degrees_to_radians(vfov);
         let h = (theta / 2.).tan();
         let viewport_height = 2. * h * focal_length;
-        let viewport_width = viewport_height * ((image_width) / image_height) as f32;
+        let viewport_width = viewport_height * (image_width as f32 / image_height as f32);
         let camera_center = Vec3::new(0., 0., 0.);
 
         // Calc the vectors across horizontal and vertical edges
@@ -85,8 +241,9 @@ impl Camera {
             image_width,
             aspect_ratio,
             samples_per_pixel,
-            max_depth,
-            vfov: (vfov as i32),
+            vfov,
+            aovs: vec![],
+            seed: 0,
             image_height,
             center: camera_center,
             pixel_delta_u,
@@ -95,7 +252,8 @@ impl Camera {
         }
     }
 
-    fn get_ray(&self, i: f32, j: f32) -> Ray {
+    /// Jittered ray through pixel column `i`, row `j`.
+    pub fn get_ray(&self, i: f32, j: f32) -> Ray {
         let pixel_center = self.pixel00_loc + (self.pixel_delta_u * i) + (self.pixel_delta_v * j);
         let pixel_sample = pixel_center + self.pixel_sample_square();
         let ray_origin = self.center;
@@ -103,26 +261,14 @@ impl Camera {
         Ray::new(ray_origin, ray_direction)
     }
 
+    fn get_center_ray(&self, i: f32, j: f32) -> Ray {
+        let pixel_center = self.pixel00_loc + (self.pixel_delta_u * i) + (self.pixel_delta_v * j);
+        Ray::new(self.center, pixel_center - self.center)
+    }
+
     fn pixel_sample_square(&self) -> Vec3 {
         let px = -0.5 + random_f32();
         let py = -0.5 + random_f32();
         (self.pixel_delta_u * px) + (self.pixel_delta_v * py)
     }
-
-    fn ray_color(ray: Ray, max_depth: i32, world: &dyn Hittable) -> Vec3 {
-        if max_depth <= 0 {
-            return Vec3::new(0., 0., 0.);
-        }
-
-        if let Some(h) = world.hit(&ray, &Interval::new(0.001, INFINITY)) {
-            if let Some((att, scatt)) = h.material.scatter(&ray, &h) {
-                return att * Self::ray_color(scatt, max_depth - 1, world);
-            }
-            return Vec3::new(0., 0., 0.);
-        }
-
-        let unit_direction = Vec3::unit_vector(ray.direction());
-        let a = (unit_direction.y() + 1.0) * 0.5;
-        Vec3::new(1., 1., 1.) * (1.0 - a) + Vec3::new(0.5, 0.7, 1.) * a
-    }
 }
@@ -0,0 +1,229 @@
+use super::{
+    hittable::Hit,
+    material::{fr_dielectric, shading_frame, Material},
+    microfacet::{reflect, refract, TrowbridgeReitz},
+    onb::Onb,
+    ray::Ray,
+    spectrum::rgb_at,
+    utils::{random_f32, PI},
+    vec3::Vec3,
+};
+
+/// A thin dielectric coat over any material, e.g. clear coat over a
+/// `Conductor` for car paint or varnish over `Lambertian` wood. Light
+/// bounces between the coat and the base stochastically, optionally absorbed
+/// on the way through the layer.
+///
+/// The base is only reached through `eval` for light sampling, so specular
+/// bases like `Metal` render but lose their highlights of small lights.
+#[derive(Clone)]
+pub struct Coated<M: Material> {
+    base: M,
+    ior: f32,
+    distribution: TrowbridgeReitz,
+    thickness: f32,
+    color: Vec3,
+}
+
+impl<M: Material> Coated<M> {
+    /// Random walk length inside the layer before giving up.
+    const MAX_BOUNCES: i32 = 16;
+
+    pub fn new(base: M, ior: f32, roughness: f32) -> Self {
+        Self {
+            base,
+            ior,
+            distribution: TrowbridgeReitz::new(roughness),
+            thickness: 0.,
+            color: Vec3::new(1., 1., 1.),
+        }
+    }
+
+    /// Tints the layer, `color` is what survives crossing `thickness` once
+    /// head on.
+    pub fn with_absorption(mut self, thickness: f32, color: Vec3) -> Self {
+        self.thickness = thickness;
+        self.color = color;
+        self
+    }
+
+    /// Transmittance of the layer along local direction `w`.
+    fn absorb(&self, w: Vec3, wavelength: f32) -> Vec3 {
+        if self.thickness <= 0. {
+            return Vec3::new(1., 1., 1.);
+        }
+        let color = rgb_at(self.color, wavelength);
+        let d = self.thickness / w.z().abs().max(1e-4);
+        Vec3::new(color.x().powf(d), color.y().powf(d), color.z().powf(d))
+    }
+
+    /// Asks the base about a direction pair given in the local frame.
+    fn base_eval(&self, hit: &Hit, frame: &Onb, down: Vec3, up: Vec3, wavelength: f32) -> Vec3 {
+        let ray = Ray::new(hit.p, frame.local(down)).with_wavelength(wavelength);
+        self.base.eval(&ray, hit, &Ray::new(hit.p, frame.local(up)))
+    }
+
+    /// Samples the base for a ray going `down` inside the layer.
+    fn base_scatter(
+        &self,
+        hit: &Hit,
+        frame: &Onb,
+        down: Vec3,
+        wavelength: f32,
+    ) -> Option<(Vec3, Vec3)> {
+        let ray = Ray::new(hit.p, frame.local(down)).with_wavelength(wavelength);
+        let (att, scattered) = self.base.scatter(&ray, hit)?;
+        let up = frame.to_local(Vec3::unit_vector(scattered.direction()));
+        if up.z() <= 0. {
+            return None;
+        }
+        Some((att, up))
+    }
+
+    /// Reflection off the coat itself, BSDF times cosine.
+    fn coat_eval(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let wm = Vec3::unit_vector(wo + wi);
+        let fresnel = fr_dielectric(wo.dot(wm), self.ior);
+        self.distribution.d(wm) * self.distribution.g(wo, wi) * fresnel / (4. * wo.z())
+    }
+
+    /// One sample estimate of the light that enters along `wo`, bounces
+    /// around the layer and leaves along `wi`, BSDF times cosine.
+    fn layer_eval(&self, hit: &Hit, frame: &Onb, wo: Vec3, wi: Vec3, wavelength: f32) -> Vec3 {
+        let normal = Vec3::new(0., 0., 1.);
+        let (Some(down), Some(exit)) =
+            (refract(wo, normal, self.ior), refract(wi, normal, self.ior))
+        else {
+            return Vec3::new(0., 0., 0.);
+        };
+        let exit = -exit;
+
+        // Leaving along `wi` squeezes the refracted cone, hence the η² and cosines
+        let t_exit = 1. - fr_dielectric(wi.z(), self.ior);
+        let exit_weight =
+            self.absorb(exit, wavelength) * (t_exit * wi.z() / (self.ior * self.ior * exit.z()));
+
+        let mut down = down;
+        let mut beta = self.absorb(down, wavelength) * (1. - fr_dielectric(wo.z(), self.ior));
+        let mut f = Vec3::new(0., 0., 0.);
+        for bounce in 0..Self::MAX_BOUNCES {
+            f += beta * self.base_eval(hit, frame, down, exit, wavelength) * exit_weight;
+
+            let Some((att, up)) = self.base_scatter(hit, frame, down, wavelength) else {
+                break;
+            };
+            // Only the share the coat reflects back stays inside
+            let reflected = fr_dielectric(up.z(), 1. / self.ior);
+            down = Vec3::new(up.x(), up.y(), -up.z());
+            beta = beta
+                * att
+                * self.absorb(up, wavelength)
+                * self.absorb(down, wavelength)
+                * reflected;
+
+            if bounce > 2 {
+                let survive = beta.max_component().min(1.);
+                if survive <= 0. || random_f32() >= survive {
+                    break;
+                }
+                beta = beta / survive;
+            }
+        }
+        f
+    }
+
+    /// Pdf of `scatter` leaving through the layer, approximated by a single
+    /// bounce off the base mixed with a cosine lobe.
+    fn layer_pdf(&self, hit: &Hit, frame: &Onb, wo: Vec3, wi: Vec3, wavelength: f32) -> f32 {
+        let normal = Vec3::new(0., 0., 1.);
+        let (Some(down), Some(exit)) =
+            (refract(wo, normal, self.ior), refract(wi, normal, self.ior))
+        else {
+            return 0.;
+        };
+        let exit = -exit;
+        let ray = Ray::new(hit.p, frame.local(down)).with_wavelength(wavelength);
+        let pdf = self
+            .base
+            .scattering_pdf(&ray, hit, &Ray::new(hit.p, frame.local(exit)));
+        if pdf <= 0. {
+            return 0.;
+        }
+        let t_exit = 1. - fr_dielectric(wi.z(), self.ior);
+        let single = pdf * t_exit * wi.z() / (self.ior * self.ior * exit.z());
+        0.9 * single + 0.1 * wi.z() / PI
+    }
+}
+
+impl<M: Material> Material for Coated<M> {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        let (frame, wo) = shading_frame(ray, hit);
+        if wo.z() <= 0. {
+            return None;
+        }
+        let wavelength = ray.wavelength();
+        let coat_reflect = fr_dielectric(wo.z(), self.ior);
+
+        if random_f32() < coat_reflect {
+            let wm = self.distribution.sample_wm(wo, random_f32(), random_f32());
+            let wi = reflect(wo, wm);
+            if wi.z() <= 0. {
+                return None;
+            }
+            let fresnel = fr_dielectric(wo.dot(wm), self.ior);
+            let g = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+            let w = fresnel / coat_reflect * g;
+            let scattered = Ray::new(hit.p, frame.local(wi)).with_wavelength(wavelength);
+            return Some((Vec3::new(w, w, w), scattered));
+        }
+
+        // Random walk between base and coat, Fresnel picks every branch so
+        // only the base and the absorption weigh the path
+        let mut down = refract(wo, Vec3::new(0., 0., 1.), self.ior)?;
+        let mut beta = self.absorb(down, wavelength);
+        for _ in 0..Self::MAX_BOUNCES {
+            let (att, up) = self.base_scatter(hit, &frame, down, wavelength)?;
+            beta = beta * att * self.absorb(up, wavelength);
+
+            if random_f32() >= fr_dielectric(up.z(), 1. / self.ior) {
+                let wi = refract(-up, Vec3::new(0., 0., -1.), 1. / self.ior)?;
+                let scattered = Ray::new(hit.p, frame.local(wi)).with_wavelength(wavelength);
+                return Some((beta, scattered));
+            }
+            down = Vec3::new(up.x(), up.y(), -up.z());
+            beta = beta * self.absorb(down, wavelength);
+        }
+        None
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        self.base.emitted(ray, hit)
+    }
+
+    fn albedo(&self, hit: &Hit) -> Vec3 {
+        self.base.albedo(hit) * self.color
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        let (frame, wo) = shading_frame(ray, hit);
+        let wi = frame.to_local(Vec3::unit_vector(scattered.direction()));
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return Vec3::new(0., 0., 0.);
+        }
+        let coat = self.coat_eval(wo, wi);
+        Vec3::new(coat, coat, coat) + self.layer_eval(hit, &frame, wo, wi, ray.wavelength())
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        let (frame, wo) = shading_frame(ray, hit);
+        let wi = frame.to_local(Vec3::unit_vector(scattered.direction()));
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return 0.;
+        }
+        let coat_reflect = fr_dielectric(wo.z(), self.ior);
+        let wm = Vec3::unit_vector(wo + wi);
+        let coat = self.distribution.d_visible(wo, wm) / (4. * wo.dot(wm));
+        coat_reflect * coat
+            + (1. - coat_reflect) * self.layer_pdf(hit, &frame, wo, wi, ray.wavelength())
+    }
+}
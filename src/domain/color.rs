@@ -4,23 +4,21 @@ fn linear_to_gamma(linear_component: f32) -> f32 {
     linear_component.sqrt()
 }
 
+/// Inverse of the output transfer, for reading 8 bit images.
+pub fn gamma_to_linear(gamma_component: f32) -> f32 {
+    gamma_component * gamma_component
+}
+
 pub fn write_color(pixel_color: Vec3, samples_per_pixel: i32) -> String {
-    let r = pixel_color.x();
-    let g = pixel_color.y();
-    let b = pixel_color.z();
+    let [r, g, b] = to_rgb8(pixel_color, samples_per_pixel);
+    format!("{r} {g} {b}\n")
+}
 
+/// Gamma corrected 8 bit channels of the average of `samples_per_pixel`.
+pub fn to_rgb8(pixel_color: Vec3, samples_per_pixel: i32) -> [u8; 3] {
     // divide by number of samples
     let scale = 1. / samples_per_pixel as f32;
-
-    let r = linear_to_gamma(r * scale);
-    let g = linear_to_gamma(g * scale);
-    let b = linear_to_gamma(b * scale);
-
     let intensity = Interval::new(0., 0.999);
-
-    let mut str = String::from("");
-    str.push_str(&(format!("{}", (256. * intensity.clamp(r)) as i32) + " "));
-    str.push_str(&(format!("{}", (256. * intensity.clamp(g)) as i32) + " "));
-    str.push_str(&(format!("{}", (256. * intensity.clamp(b)) as i32) + "\n"));
-    str
+    [pixel_color.x(), pixel_color.y(), pixel_color.z()]
+        .map(|c| (256. * intensity.clamp(linear_to_gamma(c * scale))) as u8)
 }
@@ -0,0 +1,152 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+};
+
+use super::{
+    color::{to_rgb8, write_color},
+    vec3::Vec3,
+};
+
+/// Accumulated linear values for every pixel of an image.
+pub struct Framebuffer {
+    pub width: i32,
+    pub height: i32,
+    pixels: Vec<Vec3>,
+}
+
+impl Framebuffer {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vec3::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Vec3 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn add(&mut self, x: i32, y: i32, value: Vec3) {
+        self.pixels[(y * self.width + x) as usize] += value;
+    }
+
+    pub fn merge(&mut self, other: &Framebuffer) {
+        for (pixel, value) in self.pixels.iter_mut().zip(other.pixels.iter()) {
+            *pixel += *value;
+        }
+    }
+
+    /// Copy with every pixel multiplied by `factor`, such as one over the
+    /// sample count to get the average.
+    pub fn scaled(&self, factor: f32) -> Framebuffer {
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|p| *p * factor).collect(),
+        }
+    }
+
+    /// Gamma corrected P3 image of the buffer averaged over `samples_per_pixel`.
+    pub fn write_ppm(&self, path: &str, samples_per_pixel: i32) -> io::Result<()> {
+        let mut ppm_file = format!("P3\n{} {}\n255\n", self.width, self.height);
+        for pixel in self.pixels.iter() {
+            ppm_file.push_str(&write_color(*pixel, samples_per_pixel));
+        }
+        File::create(path)?.write_all(ppm_file.as_bytes())
+    }
+
+    /// Gamma corrected 8 bit image, such as PNG or JPEG.
+    pub fn write_image(
+        &self,
+        path: &str,
+        samples_per_pixel: i32,
+        format: image::ImageFormat,
+    ) -> io::Result<()> {
+        let bytes: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|p| to_rgb8(*p, samples_per_pixel))
+            .collect();
+        image::save_buffer_with_format(
+            path,
+            &bytes,
+            self.width as u32,
+            self.height as u32,
+            image::ColorType::Rgb8,
+            format,
+        )
+        .map_err(io::Error::other)
+    }
+
+    /// Linear float PFM of the buffer averaged over `samples_per_pixel`,
+    /// for layers whose values don't fit a display range.
+    pub fn write_pfm(&self, path: &str, samples_per_pixel: i32) -> io::Result<()> {
+        let scale = 1. / samples_per_pixel as f32;
+        let mut bytes = format!("PF\n{} {}\n-1.0\n", self.width, self.height).into_bytes();
+        // PFM scanlines run bottom to top
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let pixel = self.get(x, y) * scale;
+                for c in [pixel.x(), pixel.y(), pixel.z()] {
+                    bytes.extend_from_slice(&c.to_le_bytes());
+                }
+            }
+        }
+        File::create(path)?.write_all(&bytes)
+    }
+
+    /// Reads a color PFM as written by `write_pfm`.
+    pub fn read_pfm(path: &str) -> io::Result<Framebuffer> {
+        let bytes = fs::read(path)?;
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        // Three whitespace separated header fields after the magic
+        let mut fields = vec![];
+        let mut pos = 0;
+        while fields.len() < 4 {
+            while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+                pos += 1;
+            }
+            let start = pos;
+            while bytes.get(pos).is_some_and(|c| !c.is_ascii_whitespace()) {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(invalid("PFM header ends early"));
+            }
+            fields.push(String::from_utf8_lossy(&bytes[start..pos]).into_owned());
+        }
+        pos += 1;
+        if fields[0] != "PF" {
+            return Err(invalid("not a color PFM"));
+        }
+        let number = |field: &str| field.parse().map_err(|_| invalid("bad PFM header"));
+        let (width, height): (i32, i32) = (number(&fields[1])?, number(&fields[2])?);
+        let big_endian = fields[3]
+            .parse::<f32>()
+            .map_err(|_| invalid("bad PFM scale"))?
+            > 0.;
+
+        let data = bytes.get(pos..).unwrap_or_default();
+        if width < 0 || height < 0 || data.len() < (width * height * 12) as usize {
+            return Err(invalid("PFM data ends early"));
+        }
+        let mut channels = data.chunks_exact(4).map(|c| {
+            let c = [c[0], c[1], c[2], c[3]];
+            if big_endian {
+                f32::from_be_bytes(c)
+            } else {
+                f32::from_le_bytes(c)
+            }
+        });
+        let mut image = Framebuffer::new(width, height);
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let mut next = || channels.next().unwrap_or_default();
+                image.add(x, y, Vec3::new(next(), next(), next()));
+            }
+        }
+        Ok(image)
+    }
+}
@@ -1,24 +1,132 @@
-use super::{interval::Interval, material::Material, ray::Ray, vec3::Vec3};
+use super::{aabb::Aabb, interval::Interval, material::Material, onb::Onb, ray::Ray, vec3::Vec3};
 
+#[derive(Clone, Copy)]
 pub struct Hit<'a> {
     pub p: Vec3,
+    /// Shading normal, facing against the ray.
     pub normal: Vec3,
+    /// Normal of the actual surface, facing against the ray.
+    pub geometric_normal: Vec3,
     pub t: f32,
+    pub u: f32,
+    pub v: f32,
+    /// Surface derivatives along u and v, spanning the tangent plane.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
     pub material: &'a dyn Material,
     pub front_face: bool,
+    /// Index + 1 of the top level `HittableList` entry that was hit.
+    pub object_id: u32,
+    pub material_id: u32,
 }
 
 impl<'a> Hit<'a> {
-    pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) -> () {
+    pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         self.front_face = r.direction().dot(outward_normal) < 0.0;
         self.normal = if self.front_face {
             outward_normal
         } else {
             -outward_normal
         };
+        self.geometric_normal = self.normal;
+    }
+
+    /// Replaces the shading normal with `n`, flipped to the ray's side of
+    /// the surface. `front_face` still follows the geometric normal.
+    pub fn set_shading_normal(&mut self, n: Vec3) {
+        let n = Vec3::unit_vector(n);
+        self.normal = if n.dot(self.geometric_normal) < 0. {
+            -n
+        } else {
+            n
+        };
+    }
+
+    /// Unit tangent along increasing u, perpendicular to the shading normal.
+    pub fn tangent(&self) -> Vec3 {
+        let t = self.dpdu - self.normal * self.normal.dot(self.dpdu);
+        if t.near_zero() {
+            return Onb::build_from_w(self.normal).u();
+        }
+        Vec3::unit_vector(t)
+    }
+
+    /// Geometric normal pointing out of the surface.
+    pub fn outward_normal(&self) -> Vec3 {
+        if self.front_face {
+            self.geometric_normal
+        } else {
+            -self.geometric_normal
+        }
+    }
+}
+
+/// `object.hit`, continuing the ray past hits whose material is transparent
+/// there. Aggregates call this on their children so cutouts work anywhere.
+pub fn hit_opaque<'a>(object: &'a dyn Hittable, ray: &Ray, interval: &Interval) -> Option<Hit<'a>> {
+    let mut min = interval.min;
+    loop {
+        let hit = object.hit(ray, &Interval::new(min, interval.max))?;
+        if is_opaque(&hit, ray) {
+            return Some(hit);
+        }
+        min = hit.t;
     }
 }
 
-pub trait Hittable {
-    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit>;
+/// Whether `ray` stops at `hit` rather than passing through its cutout.
+/// Also used inside aggregates with their own traversal, like meshes.
+pub fn is_opaque(hit: &Hit, ray: &Ray) -> bool {
+    let alpha = hit.material.alpha(hit);
+    alpha >= 1. || (alpha > 0. && alpha_hash(ray, hit.t) < alpha)
+}
+
+/// Uniform [0, 1) value fixed per ray and distance, so nested aggregates
+/// and repeated queries agree on partially transparent hits.
+fn alpha_hash(ray: &Ray, t: f32) -> f32 {
+    let (o, d) = (ray.origin(), ray.direction());
+    let mut h: u32 = 0x9E37_79B9;
+    for x in [o.x(), o.y(), o.z(), d.x(), d.y(), d.z(), t] {
+        h = (h ^ x.to_bits()).wrapping_mul(0x85EB_CA6B);
+        h ^= h >> 13;
+    }
+    h = h.wrapping_mul(0xC2B2_AE35);
+    h ^= h >> 16;
+    (h >> 8) as f32 / (1 << 24) as f32
+}
+
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit<'_>>;
+
+    /// Solid angle pdf of `random` picking `direction` from `origin`.
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f32 {
+        0.
+    }
+
+    /// Direction from `origin` towards a random point on the object.
+    fn random(&self, _origin: Vec3) -> Vec3 {
+        Vec3::new(1., 0., 0.)
+    }
+
+    /// Uniform random point on the surface as a front facing hit, with its
+    /// pdf per unit area. Used to start light paths on emitters.
+    fn sample_surface(&self) -> Option<(Hit<'_>, f32)> {
+        None
+    }
+
+    /// Area pdf of `sample_surface` returning the point `p`.
+    fn surface_pdf(&self, _p: Vec3) -> f32 {
+        0.
+    }
+
+    /// Box around the object, `None` when it is unbounded.
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    /// Cone (axis, cosine of its half angle) holding every outward normal,
+    /// `None` when they may point anywhere.
+    fn normal_bounds(&self) -> Option<(Vec3, f32)> {
+        None
+    }
 }
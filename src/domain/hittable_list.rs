@@ -1,7 +1,10 @@
 use super::{
-    hittable::{Hit, Hittable},
+    aabb::Aabb,
+    hittable::{hit_opaque, Hit, Hittable},
     interval::Interval,
     ray::Ray,
+    utils::random_f32,
+    vec3::Vec3,
 };
 
 pub struct HittableList {
@@ -13,25 +16,80 @@ impl HittableList {
         Self { objects: vec![] }
     }
 
-    pub fn clear(&mut self) -> () {
+    pub fn clear(&mut self) {
         Vec::clear(&mut self.objects)
     }
 
-    pub fn push(&mut self, obj: Box<dyn Hittable>) -> () {
+    pub fn push(&mut self, obj: Box<dyn Hittable>) {
         self.objects.push(obj);
     }
 }
 
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit> {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit<'_>> {
         let mut closest_so_far = interval.max;
-        let mut hit_anything: Option<Hit> = None;
-        for o in self.objects.iter() {
-            if let Some(hit) = o.hit(ray, &Interval::new(interval.min, closest_so_far)) {
+        let mut hit_anything: Option<Hit<'_>> = None;
+        for (i, o) in self.objects.iter().enumerate() {
+            let interval = Interval::new(interval.min, closest_so_far);
+            if let Some(mut hit) = hit_opaque(o.as_ref(), ray, &interval) {
+                // Outer lists overwrite, so ids name whole objects of the world
+                hit.object_id = i as u32 + 1;
                 closest_so_far = hit.t;
                 hit_anything = Some(hit);
             }
         }
         hit_anything
     }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        if self.objects.is_empty() {
+            return 0.;
+        }
+        let weight = 1. / self.objects.len() as f32;
+        self.objects
+            .iter()
+            .map(|o| weight * o.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1., 0., 0.);
+        }
+        let size = self.objects.len();
+        let i = ((random_f32() * size as f32) as usize).min(size - 1);
+        self.objects[i].random(origin)
+    }
+
+    fn sample_surface(&self) -> Option<(Hit<'_>, f32)> {
+        if self.objects.is_empty() {
+            return None;
+        }
+        let size = self.objects.len();
+        let i = ((random_f32() * size as f32) as usize).min(size - 1);
+        let (mut hit, pdf) = self.objects[i].sample_surface()?;
+        hit.object_id = i as u32 + 1;
+        Some((hit, pdf / size as f32))
+    }
+
+    fn surface_pdf(&self, p: Vec3) -> f32 {
+        if self.objects.is_empty() {
+            return 0.;
+        }
+        let weight = 1. / self.objects.len() as f32;
+        self.objects.iter().map(|o| weight * o.surface_pdf(p)).sum()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (first, rest) = self.objects.split_first()?;
+        rest.iter().try_fold(first.bounding_box()?, |b, o| {
+            Some(Aabb::surrounding(&b, &o.bounding_box()?))
+        })
+    }
 }
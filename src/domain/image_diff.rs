@@ -0,0 +1,226 @@
+use super::{framebuffer::Framebuffer, vec3::Vec3};
+
+/// Ways to score how far a render is from a reference, 0 for a match.
+/// Images are compared as linear averages, not sums over samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    /// Root mean square error of the linear values.
+    Rmse,
+    /// Mean squared error divided by the squared reference, so dark and
+    /// bright regions weigh the same.
+    RelMse,
+    /// Perceptual error in [0, 1] after the ideas of NVIDIA's FLIP: color
+    /// differences of the blurred images, amplified where edges differ.
+    Flip,
+}
+
+/// Keeps the relative error finite on black pixels.
+const REL_EPSILON: f32 = 0.01;
+
+impl Metric {
+    pub const ALL: [Metric; 3] = [Metric::Rmse, Metric::RelMse, Metric::Flip];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Rmse => "rmse",
+            Metric::RelMse => "relmse",
+            Metric::Flip => "flip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Metric> {
+        Metric::ALL.into_iter().find(|metric| metric.name() == name)
+    }
+
+    /// Error of every pixel, its mean (square rooted for RMSE) is the score.
+    /// The images must have the same size.
+    pub fn error_map(self, image: &Framebuffer, reference: &Framebuffer) -> Vec<f32> {
+        assert_eq!(
+            (image.width, image.height),
+            (reference.width, reference.height),
+            "compared images differ in size"
+        );
+        let pixels = pixels(image).zip(pixels(reference));
+        match self {
+            Metric::Rmse => pixels.map(|(a, b)| (a - b).length_squared() / 3.).collect(),
+            Metric::RelMse => pixels
+                .map(|(a, b)| {
+                    let d = a - b;
+                    (0..3)
+                        .map(|c| d[c] * d[c] / (b[c] * b[c] + REL_EPSILON))
+                        .sum::<f32>()
+                        / 3.
+                })
+                .collect(),
+            Metric::Flip => flip(image, reference),
+        }
+    }
+
+    pub fn score(self, image: &Framebuffer, reference: &Framebuffer) -> f32 {
+        let map = self.error_map(image, reference);
+        let mean = map.iter().sum::<f32>() / map.len().max(1) as f32;
+        match self {
+            Metric::Rmse => mean.sqrt(),
+            Metric::RelMse | Metric::Flip => mean,
+        }
+    }
+}
+
+fn pixels(image: &Framebuffer) -> impl Iterator<Item = Vec3> + '_ {
+    (0..image.height).flat_map(move |y| (0..image.width).map(move |x| image.get(x, y)))
+}
+
+/// Heat map of `errors`, black for none and white from `max` up.
+pub fn heat_map(width: i32, height: i32, errors: &[f32], max: f32) -> Framebuffer {
+    let mut image = Framebuffer::new(width, height);
+    for (i, error) in errors.iter().enumerate() {
+        let t = (error / max).clamp(0., 1.);
+        // Black through red and yellow to white
+        let color = Vec3::new(
+            (3. * t).min(1.),
+            (3. * t - 1.).clamp(0., 1.),
+            (3. * t - 2.).clamp(0., 1.),
+        );
+        image.add(i as i32 % width, i as i32 / width, color);
+    }
+    image
+}
+
+/// CIELAB of a linear color, clamped to the displayable range first.
+fn lab(linear: Vec3) -> Vec3 {
+    let c = Vec3::new(
+        linear.x().clamp(0., 1.),
+        linear.y().clamp(0., 1.),
+        linear.z().clamp(0., 1.),
+    );
+    // Linear sRGB to XYZ, relative to the D65 white
+    let x = (0.4124 * c.x() + 0.3576 * c.y() + 0.1805 * c.z()) / 0.9505;
+    let y = 0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z();
+    let z = (0.0193 * c.x() + 0.1192 * c.y() + 0.9505 * c.z()) / 1.089;
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16. / 116.
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    Vec3::new(116. * fy - 16., 500. * (fx - fy), 200. * (fy - fz))
+}
+
+/// Separable Gaussian blur standing in for the eye's contrast sensitivity.
+fn blur(values: &[Vec3], width: usize, height: usize) -> Vec<Vec3> {
+    const KERNEL: [f32; 5] = [0.0545, 0.2442, 0.4026, 0.2442, 0.0545];
+    let pass = |values: &[Vec3], dx: isize, dy: isize| -> Vec<Vec3> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                KERNEL
+                    .iter()
+                    .enumerate()
+                    .map(|(k, weight)| {
+                        // Clamp to the border
+                        let offset = k as isize - 2;
+                        let sx = (x as isize + offset * dx).clamp(0, width as isize - 1);
+                        let sy = (y as isize + offset * dy).clamp(0, height as isize - 1);
+                        values[sy as usize * width + sx as usize] * *weight
+                    })
+                    .fold(Vec3::default(), |sum, v| sum + v)
+            })
+            .collect()
+    };
+    pass(&pass(values, 1, 0), 0, 1)
+}
+
+/// Sobel gradient length of the lightness, per pixel.
+fn edges(lab: &[Vec3], width: usize, height: usize) -> Vec<f32> {
+    let at = |x: isize, y: isize| {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        lab[y * width + x].x()
+    };
+    (0..height as isize)
+        .flat_map(|y| (0..width as isize).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let gx = at(x + 1, y - 1) + 2. * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2. * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2. * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2. * at(x, y - 1)
+                - at(x + 1, y - 1);
+            (gx * gx + gy * gy).sqrt()
+        })
+        .collect()
+}
+
+fn flip(image: &Framebuffer, reference: &Framebuffer) -> Vec<f32> {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let lab_a: Vec<Vec3> = pixels(image).map(lab).collect();
+    let lab_b: Vec<Vec3> = pixels(reference).map(lab).collect();
+    let (blur_a, blur_b) = (blur(&lab_a, width, height), blur(&lab_b, width, height));
+    let (edges_a, edges_b) = (edges(&lab_a, width, height), edges(&lab_b, width, height));
+
+    (0..width * height)
+        .map(|i| {
+            // HyAB distance, lightness apart from chroma, compressed so
+            // large differences saturate
+            let d = blur_a[i] - blur_b[i];
+            let hyab = d.x().abs() + (d.y() * d.y() + d.z() * d.z()).sqrt();
+            let color = (hyab / 100.).min(1.).powf(0.7);
+            // A Sobel filter over the full lightness range peaks at 400
+            let feature = ((edges_a[i] - edges_b[i]).abs() / 400.).min(1.).sqrt();
+            color.powf(1. - feature)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn image(width: i32, height: i32, color: impl Fn(i32, i32) -> Vec3) -> Framebuffer {
+        let mut image = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                image.add(x, y, color(x, y));
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn identical_images_score_zero() {
+        let a = image(8, 4, |x, y| Vec3::new(x as f32 / 8., y as f32 / 4., 0.5));
+        for metric in Metric::ALL {
+            assert_eq!(metric.score(&a, &a), 0.);
+            assert_eq!(Metric::from_name(metric.name()), Some(metric));
+        }
+    }
+
+    #[test]
+    fn scores_grow_with_the_difference() {
+        let grey = |v: f32| image(8, 8, move |_, _| Vec3::new(v, v, v));
+        let reference = grey(0.5);
+        assert!((Metric::Rmse.score(&grey(0.6), &reference) - 0.1).abs() < 1e-5);
+        assert!((Metric::RelMse.score(&grey(0.6), &reference) - 0.01 / 0.26).abs() < 1e-5);
+        for metric in Metric::ALL {
+            let near = metric.score(&grey(0.55), &reference);
+            let far = metric.score(&grey(0.9), &reference);
+            assert!(0. < near && near < far, "{}", metric.name());
+        }
+        assert!(Metric::Flip.score(&grey(1.), &grey(0.)) > 0.9);
+    }
+
+    #[test]
+    fn pfm_round_trip() {
+        let a = image(5, 3, |x, y| Vec3::new(x as f32, y as f32, 2.5));
+        let path = std::env::temp_dir().join(format!("rstracer_{}.pfm", std::process::id()));
+        let path = path.to_string_lossy();
+        a.write_pfm(&path, 2).unwrap();
+        let b = Framebuffer::read_pfm(&path).unwrap();
+        std::fs::remove_file(&*path).unwrap();
+        assert_eq!(Metric::Rmse.score(&a.scaled(0.5), &b), 0.);
+    }
+}
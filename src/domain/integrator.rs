@@ -0,0 +1,372 @@
+use super::{
+    aov::Aov,
+    camera::Camera,
+    framebuffer::Framebuffer,
+    hittable::{Hit, Hittable},
+    interval::Interval,
+    medium::Medium,
+    onb::Onb,
+    ray::Ray,
+    sampler::Sampler,
+    scene::Scene,
+    spectrum,
+    stats::{self, Counter},
+    utils::{power_heuristic, INFINITY},
+    vec3::Vec3,
+};
+
+/// Light transport algorithm `Camera::render` evaluates for every sample.
+pub trait Integrator: Send + Sync {
+    /// Radiance arriving at the camera along `ray`.
+    fn li(&self, ray: Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Vec3;
+
+    /// Contributions this thread deposited at arbitrary pixels (e.g. by
+    /// light tracing) since the last call, in the same per-sample units as
+    /// `li`. The camera takes them after every row and adds them in row
+    /// order, so the sums don't depend on the number of threads.
+    fn take_splats(&self) -> Vec<(i32, i32, Vec3)> {
+        vec![]
+    }
+
+    /// Whole image for integrators that can't work one camera sample at a
+    /// time, summed over `samples_per_pixel` like the per-sample path.
+    /// `None` makes the camera call `li` for every sample instead.
+    fn render_image(&self, _camera: &Camera, _scene: &Scene) -> Option<Framebuffer> {
+        None
+    }
+}
+
+/// Unidirectional path tracer with next-event estimation and Russian
+/// roulette. Random walks through the interior media of materials.
+pub struct PathIntegrator {
+    pub max_depth: i32,
+    /// Bounces before Russian roulette may terminate a path.
+    pub rr_min_depth: i32,
+    /// Trace a single sampled wavelength per path, so dispersive
+    /// dielectrics split light into colors.
+    pub spectral: bool,
+}
+
+impl PathIntegrator {
+    pub fn new(max_depth: i32) -> Self {
+        Self {
+            max_depth,
+            rr_min_depth: 3,
+            spectral: false,
+        }
+    }
+
+    /// Randomly ends paths past `rr_min_depth`, reweighting survivors.
+    /// Returns whether the path goes on.
+    fn russian_roulette(
+        &self,
+        depth: i32,
+        throughput: &mut Vec3,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        if depth < self.rr_min_depth {
+            return true;
+        }
+        let survive = throughput.max_component().min(1.);
+        if sampler.get_1d() >= survive {
+            stats::count(Counter::RouletteKills);
+            return false;
+        }
+        *throughput = *throughput / survive;
+        true
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Vec3 {
+        let wavelength = if self.spectral {
+            spectrum::sample_wavelength(sampler.get_1d())
+        } else {
+            0.
+        };
+        let mut ray = ray.with_wavelength(wavelength);
+        let mut color = Vec3::new(0., 0., 0.);
+        let mut throughput = Vec3::new(1., 1., 1.);
+        stats::count(Counter::Paths);
+        // pdf the last bounce sampled `ray` with, zero for camera rays and specular bounces
+        let mut bsdf_pdf = 0.;
+        // Interior of the object the path is inside of, if it has one
+        let mut medium: Option<Medium> = None;
+        // Media sample distances by one channel for the whole path and weigh
+        // by the average of every channel's path pdf, kept normalized here
+        let hero = ((sampler.get_1d() * 3.) as usize).min(2);
+        let mut channel_pdf = Vec3::new(1., 1., 1.);
+
+        for depth in 0..self.max_depth {
+            let hit = scene.hit(&ray, &Interval::new(0.001, INFINITY));
+
+            if let Some(m) = medium {
+                let t_max = hit.as_ref().map_or(INFINITY, |h| h.t) * ray.direction().length();
+                let direction = Vec3::unit_vector(ray.direction());
+                let (t, f, pdf, scattered) =
+                    m.sample_distance(t_max, hero, sampler.get_1d(), wavelength);
+                let average = channel_pdf.dot(pdf) / 3.;
+                if average <= 0. {
+                    break;
+                }
+                throughput = throughput * f / average;
+                channel_pdf = channel_pdf * pdf / average;
+                if scattered {
+                    // Lights are outside the boundary, so no shadow rays from in here
+                    let (u1, u2) = sampler.get_2d();
+                    let next = m.sample_phase(direction, u1, u2);
+                    ray = Ray::new(ray.origin() + direction * t, next).with_wavelength(wavelength);
+                    bsdf_pdf = 0.;
+                    stats::count(Counter::Bounces);
+                    if !self.russian_roulette(depth, &mut throughput, sampler) {
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            let Some(h) = hit else {
+                return color + throughput * scene.background(&ray);
+            };
+
+            let mut emitted = h.material.emitted(&ray, &h);
+            if bsdf_pdf > 0. && emitted != Vec3::default() {
+                // The previous bounce also sampled this emitter explicitly
+                let light_pdf = scene.lights.pdf_value(ray.origin(), ray.direction());
+                emitted = emitted * power_heuristic(bsdf_pdf, light_pdf);
+            }
+            color += throughput * emitted;
+
+            let Some((att, scatt)) = h.material.scatter(&ray, &h) else {
+                break;
+            };
+            bsdf_pdf = h.material.scattering_pdf(&ray, &h, &scatt);
+            if bsdf_pdf > 0. {
+                color += throughput * sample_light(&ray, &h, scene);
+            }
+            if let Some(interior) = h.material.interior() {
+                let entering = scatt.direction().dot(h.outward_normal()) < 0.;
+                medium = if entering { Some(interior) } else { None };
+            }
+            throughput = throughput * att;
+            ray = scatt;
+            stats::count(Counter::Bounces);
+
+            if !self.russian_roulette(depth, &mut throughput, sampler) {
+                break;
+            }
+        }
+        if self.spectral {
+            // Every channel carries the same spectral radiance
+            return spectrum::to_rgb(color.x(), wavelength);
+        }
+        color
+    }
+}
+
+/// Recursive Whitted-style tracer. Specular bounces are followed, diffuse
+/// hits only see the emitters plus an unoccluded sky term.
+pub struct WhittedIntegrator {
+    pub max_depth: i32,
+}
+
+impl WhittedIntegrator {
+    pub fn new(max_depth: i32) -> Self {
+        Self { max_depth }
+    }
+
+    fn trace(&self, ray: Ray, scene: &Scene, depth: i32) -> Vec3 {
+        if depth <= 0 {
+            return Vec3::new(0., 0., 0.);
+        }
+        let Some(h) = scene.hit(&ray, &Interval::new(0.001, INFINITY)) else {
+            return scene.background(&ray);
+        };
+
+        let color = h.material.emitted(&ray, &h);
+        let Some((att, scatt)) = h.material.scatter(&ray, &h) else {
+            return color;
+        };
+        if h.material.scattering_pdf(&ray, &h, &scatt) > 0. {
+            let ambient = att * scene.background(&Ray::new(h.p, h.normal));
+            return color + direct_light(&ray, &h, scene) + ambient;
+        }
+        stats::count(Counter::Bounces);
+        color + att * self.trace(scatt, scene, depth - 1)
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Vec3 {
+        stats::count(Counter::Paths);
+        self.trace(ray, scene, self.max_depth)
+    }
+}
+
+/// Fraction of the cosine weighted hemisphere at the first hit that is
+/// unoccluded within `distance`.
+pub struct AmbientOcclusion {
+    pub samples: i32,
+    pub distance: f32,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: i32, distance: f32) -> Self {
+        Self { samples, distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn li(&self, ray: Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Vec3 {
+        stats::count(Counter::Paths);
+        let Some(h) = scene.hit(&ray, &Interval::new(0.001, INFINITY)) else {
+            return Vec3::new(1., 1., 1.);
+        };
+
+        let uvw = Onb::build_from_w(h.normal);
+        let mut unoccluded = 0;
+        for _ in 0..self.samples {
+            let (r1, r2) = sampler.get_2d();
+            let direction = uvw.local(Vec3::cosine_direction(r1, r2));
+            let probe = Ray::new(h.p, direction);
+            if scene
+                .hit(&probe, &Interval::new(0.001, self.distance))
+                .is_none()
+            {
+                unoccluded += 1;
+            }
+        }
+        let visibility = unoccluded as f32 / self.samples.max(1) as f32;
+        Vec3::new(visibility, visibility, visibility)
+    }
+}
+
+/// Emission plus MIS weighted direct light at the first non-specular hit.
+pub struct DirectLighting {
+    /// Specular bounces followed before giving up.
+    pub max_depth: i32,
+}
+
+impl DirectLighting {
+    pub fn new(max_depth: i32) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Integrator for DirectLighting {
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Vec3 {
+        let mut ray = ray;
+        let mut color = Vec3::new(0., 0., 0.);
+        let mut throughput = Vec3::new(1., 1., 1.);
+        stats::count(Counter::Paths);
+
+        for _ in 0..self.max_depth {
+            let Some(h) = scene.hit(&ray, &Interval::new(0.001, INFINITY)) else {
+                return color + throughput * scene.background(&ray);
+            };
+            color += throughput * h.material.emitted(&ray, &h);
+
+            let Some((att, scatt)) = h.material.scatter(&ray, &h) else {
+                break;
+            };
+            let bsdf_pdf = h.material.scattering_pdf(&ray, &h, &scatt);
+            stats::count(Counter::Bounces);
+            if bsdf_pdf <= 0. {
+                throughput = throughput * att;
+                ray = scatt;
+                continue;
+            }
+
+            color += throughput * sample_light(&ray, &h, scene);
+            // Second strategy: whatever the material sample reaches directly
+            match scene.hit(&scatt, &Interval::new(0.001, INFINITY)) {
+                Some(lh) => {
+                    let emitted = lh.material.emitted(&scatt, &lh);
+                    if emitted != Vec3::default() {
+                        let light_pdf = scene.lights.pdf_value(scatt.origin(), scatt.direction());
+                        color += throughput * att * emitted * power_heuristic(bsdf_pdf, light_pdf);
+                    }
+                }
+                None => color += throughput * att * scene.background(&scatt),
+            }
+            break;
+        }
+        color
+    }
+}
+
+/// Debug view of a single AOV at the first hit.
+pub struct AovIntegrator {
+    pub aov: Aov,
+}
+
+impl AovIntegrator {
+    pub fn new(aov: Aov) -> Self {
+        Self { aov }
+    }
+}
+
+impl Integrator for AovIntegrator {
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Vec3 {
+        let hit = scene.hit(&ray, &Interval::new(0.001, INFINITY));
+        self.aov.visualize(self.aov.value(hit.as_ref()))
+    }
+}
+
+/// Emitted light reaching `hit` along a direction picked from `scene.lights`.
+/// Returns the unoccluded `(emitted, shadow ray, light pdf)`.
+fn light_sample(ray: &Ray, hit: &Hit, scene: &Scene) -> Option<(Vec3, Ray, f32)> {
+    let direction = scene.lights.random(hit.p);
+    let light_pdf = scene.lights.pdf_value(hit.p, direction);
+    if light_pdf <= 0. {
+        return None;
+    }
+
+    let shadow = Ray::new(hit.p, direction).with_wavelength(ray.wavelength());
+    let light_hit = scene.hit(&shadow, &Interval::new(0.001, INFINITY))?;
+    let emitted = light_hit.material.emitted(&shadow, &light_hit);
+    if emitted == Vec3::default() {
+        return None;
+    }
+    Some((emitted, shadow, light_pdf))
+}
+
+/// Next-event estimation: one shadow ray towards the lights, MIS weighted
+/// against the material's own sampling, plus the analytic lights.
+fn sample_light(ray: &Ray, hit: &Hit, scene: &Scene) -> Vec3 {
+    let analytic = analytic_light(ray, hit, scene);
+    let Some((emitted, shadow, light_pdf)) = light_sample(ray, hit, scene) else {
+        return analytic;
+    };
+    let f = hit.material.eval(ray, hit, &shadow);
+    let bsdf_pdf = hit.material.scattering_pdf(ray, hit, &shadow);
+    analytic + f * emitted * (power_heuristic(light_pdf, bsdf_pdf) / light_pdf)
+}
+
+/// Light sampling only, for integrators that never hit emitters by chance.
+pub fn direct_light(ray: &Ray, hit: &Hit, scene: &Scene) -> Vec3 {
+    let analytic = analytic_light(ray, hit, scene);
+    let Some((emitted, shadow, light_pdf)) = light_sample(ray, hit, scene) else {
+        return analytic;
+    };
+    analytic + hit.material.eval(ray, hit, &shadow) * emitted / light_pdf
+}
+
+/// Light from every analytic light of the scene reaching `hit`, one shadow
+/// ray each. Nothing else can find these lights, so no MIS.
+pub fn analytic_light(ray: &Ray, hit: &Hit, scene: &Scene) -> Vec3 {
+    let mut color = Vec3::new(0., 0., 0.);
+    for light in &scene.analytic_lights {
+        let Some(sample) = light.sample_li(hit.p) else {
+            continue;
+        };
+        let shadow = Ray::new(hit.p, sample.direction).with_wavelength(ray.wavelength());
+        let unblocked = Interval::new(0.001, sample.distance * (1. - 1e-4));
+        if scene.hit(&shadow, &unblocked).is_some() {
+            continue;
+        }
+        let li = spectrum::rgb_at(sample.li, ray.wavelength());
+        color += hit.material.eval(ray, hit, &shadow) * li;
+    }
+    color
+}
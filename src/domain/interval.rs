@@ -1,22 +1,40 @@
 use super::utils::INFINITY;
 
+#[derive(Clone, Copy, Debug)]
 pub struct Interval {
     pub min: f32,
     pub max: f32,
 }
 
-impl Interval {
-    pub fn default() -> Self {
+impl Default for Interval {
+    fn default() -> Self {
         Self {
             min: INFINITY,
             max: -INFINITY,
         }
     }
+}
 
+impl Interval {
     pub fn new(min: f32, max: f32) -> Self {
         Self { min, max }
     }
 
+    /// Smallest interval holding both `a` and `b`.
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        Self::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
+    /// Interval grown by `delta` in total, half on each side.
+    pub fn expand(&self, delta: f32) -> Self {
+        let padding = delta / 2.;
+        Self::new(self.min - padding, self.max + padding)
+    }
+
+    pub fn size(&self) -> f32 {
+        self.max - self.min
+    }
+
     pub fn contains(&self, x: f32) -> bool {
         self.min <= x && x <= self.max
     }
@@ -0,0 +1,294 @@
+use super::{
+    onb::Onb,
+    ray::Ray,
+    utils::{degrees_to_radians, random_f32, INFINITY, PI},
+    vec3::Vec3,
+};
+
+/// Light from one direction at a shaded point, see `Light::sample_li`.
+#[derive(Clone, Copy, Debug)]
+pub struct LightSample {
+    /// Unit direction from the point towards the light.
+    pub direction: Vec3,
+    /// How far a shadow ray has to stay unblocked, infinite for distant lights.
+    pub distance: f32,
+    /// Incident radiance divided by the pdf of picking `direction`.
+    pub li: Vec3,
+}
+
+/// Light that isn't geometry, so rays never hit it by chance and it is
+/// only ever reached by sampling it with a shadow ray.
+pub trait Light: Send + Sync {
+    /// Picks a direction towards the light as seen from `p`.
+    fn sample_li(&self, p: Vec3) -> Option<LightSample>;
+
+    /// Starts a photon: its power divided by the pdf of the ray leaving the
+    /// light. Distant lights shine on the sphere at `center` of `radius`
+    /// around the scene.
+    fn sample_le(&self, center: Vec3, radius: f32) -> Option<(Vec3, Ray)>;
+
+    /// Total emitted power, for choosing between lights. Distant lights
+    /// count what falls on a scene of `radius`.
+    fn power(&self, radius: f32) -> Vec3;
+}
+
+/// Isotropic point light, `intensity` in watts per steradian.
+#[derive(Clone)]
+pub struct PointLight {
+    position: Vec3,
+    intensity: Vec3,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Vec3) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample_li(&self, p: Vec3) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance_squared = to_light.length_squared();
+        if distance_squared <= 0. {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            li: self.intensity / distance_squared,
+        })
+    }
+
+    fn sample_le(&self, _center: Vec3, _radius: f32) -> Option<(Vec3, Ray)> {
+        let ray = Ray::new(self.position, Vec3::random_unit_vector());
+        Some((self.intensity * (4. * PI), ray))
+    }
+
+    fn power(&self, _radius: f32) -> Vec3 {
+        self.intensity * (4. * PI)
+    }
+}
+
+/// Point light limited to a cone, full `intensity` inside `inner_angle` and
+/// smoothly falling off to nothing at `outer_angle` (both in degrees, from
+/// the axis).
+#[derive(Clone)]
+pub struct SpotLight {
+    position: Vec3,
+    direction: Vec3,
+    intensity: Vec3,
+    cos_inner: f32,
+    cos_outer: f32,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Vec3,
+        target: Vec3,
+        intensity: Vec3,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        let outer_angle = outer_angle.max(inner_angle);
+        Self {
+            position,
+            direction: Vec3::unit_vector(target - position),
+            intensity,
+            cos_inner: degrees_to_radians(inner_angle).cos(),
+            cos_outer: degrees_to_radians(outer_angle).cos(),
+        }
+    }
+
+    /// Smoothstep from the outer to the inner cone.
+    fn falloff(&self, cos_theta: f32) -> f32 {
+        if cos_theta >= self.cos_inner {
+            return 1.;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.;
+        }
+        let x = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        x * x * (3. - 2. * x)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_li(&self, p: Vec3) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance_squared = to_light.length_squared();
+        if distance_squared <= 0. {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let direction = to_light / distance;
+        let falloff = self.falloff(self.direction.dot(-direction));
+        if falloff <= 0. {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            li: self.intensity * (falloff / distance_squared),
+        })
+    }
+
+    fn sample_le(&self, _center: Vec3, _radius: f32) -> Option<(Vec3, Ray)> {
+        // Uniform over the outer cone
+        let solid_angle = 2. * PI * (1. - self.cos_outer);
+        let cos_theta = 1. - random_f32() * (1. - self.cos_outer);
+        let falloff = self.falloff(cos_theta);
+        if solid_angle <= 0. || falloff <= 0. {
+            return None;
+        }
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * random_f32();
+        let direction = Onb::build_from_w(self.direction).local(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+        let ray = Ray::new(self.position, direction);
+        Some((self.intensity * (falloff * solid_angle), ray))
+    }
+
+    fn power(&self, _radius: f32) -> Vec3 {
+        // Full cone up to the midpoint of the falloff
+        self.intensity * (2. * PI * (1. - (self.cos_inner + self.cos_outer) / 2.))
+    }
+}
+
+/// Distant light such as the sun, `irradiance` measured on a surface facing
+/// it. A nonzero angular diameter (degrees) gives soft shadows.
+#[derive(Clone)]
+pub struct DirectionalLight {
+    /// Unit direction the light travels in.
+    direction: Vec3,
+    irradiance: Vec3,
+    cos_max: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Vec3) -> Self {
+        Self {
+            direction: Vec3::unit_vector(direction),
+            irradiance,
+            cos_max: 1.,
+        }
+    }
+
+    /// The sun is about 0.53 degrees across.
+    pub fn with_angular_diameter(mut self, degrees: f32) -> Self {
+        self.cos_max = degrees_to_radians(degrees / 2.).cos();
+        self
+    }
+
+    /// Direction towards a random point of the light's disk.
+    fn to_light(&self) -> Vec3 {
+        let to_light = -self.direction;
+        if self.cos_max >= 1. {
+            return to_light;
+        }
+        // Uniform over the disk's cone, its radiance times solid angle is the irradiance
+        let cos_theta = 1. - random_f32() * (1. - self.cos_max);
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * random_f32();
+        Onb::build_from_w(to_light).local(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample_li(&self, _p: Vec3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: self.to_light(),
+            distance: INFINITY,
+            li: self.irradiance,
+        })
+    }
+
+    fn sample_le(&self, center: Vec3, radius: f32) -> Option<(Vec3, Ray)> {
+        if radius <= 0. {
+            return None;
+        }
+        // Uniform over the disk facing the light, just outside the scene
+        let r = radius * random_f32().sqrt();
+        let phi = 2. * PI * random_f32();
+        let offset =
+            Onb::build_from_w(self.direction).local(Vec3::new(r * phi.cos(), r * phi.sin(), 0.));
+        let origin = center + offset - self.direction * radius;
+        let ray = Ray::new(origin, -self.to_light());
+        Some((self.power(radius), ray))
+    }
+
+    fn power(&self, radius: f32) -> Vec3 {
+        self.irradiance * (PI * radius * radius)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn point_light_falls_off_with_distance_squared() {
+        let light = PointLight::new(Vec3::new(0., 2., 0.), Vec3::new(4., 4., 4.));
+        let sample = light.sample_li(Vec3::default()).unwrap();
+        assert_eq!(sample.direction, Vec3::new(0., 1., 0.));
+        assert_eq!(sample.distance, 2.);
+        assert_eq!(sample.li, Vec3::new(1., 1., 1.));
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let light = SpotLight::new(
+            Vec3::new(0., 1., 0.),
+            Vec3::default(),
+            Vec3::new(1., 1., 1.),
+            10.,
+            30.,
+        );
+        let inside = light.sample_li(Vec3::default()).unwrap();
+        assert_eq!(inside.li, Vec3::new(1., 1., 1.));
+        // 45 degrees off the axis is past the outer cone
+        assert!(light.sample_li(Vec3::new(1., 0., 0.)).is_none());
+        // 20 degrees is halfway through the falloff
+        let edge = light.sample_li(Vec3::new(20f32.to_radians().tan(), 0., 0.));
+        let li = edge.unwrap().li.x() * (1. + 20f32.to_radians().tan().powi(2));
+        assert!(li > 0. && li < 1., "{li}");
+    }
+
+    #[test]
+    fn sun_samples_stay_in_its_disk() {
+        let sun = DirectionalLight::new(Vec3::new(0., -1., 0.), Vec3::new(1., 1., 1.))
+            .with_angular_diameter(10.);
+        let cos_max = 5f32.to_radians().cos();
+        for _ in 0..100 {
+            let sample = sun.sample_li(Vec3::default()).unwrap();
+            assert!(sample.direction.y() >= cos_max - 1e-5);
+            assert_eq!(sample.distance, INFINITY);
+        }
+    }
+
+    #[test]
+    fn sun_photons_cover_the_scene() {
+        let sun = DirectionalLight::new(Vec3::new(0., -1., 0.), Vec3::new(1., 1., 1.));
+        let center = Vec3::new(1., 0., 0.);
+        for _ in 0..100 {
+            let (power, ray) = sun.sample_le(center, 2.).unwrap();
+            assert_eq!(power, sun.power(2.));
+            assert_eq!(ray.direction(), Vec3::new(0., -1., 0.));
+            // Starts above the scene, within its radius of the axis
+            let origin = ray.origin() - center;
+            assert!((origin.y() - 2.).abs() < 1e-5);
+            assert!(origin.x().hypot(origin.z()) <= 2. + 1e-5);
+        }
+        assert!(sun.sample_le(center, 0.).is_none());
+    }
+}
@@ -0,0 +1,527 @@
+use super::{
+    aabb::Aabb,
+    hittable::{hit_opaque, Hit, Hittable},
+    hittable_list::HittableList,
+    interval::Interval,
+    ray::Ray,
+    stats::{self, Counter},
+    utils::{random_f32, INFINITY, PI},
+    vec3::Vec3,
+};
+
+/// Surface samples used to estimate the power of each light.
+const POWER_SAMPLES: usize = 16;
+
+/// Where a group of lights is, how much they emit and which way they face.
+#[derive(Clone, Copy, Debug)]
+struct LightBounds {
+    /// `None` for lights without a bounding box, they matter everywhere.
+    bounds: Option<Aabb>,
+    phi: f32,
+    /// Cone holding every emitting normal, a cosine of -1 is all directions.
+    w: Vec3,
+    cos_theta_o: f32,
+}
+
+impl LightBounds {
+    fn new(light: &dyn Hittable, phi: f32, two_sided: bool) -> Self {
+        let bounds = light.bounding_box().map(|b| b.padded());
+        let (w, cos_theta_o) = light
+            .normal_bounds()
+            .filter(|_| !two_sided)
+            .unwrap_or((Vec3::new(0., 0., 1.), -1.));
+        Self {
+            bounds,
+            phi,
+            w,
+            cos_theta_o,
+        }
+    }
+
+    fn union(a: &LightBounds, b: &LightBounds) -> Self {
+        // Lights that emit nothing shouldn't widen anything
+        if a.phi == 0. {
+            return *b;
+        }
+        if b.phi == 0. {
+            return *a;
+        }
+        let bounds = match (a.bounds, b.bounds) {
+            (Some(a), Some(b)) => Some(Aabb::surrounding(&a, &b)),
+            _ => None,
+        };
+        let (w, cos_theta_o) = cone_union((a.w, a.cos_theta_o), (b.w, b.cos_theta_o));
+        Self {
+            bounds,
+            phi: a.phi + b.phi,
+            w,
+            cos_theta_o,
+        }
+    }
+
+    fn centroid(&self) -> Vec3 {
+        self.bounds.map(|b| b.centroid()).unwrap_or_default()
+    }
+
+    /// Conservative estimate of the light arriving at `p`, after pbrt-v4.
+    /// Only the position is known, so there is no cosine at the receiver.
+    fn importance(&self, p: Vec3) -> f32 {
+        let Some(bounds) = self.bounds else {
+            return self.phi;
+        };
+        let pc = bounds.centroid();
+        let radius = bounds.diagonal().length() / 2.;
+        let distance_squared = (p - pc).length_squared();
+        // Keep lights close to `p` from blowing up
+        let d2 = distance_squared.max(radius);
+        // From inside the bounds any normal could face `p`
+        if self.cos_theta_o <= -1. || distance_squared <= radius * radius {
+            return self.phi / d2;
+        }
+
+        let wi = Vec3::unit_vector(p - pc);
+        let cos_theta_w = self.w.dot(wi);
+        // Angle the bounds subtend from `p`
+        let cos_theta_b = (1. - radius * radius / distance_squared).max(0.).sqrt();
+        // Smallest angle between `wi` and any normal, less the bounds' spread
+        let cos_theta_x = cos_sub_clamped(cos_theta_w, self.cos_theta_o);
+        let cos_theta = cos_sub_clamped(cos_theta_x, cos_theta_b);
+        // Diffuse emitters only light their front hemisphere
+        if cos_theta <= 0. {
+            return 0.;
+        }
+        self.phi * cos_theta / d2
+    }
+}
+
+/// cos(max(0, a - b)) from the cosines of a and b.
+fn cos_sub_clamped(cos_a: f32, cos_b: f32) -> f32 {
+    if cos_a >= cos_b {
+        return 1.;
+    }
+    let sin_a = (1. - cos_a * cos_a).max(0.).sqrt();
+    let sin_b = (1. - cos_b * cos_b).max(0.).sqrt();
+    cos_a * cos_b + sin_a * sin_b
+}
+
+/// Smallest cone around two cones, each an axis and half angle cosine.
+fn cone_union(a: (Vec3, f32), b: (Vec3, f32)) -> (Vec3, f32) {
+    let everywhere = (a.0, -1.);
+    let theta_a = a.1.clamp(-1., 1.).acos();
+    let theta_b = b.1.clamp(-1., 1.).acos();
+    let theta_d = a.0.dot(b.0).clamp(-1., 1.).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return a;
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return b;
+    }
+
+    let theta_o = (theta_a + theta_d + theta_b) / 2.;
+    if theta_o >= PI {
+        return everywhere;
+    }
+    // Turn a's axis towards b's until the cone just reaches both
+    let axis = Vec3::cross(a.0, b.0);
+    if axis.length_squared() < 1e-12 {
+        return everywhere;
+    }
+    let theta_r = theta_o - theta_a;
+    let w = a.0 * theta_r.cos() + Vec3::cross(Vec3::unit_vector(axis), a.0) * theta_r.sin();
+    (Vec3::unit_vector(w), theta_o.cos())
+}
+
+#[derive(Clone, Copy, Debug)]
+enum NodeKind {
+    Leaf(usize),
+    /// First child follows the node, this is the index of the second.
+    Interior(usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bounds: LightBounds,
+    kind: NodeKind,
+}
+
+/// Many-light sampler. Groups the lights in a tree of power, position and
+/// orientation bounds and walks it towards the lights likely to matter at
+/// the shading point, instead of picking uniformly like `HittableList`.
+/// Light paths (`sample_surface`) start on lights picked by power.
+pub struct LightBvh {
+    lights: Vec<Box<dyn Hittable>>,
+    nodes: Vec<Node>,
+    /// Running sum of light power, for `sample_surface`.
+    power_cdf: Vec<f32>,
+}
+
+impl LightBvh {
+    pub fn new(lights: HittableList) -> Self {
+        let lights = lights.objects;
+        let known: Vec<Option<(f32, bool)>> =
+            lights.iter().map(|l| estimate_power(l.as_ref())).collect();
+        // Lights that can't be sampled by area get an average share
+        let (sum, count) = known
+            .iter()
+            .flatten()
+            .fold((0., 0), |(s, c), (phi, _)| (s + phi, c + 1));
+        let fallback = if count > 0 { sum / count as f32 } else { 1. };
+
+        let mut power_cdf = Vec::with_capacity(lights.len());
+        let mut leaves = Vec::with_capacity(lights.len());
+        let mut total = 0.;
+        for (i, light) in lights.iter().enumerate() {
+            let (phi, two_sided) = known[i].unwrap_or((fallback, false));
+            total += phi;
+            power_cdf.push(total);
+            leaves.push((i, LightBounds::new(light.as_ref(), phi, two_sided)));
+        }
+
+        let mut bvh = Self {
+            lights,
+            nodes: Vec::new(),
+            power_cdf,
+        };
+        if !leaves.is_empty() {
+            bvh.build(&mut leaves);
+        }
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// Median split on the longest axis of the centroids. Returns the
+    /// index of the subtree's root.
+    fn build(&mut self, leaves: &mut [(usize, LightBounds)]) -> usize {
+        let index = self.nodes.len();
+        if let [(light, bounds)] = leaves {
+            self.nodes.push(Node {
+                bounds: *bounds,
+                kind: NodeKind::Leaf(*light),
+            });
+            return index;
+        }
+
+        let centroids = leaves
+            .iter()
+            .map(|(_, b)| b.centroid())
+            .fold(None, |acc: Option<Aabb>, c| {
+                let point = Aabb::new(c, c);
+                Some(acc.map_or(point, |b| Aabb::surrounding(&b, &point)))
+            })
+            .expect("at least two lights");
+        let axis = centroids.longest_axis();
+        leaves.sort_by(|(_, a), (_, b)| a.centroid()[axis].total_cmp(&b.centroid()[axis]));
+
+        // Placeholder until both children are built
+        self.nodes.push(Node {
+            bounds: leaves[0].1,
+            kind: NodeKind::Leaf(leaves[0].0),
+        });
+        let (left, right) = leaves.split_at_mut(leaves.len() / 2);
+        let first = self.build(left);
+        let second = self.build(right);
+        self.nodes[index] = Node {
+            bounds: LightBounds::union(&self.nodes[first].bounds, &self.nodes[second].bounds),
+            kind: NodeKind::Interior(second),
+        };
+        index
+    }
+
+    /// Probabilities of taking each child of interior node `node` at `p`.
+    fn child_pmf(&self, node: usize, second: usize, p: Vec3) -> Option<(f32, f32)> {
+        let a = self.nodes[node + 1].bounds.importance(p);
+        let b = self.nodes[second].bounds.importance(p);
+        if a + b <= 0. {
+            return None;
+        }
+        Some((a / (a + b), b / (a + b)))
+    }
+
+    /// Picks a light for shading point `p`, with its probability.
+    fn pick(&self, p: Vec3) -> Option<(usize, f32)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut node = 0;
+        let mut pmf = 1.;
+        loop {
+            stats::count(Counter::BvhNodes);
+            match self.nodes[node].kind {
+                NodeKind::Leaf(light) => return Some((light, pmf)),
+                NodeKind::Interior(second) => {
+                    let (pa, pb) = self.child_pmf(node, second, p)?;
+                    if random_f32() < pa {
+                        node += 1;
+                        pmf *= pa;
+                    } else {
+                        node = second;
+                        pmf *= pb;
+                    }
+                }
+            }
+        }
+    }
+
+    /// `pdf_value` summed over the lights `ray` may reach below `node`.
+    fn pdf_below(&self, node: usize, ray: &Ray, pmf: f32) -> f32 {
+        stats::count(Counter::BvhNodes);
+        match self.nodes[node].kind {
+            NodeKind::Leaf(light) => {
+                pmf * self.lights[light].pdf_value(ray.origin(), ray.direction())
+            }
+            NodeKind::Interior(second) => {
+                let Some((pa, pb)) = self.child_pmf(node, second, ray.origin()) else {
+                    return 0.;
+                };
+                [(node + 1, pa), (second, pb)]
+                    .into_iter()
+                    .filter(|&(child, p)| p > 0. && self.reaches(child, ray))
+                    .map(|(child, p)| self.pdf_below(child, ray, pmf * p))
+                    .sum()
+            }
+        }
+    }
+
+    fn reaches(&self, node: usize, ray: &Ray) -> bool {
+        match self.nodes[node].bounds.bounds {
+            Some(b) => b.hit(ray, &Interval::new(0., INFINITY)),
+            None => true,
+        }
+    }
+
+    fn hit_below<'a>(&'a self, node: usize, ray: &Ray, interval: &mut Interval) -> Option<Hit<'a>> {
+        stats::count(Counter::BvhNodes);
+        if !self.reaches(node, ray) {
+            return None;
+        }
+        match self.nodes[node].kind {
+            NodeKind::Leaf(light) => {
+                let mut hit = hit_opaque(self.lights[light].as_ref(), ray, interval)?;
+                hit.object_id = light as u32 + 1;
+                interval.max = hit.t;
+                Some(hit)
+            }
+            NodeKind::Interior(second) => {
+                let first = self.hit_below(node + 1, ray, interval);
+                self.hit_below(second, ray, interval).or(first)
+            }
+        }
+    }
+
+    fn power_pmf(&self, light: usize) -> f32 {
+        let total = self.power_cdf.last().copied().unwrap_or(0.);
+        let below = if light == 0 {
+            0.
+        } else {
+            self.power_cdf[light - 1]
+        };
+        (self.power_cdf[light] - below) / total
+    }
+}
+
+/// Emitted power of a light from its own surface samples, assuming diffuse
+/// emission, and whether its back faces emit too. `None` when the light
+/// can't be sampled by area.
+pub fn estimate_power(light: &dyn Hittable) -> Option<(f32, bool)> {
+    let mut sum = 0.;
+    let mut two_sided = false;
+    for _ in 0..POWER_SAMPLES {
+        let (hit, pdf) = light.sample_surface()?;
+        if pdf <= 0. {
+            return None;
+        }
+        let n = hit.normal;
+        let front = hit.material.emitted(&Ray::new(hit.p + n, -n), &hit);
+        let back = Hit {
+            normal: -n,
+            front_face: false,
+            ..hit
+        };
+        let back = back.material.emitted(&Ray::new(back.p - n, n), &back);
+        two_sided |= back != Vec3::default();
+        let le = front + back;
+        sum += (le.x() + le.y() + le.z()) / 3. * PI / pdf;
+    }
+    Some((sum / POWER_SAMPLES as f32, two_sided))
+}
+
+impl Hittable for LightBvh {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit<'_>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        self.hit_below(0, ray, &mut Interval::new(interval.min, interval.max))
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        if self.nodes.is_empty() {
+            return 0.;
+        }
+        let ray = Ray::new(origin, direction);
+        if !self.reaches(0, &ray) {
+            return 0.;
+        }
+        self.pdf_below(0, &ray, 1.)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        match self.pick(origin) {
+            Some((light, _)) => self.lights[light].random(origin),
+            None => Vec3::new(1., 0., 0.),
+        }
+    }
+
+    fn sample_surface(&self) -> Option<(Hit<'_>, f32)> {
+        let total = *self.power_cdf.last()?;
+        if total <= 0. {
+            return None;
+        }
+        let target = random_f32() * total;
+        let light = self
+            .power_cdf
+            .partition_point(|&c| c <= target)
+            .min(self.lights.len() - 1);
+        let (mut hit, pdf) = self.lights[light].sample_surface()?;
+        hit.object_id = light as u32 + 1;
+        Some((hit, pdf * self.power_pmf(light)))
+    }
+
+    fn surface_pdf(&self, p: Vec3) -> f32 {
+        if self.power_cdf.last().is_none_or(|&total| total <= 0.) {
+            return 0.;
+        }
+        self.nodes
+            .iter()
+            .filter_map(|node| match node.kind {
+                NodeKind::Leaf(light) => Some((light, node.bounds.bounds)),
+                NodeKind::Interior(_) => None,
+            })
+            .filter(|(_, bounds)| bounds.is_none_or(|b| b.contains(p)))
+            .map(|(light, _)| self.power_pmf(light) * self.lights[light].surface_pdf(p))
+            .sum()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first()?.bounds.bounds
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::{material::DiffuseLight, mesh::TriangleMesh, sphere::Sphere};
+
+    fn lamp(center: Vec3, radius: f32, power: f32) -> Box<dyn Hittable> {
+        Box::new(Sphere::new(
+            center,
+            radius,
+            DiffuseLight::new(Vec3::new(power, power, power)),
+        ))
+    }
+
+    /// Index of the light a direction from `p` points at.
+    fn target(bvh: &LightBvh, p: Vec3, direction: Vec3) -> Option<usize> {
+        let hit = bvh.hit(&Ray::new(p, direction), &Interval::new(0.001, INFINITY))?;
+        Some(hit.object_id as usize - 1)
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let mut lights = HittableList::new();
+        lights.push(lamp(Vec3::new(3., 0., 0.), 1., 4.));
+        lights.push(lamp(Vec3::new(-4., 1., 0.), 1.5, 1.));
+        lights.push(lamp(Vec3::new(0., 0., 5.), 1., 10.));
+        let bvh = LightBvh::new(lights);
+
+        let p = Vec3::new(0., 0.5, 0.);
+        let n = 400;
+        let mut sum = 0.;
+        for i in 0..n {
+            // Equal area bands in z, so every cell is the same solid angle
+            let z = 1. - 2. * (i as f32 + 0.5) / n as f32;
+            let r = (1. - z * z).sqrt();
+            for j in 0..2 * n {
+                let phi = PI * (j as f32 + 0.5) / n as f32;
+                sum += bvh.pdf_value(p, Vec3::new(r * phi.cos(), r * phi.sin(), z));
+            }
+        }
+        let integral = sum * 4. * PI / (2 * n * n) as f32;
+        assert!((integral - 1.).abs() < 2e-2, "{integral}");
+    }
+
+    #[test]
+    fn prefers_close_bright_lights() {
+        let mut lights = HittableList::new();
+        lights.push(lamp(Vec3::new(2., 0., 0.), 0.5, 10.));
+        lights.push(lamp(Vec3::new(-20., 0., 0.), 0.5, 1.));
+        let bvh = LightBvh::new(lights);
+
+        let p = Vec3::default();
+        let near = (0..1000)
+            .filter(|_| target(&bvh, p, bvh.random(p)) == Some(0))
+            .count();
+        assert!(near > 950, "{near}");
+    }
+
+    #[test]
+    fn skips_lights_facing_away() {
+        let light = || DiffuseLight::new(Vec3::new(1., 1., 1.));
+        let mut lights = HittableList::new();
+        // Faces down onto the origin
+        lights.push(Box::new(TriangleMesh::quad(
+            Vec3::new(1., 2., 0.),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0., 0., 1.),
+            light(),
+        )));
+        // Faces up, away from it
+        lights.push(Box::new(TriangleMesh::quad(
+            Vec3::new(-2., 2., 0.),
+            Vec3::new(0., 0., 1.),
+            Vec3::new(1., 0., 0.),
+            light(),
+        )));
+        let bvh = LightBvh::new(lights);
+
+        let p = Vec3::default();
+        assert!((0..100).all(|_| target(&bvh, p, bvh.random(p)) == Some(0)));
+        // An unbiased estimate still needs the pdf of where a sample went
+        let facing = Vec3::new(1.5, 2., 0.5);
+        assert!(bvh.pdf_value(p, facing) > 0.);
+        assert_eq!(bvh.pdf_value(p, Vec3::new(-1.5, 2., 0.5)), 0.);
+
+        // Two sided ones light both ways, with twice the power
+        let mut lights = HittableList::new();
+        lights.push(Box::new(TriangleMesh::quad(
+            Vec3::new(-2., 2., 0.),
+            Vec3::new(0., 0., 1.),
+            Vec3::new(1., 0., 0.),
+            DiffuseLight::two_sided(Vec3::new(1., 1., 1.)),
+        )));
+        let bvh = LightBvh::new(lights);
+        assert!(bvh.pdf_value(p, Vec3::new(-1.5, 2., 0.5)) > 0.);
+        let (phi, two_sided) = estimate_power(bvh.lights[0].as_ref()).unwrap();
+        assert!((phi - 2. * PI).abs() < 1e-4 && two_sided, "{phi}");
+    }
+
+    #[test]
+    fn surface_samples_follow_power() {
+        let mut lights = HittableList::new();
+        lights.push(lamp(Vec3::new(2., 0., 0.), 1., 3.));
+        lights.push(lamp(Vec3::new(-2., 0., 0.), 1., 1.));
+        let bvh = LightBvh::new(lights);
+
+        let (hit, pdf) = bvh.sample_surface().unwrap();
+        let expected = bvh.power_pmf(hit.object_id as usize - 1) / (4. * PI);
+        assert!((pdf - expected).abs() < 1e-5);
+        assert!((bvh.power_pmf(0) - 0.75).abs() < 1e-5);
+        assert!((bvh.surface_pdf(hit.p) - pdf).abs() < 1e-5);
+    }
+}
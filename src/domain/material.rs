@@ -1,58 +1,369 @@
-use libc::rand;
-use rand::Rng;
+use super::{
+    hittable::Hit,
+    medium::Medium,
+    microfacet::{reflect, refract, TrowbridgeReitz},
+    onb::Onb,
+    ray::Ray,
+    spectrum::rgb_at,
+    texture::{SolidColor, Texture},
+    utils::{random_f32, PI},
+    vec3::Vec3,
+};
 
-use super::{hittable::Hit, ray::Ray, utils::random_f32, vec3::Vec3};
-
-pub trait Material {
+/// Surface response to light. On spectral rays (`ray.wavelength() > 0`)
+/// colors are upsampled with `rgb_at` and scattered rays keep the wavelength.
+pub trait Material: Send + Sync {
     fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)>;
+
+    /// Radiance given off by the surface itself.
+    fn emitted(&self, _ray: &Ray, _hit: &Hit) -> Vec3 {
+        Vec3::new(0., 0., 0.)
+    }
+
+    /// Medium filling the inside of the object, entered by rays scattered
+    /// through the surface.
+    fn interior(&self) -> Option<Medium> {
+        None
+    }
+
+    /// Opacity at the hit, aggregates skip hits where it is below one with
+    /// that probability.
+    fn alpha(&self, _hit: &Hit) -> f32 {
+        1.
+    }
+
+    /// Normal the material shades with, for the normal AOV. Differs from
+    /// `hit.normal` when the material perturbs it.
+    fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        hit.normal
+    }
+
+    /// Base color of the surface, for the albedo AOV.
+    fn albedo(&self, _hit: &Hit) -> Vec3 {
+        Vec3::new(0., 0., 0.)
+    }
+
+    /// BSDF times cosine towards `scattered`, used when the direction was
+    /// picked by someone else (e.g. light sampling).
+    fn eval(&self, _ray: &Ray, _hit: &Hit, _scattered: &Ray) -> Vec3 {
+        Vec3::new(0., 0., 0.)
+    }
+
+    /// Solid angle pdf of `scatter` producing `scattered`. Zero means the
+    /// material is specular and can't be sampled towards a light.
+    fn scattering_pdf(&self, _ray: &Ray, _hit: &Hit, _scattered: &Ray) -> f32 {
+        0.
+    }
+}
+
+/// Boxed materials, for picking one at runtime.
+impl<M: Material + ?Sized> Material for Box<M> {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        (**self).scatter(ray, hit)
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        (**self).emitted(ray, hit)
+    }
+
+    fn interior(&self) -> Option<Medium> {
+        (**self).interior()
+    }
+
+    fn alpha(&self, hit: &Hit) -> f32 {
+        (**self).alpha(hit)
+    }
+
+    fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        (**self).shading_normal(hit)
+    }
+
+    fn albedo(&self, hit: &Hit) -> Vec3 {
+        (**self).albedo(hit)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        (**self).eval(ray, hit, scattered)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        (**self).scattering_pdf(ray, hit, scattered)
+    }
 }
 
 pub struct Lambertian {
-    albedo: Vec3,
+    albedo: Box<dyn Texture>,
 }
 impl Lambertian {
     pub fn new(albedo: Vec3) -> Self {
-        Self { albedo }
+        Self::textured(SolidColor::new(albedo))
+    }
+
+    /// Albedo looked up in `texture` at the hit's uv.
+    pub fn textured(texture: impl Texture + 'static) -> Self {
+        Self {
+            albedo: Box::new(texture),
+        }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
         let mut scatter_direction = hit.normal + Vec3::random_unit_vector();
         if scatter_direction.near_zero() {
             scatter_direction = hit.normal;
         }
-        let scattered = Ray::new(hit.p, scatter_direction);
-        let attenuation = self.albedo;
-        return Some((attenuation, scattered));
+        let scattered = Ray::new(hit.p, scatter_direction).with_wavelength(ray.wavelength());
+        let attenuation = rgb_at(self.albedo(hit), ray.wavelength());
+        Some((attenuation, scattered))
+    }
+
+    fn albedo(&self, hit: &Hit) -> Vec3 {
+        self.albedo.value(hit.u, hit.v, hit.p)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        rgb_at(self.albedo(hit), ray.wavelength()) * self.scattering_pdf(ray, hit, scattered)
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        let cosine = Vec3::dot(hit.normal, Vec3::unit_vector(scattered.direction()));
+        if cosine < 0. {
+            0.
+        } else {
+            cosine / PI
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DiffuseLight {
+    emit: Vec3,
+    two_sided: bool,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Vec3) -> Self {
+        Self {
+            emit,
+            two_sided: false,
+        }
+    }
+
+    /// Emits from the back face too, like pbrt's `"bool twosided"`.
+    pub fn two_sided(emit: Vec3) -> Self {
+        Self {
+            emit,
+            two_sided: true,
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &Hit) -> Option<(Vec3, Ray)> {
+        None
+    }
+
+    fn albedo(&self, _hit: &Hit) -> Vec3 {
+        self.emit
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        if hit.front_face || self.two_sided {
+            rgb_at(self.emit, ray.wavelength())
+        } else {
+            Vec3::new(0., 0., 0.)
+        }
+    }
+}
+
+/// `surface` glowing with what `light` emits. Emitters of imported scenes
+/// keep reflecting through their material.
+pub struct Emissive<L: Material, M: Material> {
+    light: L,
+    surface: M,
+}
+
+impl<L: Material, M: Material> Emissive<L, M> {
+    pub fn new(light: L, surface: M) -> Self {
+        Self { light, surface }
+    }
+}
+
+impl<L: Material, M: Material> Material for Emissive<L, M> {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        self.surface.scatter(ray, hit)
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        self.light.emitted(ray, hit)
+    }
+
+    fn interior(&self) -> Option<Medium> {
+        self.surface.interior()
+    }
+
+    fn alpha(&self, hit: &Hit) -> f32 {
+        self.surface.alpha(hit)
+    }
+
+    fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        self.surface.shading_normal(hit)
+    }
+
+    fn albedo(&self, hit: &Hit) -> Vec3 {
+        self.surface.albedo(hit)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        self.surface.eval(ray, hit, scattered)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        self.surface.scattering_pdf(ray, hit, scattered)
+    }
+}
+
+/// Index of refraction as a function of wavelength.
+#[derive(Clone, Copy, Debug)]
+pub enum Ior {
+    Constant(f32),
+    /// `n = a + b / λ²`, λ in micrometres.
+    Cauchy {
+        a: f32,
+        b: f32,
+    },
+    /// `n² = 1 + Σ b λ² / (λ² - c)`, λ in micrometres.
+    Sellmeier {
+        b: [f32; 3],
+        c: [f32; 3],
+    },
+}
+
+impl Ior {
+    /// Sodium d-line, used when an RGB ray carries no wavelength.
+    const D_LINE: f32 = 587.6;
+
+    /// Schott N-BK7 crown glass.
+    pub fn bk7() -> Self {
+        Ior::Sellmeier {
+            b: [1.039_612, 0.231_792_34, 1.010_469_5],
+            c: [0.006_000_699, 0.020_017_914, 103.560_65],
+        }
+    }
+
+    /// Dense flint glass, strongly dispersive.
+    pub fn flint() -> Self {
+        Ior::Cauchy {
+            a: 1.7280,
+            b: 0.01342,
+        }
+    }
+
+    pub fn diamond() -> Self {
+        Ior::Sellmeier {
+            b: [0.3306, 4.3356, 0.],
+            c: [0.030_625, 0.011_236, 0.],
+        }
+    }
+
+    /// Index at `wavelength` nm, or at the d-line for zero.
+    pub fn at(&self, wavelength: f32) -> f32 {
+        let nm = if wavelength > 0. {
+            wavelength
+        } else {
+            Self::D_LINE
+        };
+        let um2 = (nm / 1000.).powi(2);
+        match *self {
+            Ior::Constant(n) => n,
+            Ior::Cauchy { a, b } => a + b / um2,
+            Ior::Sellmeier { b, c } => {
+                let sum: f32 = (0..3).map(|i| b[i] * um2 / (um2 - c[i])).sum();
+                (1. + sum).sqrt()
+            }
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct Dialectric {
-    ir: f32,
+    ior: Ior,
 }
 
 impl Dialectric {
     pub fn new(index_of_refraction: f32) -> Self {
         Self {
-            ir: index_of_refraction,
+            ior: Ior::Constant(index_of_refraction),
         }
     }
+
+    /// Glass whose index varies with wavelength, dispersing spectral rays.
+    pub fn with_dispersion(ior: Ior) -> Self {
+        Self { ior }
+    }
+}
+
+/// Unpolarized Fresnel reflectance of a dielectric interface, `eta` being
+/// the index on the far side over the near side.
+pub fn fr_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let (cos_i, eta) = if cos_i < 0. {
+        (-cos_i, 1. / eta)
+    } else {
+        (cos_i.min(1.), eta)
+    };
+    let sin2_t = (1. - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1. {
+        return 1.;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.
 }
 
-fn schlick(cosine: f32, ir: f32) -> f32 {
-    let r0 = ((1.0 - ir) / (1.0 + ir)).powi(2);
-    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+/// Reflectance of a thin film of index `film_ior` and `thickness` nm on a
+/// substrate of index `base_ior`, at `wavelength` nm in air. `cos_i` is the
+/// angle in air. Sums the reflections off both film faces with their phase
+/// difference (Airy), averaged over both polarizations.
+pub fn fr_thin_film(
+    cos_i: f32,
+    film_ior: f32,
+    thickness: f32,
+    base_ior: f32,
+    wavelength: f32,
+) -> f32 {
+    let cos1 = cos_i.clamp(0., 1.);
+    let sin2_1 = 1. - cos1 * cos1;
+    let sin2_2 = sin2_1 / (film_ior * film_ior);
+    let sin2_3 = sin2_1 / (base_ior * base_ior);
+    if sin2_2 >= 1. || sin2_3 >= 1. {
+        return 1.;
+    }
+    let cos2 = (1. - sin2_2).sqrt();
+    let cos3 = (1. - sin2_3).sqrt();
+
+    let phase = 4. * PI * film_ior * thickness * cos2 / wavelength;
+    let airy = |r12: f32, r23: f32| {
+        let cross = 2. * r12 * r23 * phase.cos();
+        (r12 * r12 + r23 * r23 + cross) / (1. + r12 * r12 * r23 * r23 + cross)
+    };
+    let (n1, n2, n3) = (1., film_ior, base_ior);
+    let s = airy(
+        (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2),
+        (n2 * cos2 - n3 * cos3) / (n2 * cos2 + n3 * cos3),
+    );
+    let p = airy(
+        (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2),
+        (n3 * cos2 - n2 * cos3) / (n3 * cos2 + n2 * cos3),
+    );
+    ((s + p) / 2.).clamp(0., 1.)
 }
 
 impl Material for Dialectric {
     fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
-        let refraction_ratio = if hit.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
+        let ir = self.ior.at(ray.wavelength());
+        let refraction_ratio = if hit.front_face { 1.0 / ir } else { ir };
 
         let unit_direction = Vec3::unit_vector(ray.direction());
 
@@ -60,7 +371,7 @@ impl Material for Dialectric {
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let will_reflect = random_f32() < schlick(cos_theta, refraction_ratio);
+        let will_reflect = random_f32() < fr_dielectric(cos_theta, 1. / refraction_ratio);
 
         let direction = if cannot_refract || will_reflect {
             Vec3::reflect(unit_direction, hit.normal)
@@ -68,10 +379,131 @@ impl Material for Dialectric {
             Vec3::refract(unit_direction, hit.normal, refraction_ratio)
         };
 
-        let scattered = Ray::new(hit.p, direction);
+        let scattered = Ray::new(hit.p, direction).with_wavelength(ray.wavelength());
 
         Some((Vec3::new(1.0, 1.0, 1.0), scattered))
     }
+
+    fn albedo(&self, _hit: &Hit) -> Vec3 {
+        Vec3::new(1., 1., 1.)
+    }
+}
+
+/// Smooth dielectric with a thin transparent film on top, iridescent as the
+/// film's reflections interfere. `thickness` is in nm. Spectral rays see
+/// their own wavelength, RGB rays three representative ones.
+#[derive(Clone)]
+pub struct ThinFilm {
+    thickness: f32,
+    film_ior: f32,
+    base: Ior,
+}
+
+impl ThinFilm {
+    /// Wavelengths standing in for the RGB channels.
+    const RGB_WAVELENGTHS: [f32; 3] = [630., 532., 465.];
+
+    pub fn new(thickness: f32, film_ior: f32, base: Ior) -> Self {
+        Self {
+            thickness,
+            film_ior,
+            base,
+        }
+    }
+
+    /// Soapy water with air on both sides.
+    pub fn soap_bubble(thickness: f32) -> Self {
+        Self::new(thickness, 1.33, Ior::Constant(1.))
+    }
+
+    /// Quarter wave magnesium fluoride coating on crown glass.
+    pub fn coated_lens() -> Self {
+        Self::new(100., 1.38, Ior::bk7())
+    }
+
+    fn reflectance(&self, cos_i: f32, base_ior: f32, wavelength: f32) -> Vec3 {
+        let r = |nm| fr_thin_film(cos_i, self.film_ior, self.thickness, base_ior, nm);
+        if wavelength > 0. {
+            let r = r(wavelength);
+            Vec3::new(r, r, r)
+        } else {
+            let [red, green, blue] = Self::RGB_WAVELENGTHS;
+            Vec3::new(r(red), r(green), r(blue))
+        }
+    }
+}
+
+impl Material for ThinFilm {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        let ir = self.base.at(ray.wavelength());
+        let refraction_ratio = if hit.front_face { 1.0 / ir } else { ir };
+
+        let unit_direction = Vec3::unit_vector(ray.direction());
+        let cos_theta = (-unit_direction).dot(hit.normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+
+        let reflect = |attenuation| {
+            let direction = Vec3::reflect(unit_direction, hit.normal);
+            let scattered = Ray::new(hit.p, direction).with_wavelength(ray.wavelength());
+            Some((attenuation, scattered))
+        };
+        if refraction_ratio * sin_theta > 1.0 {
+            return reflect(Vec3::new(1., 1., 1.));
+        }
+
+        // Reflectance from either side is the same, measured by the angle in air
+        let cos_air = if hit.front_face {
+            cos_theta
+        } else {
+            (1. - (ir * sin_theta).powi(2)).max(0.).sqrt()
+        };
+        let r = self.reflectance(cos_air, ir, ray.wavelength());
+        let p = (r.x() + r.y() + r.z()) / 3.;
+
+        if random_f32() < p {
+            reflect(r / p)
+        } else {
+            let direction = Vec3::refract(unit_direction, hit.normal, refraction_ratio);
+            let scattered = Ray::new(hit.p, direction).with_wavelength(ray.wavelength());
+            Some(((Vec3::new(1., 1., 1.) - r) / (1. - p), scattered))
+        }
+    }
+
+    fn albedo(&self, _hit: &Hit) -> Vec3 {
+        Vec3::new(1., 1., 1.)
+    }
+}
+
+/// Translucent solid like skin, wax or marble. A smooth dielectric boundary
+/// around a scattering interior that `PathIntegrator` random walks through;
+/// integrators ignoring media see clear glass.
+#[derive(Clone)]
+pub struct Subsurface {
+    boundary: Dialectric,
+    medium: Medium,
+}
+
+impl Subsurface {
+    pub fn new(index_of_refraction: f32, medium: Medium) -> Self {
+        Self {
+            boundary: Dialectric::new(index_of_refraction),
+            medium,
+        }
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        self.boundary.scatter(ray, hit)
+    }
+
+    fn interior(&self) -> Option<Medium> {
+        Some(self.medium)
+    }
+
+    fn albedo(&self, _hit: &Hit) -> Vec3 {
+        self.medium.sigma_s / self.medium.sigma_t()
+    }
 }
 
 pub struct Metal {
@@ -95,10 +527,275 @@ impl Material for Metal {
             reflected += Vec3::random_in_unit_sphere() * self.fuzz
         };
         if Vec3::dot(reflected, hit.normal) > 0.0 {
-            let scattered = Ray::new(hit.p, reflected);
-            Some((self.albedo, scattered))
+            let scattered = Ray::new(hit.p, reflected).with_wavelength(ray.wavelength());
+            Some((rgb_at(self.albedo, ray.wavelength()), scattered))
         } else {
             None
         }
     }
+
+    fn albedo(&self, _hit: &Hit) -> Vec3 {
+        self.albedo
+    }
+}
+
+/// Fresnel reflectance of a conductor with complex index `eta + ik`.
+fn fr_conductor(cos_i: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = cos_i.clamp(0., 1.).powi(2);
+    let sin2 = 1. - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+    let t2 = 2. * cos_i.clamp(0., 1.) * a;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    (rp + rs) / 2.
+}
+
+/// Outgoing direction of `ray` in the shading frame of `hit`.
+pub fn shading_frame(ray: &Ray, hit: &Hit) -> (Onb, Vec3) {
+    let frame = Onb::build_from_w(hit.normal);
+    let wo = frame.to_local(-Vec3::unit_vector(ray.direction()));
+    (frame, wo)
+}
+
+/// Rough metal, a GGX microfacet conductor with a per channel complex index
+/// of refraction.
+#[derive(Clone)]
+pub struct Conductor {
+    eta: Vec3,
+    k: Vec3,
+    distribution: TrowbridgeReitz,
+}
+
+impl Conductor {
+    pub fn new(eta: Vec3, k: Vec3, roughness: f32) -> Self {
+        Self {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::new(roughness),
+        }
+    }
+
+    pub fn gold(roughness: f32) -> Self {
+        Self::new(
+            Vec3::new(0.143, 0.374, 1.442),
+            Vec3::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f32) -> Self {
+        Self::new(
+            Vec3::new(0.200, 0.924, 1.102),
+            Vec3::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminum(roughness: f32) -> Self {
+        Self::new(
+            Vec3::new(1.657, 0.880, 0.521),
+            Vec3::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    fn fresnel(&self, cos_i: f32) -> Vec3 {
+        Vec3::new(
+            fr_conductor(cos_i, self.eta.x(), self.k.x()),
+            fr_conductor(cos_i, self.eta.y(), self.k.y()),
+            fr_conductor(cos_i, self.eta.z(), self.k.z()),
+        )
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        let (frame, wo) = shading_frame(ray, hit);
+        if wo.z() <= 0. {
+            return None;
+        }
+        let wm = self.distribution.sample_wm(wo, random_f32(), random_f32());
+        let wi = reflect(wo, wm);
+        if wi.z() <= 0. {
+            return None;
+        }
+
+        // f cos / pdf collapses to F G / G1
+        let g = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+        let attenuation = rgb_at(self.fresnel(wo.dot(wm)) * g, ray.wavelength());
+        let scattered = Ray::new(hit.p, frame.local(wi)).with_wavelength(ray.wavelength());
+        Some((attenuation, scattered))
+    }
+
+    fn albedo(&self, _hit: &Hit) -> Vec3 {
+        self.fresnel(1.)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        let (frame, wo) = shading_frame(ray, hit);
+        let wi = frame.to_local(Vec3::unit_vector(scattered.direction()));
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return Vec3::new(0., 0., 0.);
+        }
+        let wm = Vec3::unit_vector(wo + wi);
+        let d = self.distribution.d(wm);
+        let g = self.distribution.g(wo, wi);
+        let f = self.fresnel(wo.dot(wm)) * (d * g / (4. * wo.z()));
+        rgb_at(f, ray.wavelength())
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        let (frame, wo) = shading_frame(ray, hit);
+        let wi = frame.to_local(Vec3::unit_vector(scattered.direction()));
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return 0.;
+        }
+        let wm = Vec3::unit_vector(wo + wi);
+        self.distribution.d_visible(wo, wm) / (4. * wo.dot(wm).abs())
+    }
+}
+
+/// Frosted glass, a GGX microfacet dielectric that both reflects and
+/// transmits.
+#[derive(Clone)]
+pub struct RoughDielectric {
+    ior: Ior,
+    distribution: TrowbridgeReitz,
+}
+
+impl RoughDielectric {
+    pub fn new(ior: Ior, roughness: f32) -> Self {
+        Self {
+            ior,
+            distribution: TrowbridgeReitz::new(roughness),
+        }
+    }
+
+    /// Index on the far side of the surface over the near side.
+    fn eta(&self, ray: &Ray, hit: &Hit) -> f32 {
+        let ir = self.ior.at(ray.wavelength());
+        if hit.front_face {
+            ir
+        } else {
+            1. / ir
+        }
+    }
+
+    /// BSDF times cosine and the pdf of sampling `wi`, in the shading frame.
+    fn evaluate(&self, wo: Vec3, wi: Vec3, eta: f32) -> (f32, f32) {
+        let (cos_o, cos_i) = (wo.z(), wi.z());
+        if cos_o <= 0. || cos_i == 0. {
+            return (0., 0.);
+        }
+        let reflect = cos_i > 0.;
+
+        // Generalized half vector, facing the outgoing side
+        let etap = if reflect { 1. } else { eta };
+        let wm = wi * etap + wo;
+        if wm.near_zero() {
+            return (0., 0.);
+        }
+        let mut wm = Vec3::unit_vector(wm);
+        if wm.z() < 0. {
+            wm = -wm;
+        }
+        if wm.dot(wi) * cos_i < 0. || wm.dot(wo) * cos_o < 0. {
+            return (0., 0.);
+        }
+
+        let r = fr_dielectric(wo.dot(wm), eta);
+        let d = self.distribution.d(wm);
+        let g = self.distribution.g(wo, wi);
+        let d_visible = self.distribution.d_visible(wo, wm);
+        if reflect {
+            let f = d * g * r / (4. * cos_o);
+            let pdf = d_visible / (4. * wo.dot(wm).abs()) * r;
+            (f, pdf)
+        } else {
+            let denom = (wi.dot(wm) + wo.dot(wm) / eta).powi(2);
+            let f = d * g * (1. - r) * (wi.dot(wm) * wo.dot(wm) / (cos_o * denom)).abs();
+            let pdf = d_visible * wi.dot(wm).abs() / denom * (1. - r);
+            (f, pdf)
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        let (frame, wo) = shading_frame(ray, hit);
+        if wo.z() <= 0. {
+            return None;
+        }
+        let eta = self.eta(ray, hit);
+        let wm = self.distribution.sample_wm(wo, random_f32(), random_f32());
+
+        let wi = if random_f32() < fr_dielectric(wo.dot(wm), eta) {
+            let wi = reflect(wo, wm);
+            if wi.z() <= 0. {
+                return None;
+            }
+            wi
+        } else {
+            let wi = refract(wo, wm, eta)?;
+            if wi.z() >= 0. {
+                return None;
+            }
+            wi
+        };
+
+        // Fresnel cancels against the lobe choice, leaving G / G1
+        let g = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+        let scattered = Ray::new(hit.p, frame.local(wi)).with_wavelength(ray.wavelength());
+        Some((Vec3::new(g, g, g), scattered))
+    }
+
+    fn albedo(&self, _hit: &Hit) -> Vec3 {
+        Vec3::new(1., 1., 1.)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        let (frame, wo) = shading_frame(ray, hit);
+        let wi = frame.to_local(Vec3::unit_vector(scattered.direction()));
+        let (f, _) = self.evaluate(wo, wi, self.eta(ray, hit));
+        Vec3::new(f, f, f)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        let (frame, wo) = shading_frame(ray, hit);
+        let wi = frame.to_local(Vec3::unit_vector(scattered.direction()));
+        self.evaluate(wo, wi, self.eta(ray, hit)).1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exact_fresnel_at_normal_incidence() {
+        assert!((fr_dielectric(1., 1.5) - 0.04).abs() < 1e-6);
+        assert!((fr_dielectric(-1., 1.5) - 0.04).abs() < 1e-6);
+        assert_eq!(fr_dielectric(0.1, 1. / 1.5), 1.);
+    }
+
+    #[test]
+    fn vanishing_film_is_plain_fresnel() {
+        for cos in [1., 0.7, 0.3] {
+            let film = fr_thin_film(cos, 1.38, 0., 1.5, 550.);
+            assert!((film - fr_dielectric(cos, 1.5)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn quarter_wave_coating_cancels_reflection() {
+        // n = √1.5 at a quarter wave is a perfect anti-reflection coating
+        let n = 1.5_f32.sqrt();
+        let r = fr_thin_film(1., n, 550. / (4. * n), 1.5, 550.);
+        assert!(r < 1e-5, "{r}");
+    }
 }
@@ -0,0 +1,139 @@
+use super::{onb::Onb, spectrum::rgb_at, utils::PI, vec3::Vec3};
+
+/// Homogeneous participating medium filling the inside of an object.
+/// Coefficients are per unit distance, per RGB channel.
+#[derive(Clone, Copy, Debug)]
+pub struct Medium {
+    pub sigma_a: Vec3,
+    pub sigma_s: Vec3,
+    /// Henyey-Greenstein asymmetry, positive scatters forward.
+    pub g: f32,
+}
+
+impl Medium {
+    pub fn new(sigma_a: Vec3, sigma_s: Vec3, g: f32) -> Self {
+        Self {
+            sigma_a,
+            sigma_s,
+            g,
+        }
+    }
+
+    /// Medium whose single scattering `albedo` and `mean_free_path` (the
+    /// average distance between interactions) are given instead.
+    pub fn from_albedo(albedo: Vec3, mean_free_path: Vec3, g: f32) -> Self {
+        let sigma_t = Vec3::new(1., 1., 1.) / mean_free_path;
+        let sigma_s = albedo * sigma_t;
+        Self::new(sigma_t - sigma_s, sigma_s, g)
+    }
+
+    pub fn sigma_t(&self) -> Vec3 {
+        self.sigma_a + self.sigma_s
+    }
+
+    /// Share of light surviving `distance` through the medium.
+    pub fn transmittance(&self, distance: f32, wavelength: f32) -> Vec3 {
+        let sigma_t = rgb_at(self.sigma_t(), wavelength);
+        Vec3::new(
+            (-sigma_t.x() * distance).exp(),
+            (-sigma_t.y() * distance).exp(),
+            (-sigma_t.z() * distance).exp(),
+        )
+    }
+
+    /// Samples where a ray travelling up to `t_max` first interacts, by the
+    /// free flight distance of `channel`. Returns the distance, the path
+    /// factor, its pdf as if each channel had been sampled, and whether the
+    /// ray scattered before `t_max`. Callers combine the pdfs over the whole
+    /// path, per event weights vary wildly for colored media.
+    pub fn sample_distance(
+        &self,
+        t_max: f32,
+        channel: usize,
+        u: f32,
+        wavelength: f32,
+    ) -> (f32, Vec3, Vec3, bool) {
+        let sigma_t = rgb_at(self.sigma_t(), wavelength);
+        let sigma_s = rgb_at(self.sigma_s, wavelength);
+        let density = match channel {
+            0 => sigma_t.x(),
+            1 => sigma_t.y(),
+            _ => sigma_t.z(),
+        };
+        let t = if density > 0. {
+            -(1. - u).ln() / density
+        } else {
+            f32::INFINITY
+        };
+
+        if t < t_max {
+            let tr = self.transmittance(t, wavelength);
+            (t, sigma_s * tr, sigma_t * tr, true)
+        } else {
+            let tr = self.transmittance(t_max, wavelength);
+            (t_max, tr, tr, false)
+        }
+    }
+
+    /// Henyey-Greenstein phase function for the angle between the incoming
+    /// travel direction and the outgoing one.
+    pub fn phase(&self, cos_theta: f32) -> f32 {
+        let denom = 1. + self.g * self.g - 2. * self.g * cos_theta;
+        (1. - self.g * self.g) / (4. * PI * denom * denom.max(1e-8).sqrt())
+    }
+
+    /// New travel direction after scattering, drawn exactly from `phase`.
+    pub fn sample_phase(&self, direction: Vec3, u1: f32, u2: f32) -> Vec3 {
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1. - 2. * u1
+        } else {
+            let s = (1. - g * g) / (1. - g + 2. * g * u1);
+            (1. + g * g - s * s) / (2. * g)
+        }
+        .clamp(-1., 1.);
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * u2;
+        Onb::build_from_w(direction).local(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn phase_integrates_to_one() {
+        for g in [-0.5, 0., 0.8] {
+            let medium = Medium::new(Vec3::default(), Vec3::default(), g);
+            let n = 10_000;
+            let sum: f32 = (0..n)
+                .map(|i| {
+                    let cos = -1. + 2. * (i as f32 + 0.5) / n as f32;
+                    medium.phase(cos) * 2. * PI * (2. / n as f32)
+                })
+                .sum();
+            assert!((sum - 1.).abs() < 1e-3, "g {g}: {sum}");
+        }
+    }
+
+    #[test]
+    fn sampled_phase_has_mean_cosine_g() {
+        let medium = Medium::new(Vec3::default(), Vec3::default(), 0.6);
+        let forward = Vec3::new(0., 0., 1.);
+        let n = 100;
+        let mut sum = 0.;
+        for i in 0..n {
+            for j in 0..n {
+                let (u1, u2) = ((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                sum += medium.sample_phase(forward, u1, u2).dot(forward);
+            }
+        }
+        let mean = sum / (n * n) as f32;
+        assert!((mean - 0.6).abs() < 1e-2, "{mean}");
+    }
+}
@@ -0,0 +1,521 @@
+use super::{
+    aabb::Aabb,
+    hittable::{is_opaque, Hit, Hittable},
+    interval::Interval,
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    stats::{self, Counter},
+    utils::{random_f32, INFINITY},
+    vec3::Vec3,
+};
+
+/// Most triangles in a leaf of the mesh BVH.
+const LEAF_SIZE: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+enum NodeKind {
+    /// Triangles `order[start..end]`.
+    Leaf(usize, usize),
+    /// First child follows the node, `second` is the index of the other.
+    /// Both were split along `axis`.
+    Interior { second: usize, axis: usize },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bounds: Aabb,
+    kind: NodeKind,
+}
+
+/// Indexed triangles sharing one material, counter-clockwise winding facing
+/// out. Optional per-vertex normals give smooth shading and per-vertex uvs
+/// drive textures, otherwise each triangle maps to (0, 0), (1, 0), (1, 1).
+/// Rays and light queries find triangles through a BVH built with the mesh.
+pub struct TriangleMesh<M: Material> {
+    positions: Vec<Vec3>,
+    indices: Vec<[usize; 3]>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f32, f32)>>,
+    material: M,
+    material_id: u32,
+    /// Running sum of triangle areas, for picking one by area.
+    cdf: Vec<f32>,
+    nodes: Vec<Node>,
+    /// Triangle indices in leaf order.
+    order: Vec<usize>,
+}
+
+impl<M: Material> TriangleMesh<M> {
+    pub fn new(positions: Vec<Vec3>, indices: Vec<[usize; 3]>, material: M) -> Self {
+        let mut mesh = Self {
+            positions,
+            indices,
+            normals: None,
+            uvs: None,
+            material,
+            material_id: 0,
+            cdf: Vec::new(),
+            nodes: Vec::new(),
+            order: Vec::new(),
+        };
+        let mut total = 0.;
+        for i in 0..mesh.indices.len() {
+            let (p0, p1, p2) = mesh.triangle(i);
+            total += Vec3::cross(p1 - p0, p2 - p0).length() / 2.;
+            mesh.cdf.push(total);
+        }
+
+        let boxes: Vec<Aabb> = (0..mesh.len())
+            .map(|i| {
+                let (p0, p1, p2) = mesh.triangle(i);
+                Aabb::surrounding(&Aabb::new(p0, p1), &Aabb::new(p2, p2)).padded()
+            })
+            .collect();
+        let mut order: Vec<usize> = (0..mesh.len()).collect();
+        if !order.is_empty() {
+            Self::build(&mut mesh.nodes, &mut order, 0, &boxes);
+        }
+        mesh.order = order;
+        mesh
+    }
+
+    /// Median split on the longest axis of the centroids, like `LightBvh`.
+    /// `order` starts at `start` of the whole order. Returns the index of
+    /// the subtree's root.
+    fn build(nodes: &mut Vec<Node>, order: &mut [usize], start: usize, boxes: &[Aabb]) -> usize {
+        let index = nodes.len();
+        let bounds = order
+            .iter()
+            .map(|&i| boxes[i])
+            .reduce(|a, b| Aabb::surrounding(&a, &b))
+            .expect("at least one triangle");
+        if order.len() <= LEAF_SIZE {
+            nodes.push(Node {
+                bounds,
+                kind: NodeKind::Leaf(start, start + order.len()),
+            });
+            return index;
+        }
+
+        let centroids = order
+            .iter()
+            .map(|&i| Aabb::new(boxes[i].centroid(), boxes[i].centroid()))
+            .reduce(|a, b| Aabb::surrounding(&a, &b))
+            .expect("at least one triangle");
+        let axis = centroids.longest_axis();
+        let mid = order.len() / 2;
+        order.select_nth_unstable_by(mid, |&a, &b| {
+            boxes[a].centroid()[axis].total_cmp(&boxes[b].centroid()[axis])
+        });
+
+        // Placeholder until both children are built
+        nodes.push(Node {
+            bounds,
+            kind: NodeKind::Leaf(start, start),
+        });
+        let (left, right) = order.split_at_mut(mid);
+        Self::build(nodes, left, start, boxes);
+        let second = Self::build(nodes, right, start + mid, boxes);
+        nodes[index].kind = NodeKind::Interior { second, axis };
+        index
+    }
+
+    /// Quad from `q` spanned by `u` and `v`, facing `u × v`.
+    pub fn quad(q: Vec3, u: Vec3, v: Vec3, material: M) -> Self {
+        Self::new(
+            vec![q, q + u, q + u + v, q + v],
+            vec![[0, 1, 2], [0, 2, 3]],
+            material,
+        )
+        .with_uvs(vec![(0., 0.), (1., 0.), (1., 1.), (0., 1.)])
+    }
+
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Self {
+        assert_eq!(normals.len(), self.positions.len(), "normal count");
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<(f32, f32)>) -> Self {
+        assert_eq!(uvs.len(), self.positions.len(), "uv count");
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Tags hits with `material_id` for the material id AOV.
+    pub fn with_material_id(mut self, material_id: u32) -> Self {
+        self.material_id = material_id;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn area(&self) -> f32 {
+        self.cdf.last().copied().unwrap_or(0.)
+    }
+
+    fn triangle(&self, i: usize) -> (Vec3, Vec3, Vec3) {
+        let [a, b, c] = self.indices[i];
+        (self.positions[a], self.positions[b], self.positions[c])
+    }
+
+    /// Möller-Trumbore, returns t and the barycentrics of the second and
+    /// third vertex.
+    fn intersect(&self, i: usize, r: &Ray, ray_t: &Interval) -> Option<(f32, f32, f32)> {
+        stats::count(Counter::TriangleTests);
+        let (p0, p1, p2) = self.triangle(i);
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+        let pvec = Vec3::cross(r.direction(), e2);
+        let det = e1.dot(pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1. / det;
+        let tvec = r.origin() - p0;
+        let b1 = tvec.dot(pvec) * inv_det;
+        if !(0. ..=1.).contains(&b1) {
+            return None;
+        }
+        let qvec = Vec3::cross(tvec, e1);
+        let b2 = r.direction().dot(qvec) * inv_det;
+        if b2 < 0. || b1 + b2 > 1. {
+            return None;
+        }
+        let t = e2.dot(qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+        Some((t, b1, b2))
+    }
+
+    /// Closest opaque hit below `node` within `closest`, which shrinks as
+    /// hits are found. Cutouts are skipped here, so the traversal goes on to
+    /// what lies behind them.
+    fn hit_below(&self, node: usize, r: &Ray, closest: &mut Interval) -> Option<Hit<'_>> {
+        stats::count(Counter::BvhNodes);
+        let Node { bounds, kind } = self.nodes[node];
+        if !bounds.hit(r, closest) {
+            return None;
+        }
+        match kind {
+            NodeKind::Leaf(start, end) => {
+                let mut found = None;
+                for &i in &self.order[start..end] {
+                    let Some((t, b1, b2)) = self.intersect(i, r, closest) else {
+                        continue;
+                    };
+                    let hit = self.oriented(i, b1, b2, t, r);
+                    if is_opaque(&hit, r) {
+                        closest.max = t;
+                        found = Some(hit);
+                    }
+                }
+                found
+            }
+            NodeKind::Interior { second, axis } => {
+                // Nearer child first, so the farther one is often culled
+                let (near, far) = if r.direction()[axis] < 0. {
+                    (second, node + 1)
+                } else {
+                    (node + 1, second)
+                };
+                let first = self.hit_below(near, r, closest);
+                self.hit_below(far, r, closest).or(first)
+            }
+        }
+    }
+
+    /// Whether `p` lies on a triangle below `node`.
+    fn on_surface_below(&self, node: usize, p: Vec3) -> bool {
+        stats::count(Counter::BvhNodes);
+        let Node { bounds, kind } = self.nodes[node];
+        // Wide enough for the tolerance of `on_triangle`
+        let near = Aabb {
+            x: bounds.x.expand(4e-3),
+            y: bounds.y.expand(4e-3),
+            z: bounds.z.expand(4e-3),
+        };
+        if !near.contains(p) {
+            return false;
+        }
+        match kind {
+            NodeKind::Leaf(start, end) => self.order[start..end]
+                .iter()
+                .any(|&i| self.on_triangle(i, p)),
+            NodeKind::Interior { second, .. } => {
+                self.on_surface_below(node + 1, p) || self.on_surface_below(second, p)
+            }
+        }
+    }
+
+    fn on_triangle(&self, i: usize, p: Vec3) -> bool {
+        let (p0, p1, p2) = self.triangle(i);
+        let n = Vec3::cross(p1 - p0, p2 - p0);
+        let n2 = n.length_squared();
+        if n2 == 0. || (p - p0).dot(n).abs() > 1e-3 * n2.sqrt() {
+            return false;
+        }
+        // Barycentrics from sub-triangle areas
+        let b1 = Vec3::cross(p - p0, p2 - p0).dot(n) / n2;
+        let b2 = Vec3::cross(p1 - p0, p - p0).dot(n) / n2;
+        b1 >= -1e-4 && b2 >= -1e-4 && b1 + b2 <= 1. + 1e-4
+    }
+
+    /// Hit at barycentrics (b1, b2) of triangle `i`, facing `r`.
+    fn oriented(&self, i: usize, b1: f32, b2: f32, t: f32, r: &Ray) -> Hit<'_> {
+        let mut hit = self.surface(i, b1, b2, t);
+        let shading = hit.normal;
+        hit.set_face_normal(r, hit.geometric_normal);
+        hit.set_shading_normal(shading);
+        hit
+    }
+
+    /// Front facing hit at barycentrics (b1, b2) of triangle `i`.
+    fn surface(&self, i: usize, b1: f32, b2: f32, t: f32) -> Hit<'_> {
+        let [a, b, c] = self.indices[i];
+        let (p0, p1, p2) = self.triangle(i);
+        let b0 = 1. - b1 - b2;
+
+        let (uv0, uv1, uv2) = match &self.uvs {
+            Some(uvs) => (uvs[a], uvs[b], uvs[c]),
+            None => ((0., 0.), (1., 0.), (1., 1.)),
+        };
+        let u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+        let v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+
+        let mut ng = Vec3::unit_vector(Vec3::cross(p1 - p0, p2 - p0));
+        let ns = self
+            .normals
+            .as_ref()
+            .map(|n| Vec3::unit_vector(n[a] * b0 + n[b] * b1 + n[c] * b2));
+        if let Some(ns) = ns {
+            // Vertex normals are the authority on which side is out
+            if ng.dot(ns) < 0. {
+                ng = -ng;
+            }
+        }
+
+        // Solve p = p2 + Δu dp/du + Δv dp/dv over two edges
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let (dp02, dp12) = (p0 - p2, p1 - p2);
+        let det = du02 * dv12 - dv02 * du12;
+        let (dpdu, dpdv) = if det.abs() < 1e-9 {
+            let frame = Onb::build_from_w(ng);
+            (frame.u(), frame.v())
+        } else {
+            let inv = 1. / det;
+            (
+                (dp02 * dv12 - dp12 * dv02) * inv,
+                (dp12 * du02 - dp02 * du12) * inv,
+            )
+        };
+
+        Hit {
+            p: p0 * b0 + p1 * b1 + p2 * b2,
+            normal: ns.unwrap_or(ng),
+            geometric_normal: ng,
+            t,
+            u,
+            v,
+            dpdu,
+            dpdv,
+            material: &self.material,
+            front_face: true,
+            object_id: 0,
+            material_id: self.material_id,
+        }
+    }
+}
+
+impl<M: Material> Hittable for TriangleMesh<M> {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<Hit<'_>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        self.hit_below(0, r, &mut Interval::new(ray_t.min, ray_t.max))
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let Some(hit) = self.hit(
+            &Ray::new(origin, direction),
+            &Interval::new(0.001, INFINITY),
+        ) else {
+            return 0.;
+        };
+        let distance_squared = hit.t * hit.t * direction.length_squared();
+        let cosine = hit.geometric_normal.dot(direction).abs() / direction.length();
+        if cosine <= 0. {
+            return 0.;
+        }
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        match self.sample_surface() {
+            Some((hit, _)) => hit.p - origin,
+            None => Vec3::new(1., 0., 0.),
+        }
+    }
+
+    fn sample_surface(&self) -> Option<(Hit<'_>, f32)> {
+        let area = self.area();
+        if area <= 0. {
+            return None;
+        }
+        let target = random_f32() * area;
+        let i = self
+            .cdf
+            .partition_point(|&c| c < target)
+            .min(self.cdf.len() - 1);
+        let su0 = random_f32().sqrt();
+        let (b1, b2) = (random_f32() * su0, 1. - su0);
+        let hit = self.surface(i, b1, b2, 0.);
+        Some((hit, 1. / area))
+    }
+
+    fn surface_pdf(&self, p: Vec3) -> f32 {
+        if self.nodes.is_empty() || !self.on_surface_below(0, p) {
+            return 0.;
+        }
+        1. / self.area()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.nodes.first()?.bounds)
+    }
+
+    fn normal_bounds(&self) -> Option<(Vec3, f32)> {
+        if self.normals.is_some() {
+            // Vertex normals may flip a face, see `surface`
+            return None;
+        }
+        // Area weighted mean normal, widened to reach the farthest face
+        let faces: Vec<Vec3> = (0..self.indices.len())
+            .map(|i| {
+                let (p0, p1, p2) = self.triangle(i);
+                Vec3::cross(p1 - p0, p2 - p0)
+            })
+            .filter(|n| n.length_squared() > 0.)
+            .collect();
+        let sum = faces.iter().fold(Vec3::default(), |acc, &n| acc + n);
+        if sum.near_zero() {
+            return None;
+        }
+        let axis = Vec3::unit_vector(sum);
+        let cos_theta = faces
+            .iter()
+            .map(|&n| axis.dot(Vec3::unit_vector(n)))
+            .fold(1_f32, f32::min);
+        Some((axis, cos_theta))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::material::Lambertian;
+
+    fn unit_quad() -> TriangleMesh<Lambertian> {
+        TriangleMesh::quad(
+            Vec3::new(0., 0., 0.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(0., 1., 0.),
+            Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    #[test]
+    fn quad_hit_uv_and_partials() {
+        let quad = unit_quad();
+        let ray = Ray::new(Vec3::new(0.5, 0.25, 1.), Vec3::new(0., 0., -1.));
+        let hit = quad.hit(&ray, &Interval::new(0.001, INFINITY)).unwrap();
+        assert!((hit.t - 1.).abs() < 1e-5);
+        assert!((hit.u - 0.25).abs() < 1e-5 && (hit.v - 0.25).abs() < 1e-5);
+        assert!(hit.front_face);
+        assert_eq!(hit.geometric_normal, Vec3::new(0., 0., 1.));
+        assert!((hit.dpdu - Vec3::new(2., 0., 0.)).length() < 1e-5);
+        assert!((hit.dpdv - Vec3::new(0., 1., 0.)).length() < 1e-5);
+    }
+
+    #[test]
+    fn back_face_and_miss() {
+        let quad = unit_quad();
+        let ray = Ray::new(Vec3::new(1., 0.5, -1.), Vec3::new(0., 0., 1.));
+        let hit = quad.hit(&ray, &Interval::new(0.001, INFINITY)).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(hit.normal, Vec3::new(0., 0., -1.));
+
+        let ray = Ray::new(Vec3::new(2.5, 0.5, 1.), Vec3::new(0., 0., -1.));
+        assert!(quad.hit(&ray, &Interval::new(0.001, INFINITY)).is_none());
+    }
+
+    /// Bumpy 32 by 32 grid of quads over [0, 4]², two triangles each.
+    fn terrain() -> TriangleMesh<Lambertian> {
+        let n = 33;
+        let positions = (0..n * n)
+            .map(|i| {
+                let (x, y) = ((i % n) as f32 / 8., (i / n) as f32 / 8.);
+                Vec3::new(x, y, (3. * x).sin() * (2. * y).cos() * 0.3)
+            })
+            .collect();
+        let indices = (0..(n - 1) * (n - 1))
+            .flat_map(|i| {
+                let a = i / (n - 1) * n + i % (n - 1);
+                [[a, a + 1, a + n + 1], [a, a + n + 1, a + n]]
+            })
+            .collect();
+        TriangleMesh::new(
+            positions,
+            indices,
+            Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    #[test]
+    fn bvh_finds_the_closest_triangle() {
+        let mesh = terrain();
+        let interval = Interval::new(0.001, INFINITY);
+        stats::take();
+        for i in 0..200 {
+            let origin = Vec3::new(random_f32() * 5. - 0.5, random_f32() * 5. - 0.5, 2.);
+            let target = Vec3::new(random_f32() * 4., random_f32() * 4., 0.);
+            let ray = Ray::new(origin, target - origin);
+
+            // Every triangle, one by one
+            let mut closest = Interval::new(interval.min, interval.max);
+            for j in 0..mesh.len() {
+                if let Some((t, _, _)) = mesh.intersect(j, &ray, &closest) {
+                    closest.max = t;
+                }
+            }
+            let expected = (closest.max < INFINITY).then_some(closest.max);
+            let counts = stats::take();
+            let hit = mesh.hit(&ray, &interval);
+            assert_eq!(hit.as_ref().map(|h| h.t), expected, "ray {i}");
+            assert!(stats::take()[Counter::TriangleTests] < 100);
+            assert_eq!(counts[Counter::TriangleTests], mesh.len() as u64);
+
+            if let Some(hit) = hit {
+                assert_eq!(mesh.surface_pdf(hit.p), 1. / mesh.area());
+                assert_eq!(mesh.surface_pdf(hit.p + Vec3::new(0., 0., 0.1)), 0.);
+            }
+        }
+    }
+
+    #[test]
+    fn area_pdf() {
+        let quad = unit_quad();
+        assert_eq!(quad.area(), 2.);
+        assert_eq!(quad.surface_pdf(Vec3::new(1.5, 0.5, 0.)), 0.5);
+        assert_eq!(quad.surface_pdf(Vec3::new(1.5, 0.5, 0.1)), 0.);
+    }
+}
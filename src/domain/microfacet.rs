@@ -0,0 +1,161 @@
+use super::{utils::PI, vec3::Vec3};
+
+/// Trowbridge-Reitz (GGX) distribution of microfacet normals with Smith
+/// masking-shadowing. Directions are in a local frame with the surface
+/// normal along +z.
+#[derive(Clone, Copy, Debug)]
+pub struct TrowbridgeReitz {
+    alpha: f32,
+}
+
+impl TrowbridgeReitz {
+    /// `roughness` is perceptual, the distribution width is its square.
+    pub fn new(roughness: f32) -> Self {
+        Self {
+            alpha: (roughness * roughness).max(1e-3),
+        }
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    /// Density of microfacet normals `wm`.
+    pub fn d(&self, wm: Vec3) -> f32 {
+        let cos2 = wm.z() * wm.z();
+        if cos2 <= 0. {
+            return 0.;
+        }
+        let tan2 = (1. - cos2) / cos2;
+        let e = 1. + tan2 / (self.alpha * self.alpha);
+        1. / (PI * self.alpha * self.alpha * cos2 * cos2 * e * e)
+    }
+
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2 = w.z() * w.z();
+        if cos2 <= 0. {
+            return 0.;
+        }
+        let tan2 = (1. - cos2) / cos2;
+        ((1. + self.alpha * self.alpha * tan2).sqrt() - 1.) / 2.
+    }
+
+    /// Fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Height correlated masking-shadowing for the pair `wo`, `wi`.
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of normals visible from `w`, the pdf of `sample_wm`.
+    pub fn d_visible(&self, w: Vec3, wm: Vec3) -> f32 {
+        if w.z() == 0. {
+            return 0.;
+        }
+        self.g1(w) / w.z().abs() * self.d(wm) * w.dot(wm).max(0.)
+    }
+
+    /// Samples a normal from the distribution of normals visible from `w`
+    /// (Heitz 2018).
+    pub fn sample_wm(&self, w: Vec3, u1: f32, u2: f32) -> Vec3 {
+        // Stretch to the hemisphere configuration
+        let mut wh = Vec3::unit_vector(Vec3::new(self.alpha * w.x(), self.alpha * w.y(), w.z()));
+        if wh.z() < 0. {
+            wh = -wh;
+        }
+        let t1 = if wh.z() < 0.99999 {
+            Vec3::unit_vector(Vec3::cross(Vec3::new(0., 0., 1.), wh))
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = Vec3::cross(wh, t1);
+
+        // Uniform disk point, warped towards the visible half
+        let r = u1.sqrt();
+        let phi = 2. * PI * u2;
+        let px = r * phi.cos();
+        let mut py = r * phi.sin();
+        let h = (1. - px * px).sqrt();
+        let s = (1. + wh.z()) / 2.;
+        py = (1. - s) * h + s * py;
+        let pz = (1. - px * px - py * py).max(0.).sqrt();
+        let nh = t1 * px + t2 * py + wh * pz;
+
+        // Back to the ellipsoid configuration
+        Vec3::unit_vector(Vec3::new(
+            self.alpha * nh.x(),
+            self.alpha * nh.y(),
+            nh.z().max(1e-6),
+        ))
+    }
+}
+
+/// Mirror of `wo` about `n`, both pointing away from the surface.
+pub fn reflect(wo: Vec3, n: Vec3) -> Vec3 {
+    -wo + n * (2. * wo.dot(n))
+}
+
+/// Refraction of `wo` through a surface with normal `n` on the side of `wo`
+/// and relative index `eta`. `None` on total internal reflection.
+pub fn refract(wo: Vec3, n: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = wo.dot(n);
+    let sin2_t = (1. - cos_i * cos_i).max(0.) / (eta * eta);
+    if sin2_t >= 1. {
+        return None;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    Some(-wo / eta + n * (cos_i / eta - cos_t))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Integrates `f` over the hemisphere with a midpoint rule.
+    fn integrate(f: impl Fn(Vec3) -> f32) -> f32 {
+        let n = 400;
+        let (d_theta, d_phi) = (PI / 2. / n as f32, 2. * PI / n as f32);
+        let mut sum = 0.;
+        for i in 0..n {
+            let theta = (i as f32 + 0.5) * d_theta;
+            for j in 0..n {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let w = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                sum += f(w) * theta.sin() * d_theta * d_phi;
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn projected_area_is_one() {
+        let distribution = TrowbridgeReitz::new(0.6);
+        let area = integrate(|wm| distribution.d(wm) * wm.z());
+        assert!((area - 1.).abs() < 1e-2, "{area}");
+    }
+
+    #[test]
+    fn visible_normals_integrate_to_one() {
+        let distribution = TrowbridgeReitz::new(0.6);
+        let wo = Vec3::unit_vector(Vec3::new(0.6, 0.2, 0.5));
+        let total = integrate(|wm| distribution.d_visible(wo, wm));
+        assert!((total - 1.).abs() < 1e-2, "{total}");
+    }
+
+    #[test]
+    fn samples_face_the_viewer() {
+        let distribution = TrowbridgeReitz::new(0.8);
+        let wo = Vec3::unit_vector(Vec3::new(0.9, 0., 0.1));
+        for i in 0..100 {
+            let wm = distribution.sample_wm(wo, (i as f32 + 0.5) / 100., 0.37 * i as f32 % 1.);
+            assert!(wm.z() > 0. && wo.dot(wm) >= 0.);
+        }
+    }
+}
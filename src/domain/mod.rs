@@ -1,10 +1,38 @@
+pub mod aabb;
+pub mod alpha_mask;
+pub mod aov;
+pub mod bdpt;
 pub mod camera;
+pub mod coated;
 pub mod color;
+pub mod framebuffer;
 pub mod hittable;
 pub mod hittable_list;
+pub mod image_diff;
+pub mod integrator;
 pub mod interval;
+pub mod light;
+pub mod light_bvh;
 pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod microfacet;
+pub mod normal_map;
+pub mod onb;
+pub mod pbrt;
+pub mod ply;
+pub mod principled;
 pub mod ray;
+pub mod sampler;
+pub mod scene;
+pub mod scene_file;
+pub mod scenes;
+pub mod sky;
+pub mod spectrum;
 pub mod sphere;
+pub mod sppm;
+pub mod stats;
+pub mod texture;
+pub mod two_sided;
 pub mod utils;
 pub mod vec3;
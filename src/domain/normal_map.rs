@@ -0,0 +1,159 @@
+use super::{
+    hittable::Hit,
+    material::Material,
+    medium::Medium,
+    ray::Ray,
+    texture::{ImageTexture, Texture},
+    vec3::Vec3,
+};
+
+/// Wraps a material with a shading normal read from a tangent space normal
+/// map, RGB in [0, 1] mapping to xyz in [-1, 1] with z along the normal.
+pub struct NormalMap<M: Material> {
+    material: M,
+    map: ImageTexture,
+}
+
+impl<M: Material> NormalMap<M> {
+    pub fn new(material: M, map: ImageTexture) -> Self {
+        Self { material, map }
+    }
+
+    fn perturb<'a>(&self, hit: &Hit<'a>) -> Hit<'a> {
+        let c = self.map.value(hit.u, hit.v, hit.p);
+        let local = Vec3::new(2. * c.x() - 1., 2. * c.y() - 1., 2. * c.z() - 1.);
+
+        // Tangent frame around the outward side, bitangent along dp/dv
+        let n = if hit.front_face {
+            hit.normal
+        } else {
+            -hit.normal
+        };
+        let t = hit.tangent();
+        let mut b = Vec3::cross(n, t);
+        if b.dot(hit.dpdv) < 0. {
+            b = -b;
+        }
+
+        let mut perturbed = *hit;
+        perturbed.set_shading_normal(t * local.x() + b * local.y() + n * local.z());
+        perturbed
+    }
+}
+
+/// Wraps a material with a shading normal bent by a height field, read
+/// from any channel of `height` and scaled by `scale` world units.
+pub struct BumpMap<M: Material, T: Texture> {
+    material: M,
+    height: T,
+    scale: f32,
+}
+
+impl<M: Material, T: Texture> BumpMap<M, T> {
+    /// Step in (u, v) for finite differences of the height.
+    const DELTA: f32 = 5e-4;
+
+    pub fn new(material: M, height: T, scale: f32) -> Self {
+        Self {
+            material,
+            height,
+            scale,
+        }
+    }
+
+    fn displacement(&self, u: f32, v: f32, p: Vec3) -> f32 {
+        self.scale * self.height.value(u, v, p).x()
+    }
+
+    fn perturb<'a>(&self, hit: &Hit<'a>) -> Hit<'a> {
+        let d = Self::DELTA;
+        let n = hit.outward_normal();
+        let displace = self.displacement(hit.u, hit.v, hit.p);
+        let du = (self.displacement(hit.u + d, hit.v, hit.p + hit.dpdu * d) - displace) / d;
+        let dv = (self.displacement(hit.u, hit.v + d, hit.p + hit.dpdv * d) - displace) / d;
+
+        // Displaced surface p + h n, curvature of n itself neglected
+        let dpdu = hit.dpdu + n * du;
+        let dpdv = hit.dpdv + n * dv;
+        let bumped = Vec3::cross(dpdu, dpdv);
+        if bumped.near_zero() {
+            return *hit;
+        }
+
+        let mut perturbed = *hit;
+        perturbed.set_shading_normal(bumped);
+        perturbed.dpdu = dpdu;
+        perturbed.dpdv = dpdv;
+        perturbed
+    }
+}
+
+impl<M: Material> Material for NormalMap<M> {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        self.material.scatter(ray, &self.perturb(hit))
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        self.material.emitted(ray, &self.perturb(hit))
+    }
+
+    fn interior(&self) -> Option<Medium> {
+        self.material.interior()
+    }
+
+    fn alpha(&self, hit: &Hit) -> f32 {
+        self.material.alpha(hit)
+    }
+
+    fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        self.material.shading_normal(&self.perturb(hit))
+    }
+
+    fn albedo(&self, hit: &Hit) -> Vec3 {
+        self.material.albedo(&self.perturb(hit))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        self.material.eval(ray, &self.perturb(hit), scattered)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        self.material
+            .scattering_pdf(ray, &self.perturb(hit), scattered)
+    }
+}
+
+impl<M: Material, T: Texture> Material for BumpMap<M, T> {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        self.material.scatter(ray, &self.perturb(hit))
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        self.material.emitted(ray, &self.perturb(hit))
+    }
+
+    fn interior(&self) -> Option<Medium> {
+        self.material.interior()
+    }
+
+    fn alpha(&self, hit: &Hit) -> f32 {
+        self.material.alpha(hit)
+    }
+
+    fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        self.material.shading_normal(&self.perturb(hit))
+    }
+
+    fn albedo(&self, hit: &Hit) -> Vec3 {
+        self.material.albedo(&self.perturb(hit))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        self.material.eval(ray, &self.perturb(hit), scattered)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        self.material
+            .scattering_pdf(ray, &self.perturb(hit), scattered)
+    }
+}
@@ -0,0 +1,43 @@
+use super::vec3::Vec3;
+
+/// Orthonormal basis built around a single axis `w`.
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    pub fn build_from_w(w: Vec3) -> Self {
+        let unit_w = Vec3::unit_vector(w);
+        let a = if unit_w.x().abs() > 0.9 {
+            Vec3::new(0., 1., 0.)
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let v = Vec3::unit_vector(Vec3::cross(unit_w, a));
+        let u = Vec3::cross(unit_w, v);
+        Self {
+            axis: [u, v, unit_w],
+        }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        self.u() * a.x() + self.v() * a.y() + self.w() * a.z()
+    }
+
+    /// Inverse of `local`: world space `a` in basis coordinates.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u()), a.dot(self.v()), a.dot(self.w()))
+    }
+}
@@ -0,0 +1,1495 @@
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    fs, ops,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    camera::Camera,
+    coated::Coated,
+    hittable::Hittable,
+    hittable_list::HittableList,
+    light::{DirectionalLight, PointLight, SpotLight},
+    material::{
+        Conductor, Dialectric, DiffuseLight, Emissive, Ior, Lambertian, Material, RoughDielectric,
+    },
+    mesh::TriangleMesh,
+    ply,
+    scene::Scene,
+    scene_file::{IntegratorKind, RenderSettings, SceneDescription, SceneError},
+    spectrum::blackbody,
+    sphere::Sphere,
+    utils::degrees_to_radians,
+    vec3::Vec3,
+};
+
+/// A pbrt scene and everything in it that was skipped or approximated, one
+/// line per feature as "file:line: what".
+pub struct PbrtImport {
+    pub description: SceneDescription,
+    pub unsupported: Vec<String>,
+}
+
+/// Reads a pbrt-v3 or pbrt-v4 scene. Included files and PLY meshes are
+/// relative to the directory of `path`.
+pub fn load(path: impl AsRef<Path>) -> Result<PbrtImport, SceneError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|e| SceneError::Io(path.to_path_buf(), e))?;
+    import(
+        &text,
+        &path.display().to_string(),
+        path.parent().unwrap_or(Path::new(".")),
+    )
+}
+
+/// Builds the pbrt scene in `text`, with included files relative to
+/// `base_dir`. Locations in errors and reports are named "input".
+pub fn parse(text: &str, base_dir: &Path) -> Result<PbrtImport, SceneError> {
+    import(text, "input", base_dir)
+}
+
+fn import(text: &str, file: &str, base_dir: &Path) -> Result<PbrtImport, SceneError> {
+    let mut importer = Importer::new(base_dir);
+    importer.run(text, file)?;
+    Ok(importer.finish())
+}
+
+/// Deepest chain of `Include`s followed, to stop files including themselves.
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Num(f32),
+    Open,
+    Close,
+}
+
+/// Tokens of `text` with their line numbers.
+fn tokenize(text: &str, file: &str) -> Result<Vec<(Token, usize)>, SceneError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '[' | ']' => {
+                chars.next();
+                tokens.push((if c == '[' { Token::Open } else { Token::Close }, line));
+            }
+            '"' => {
+                chars.next();
+                let start = line;
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some(c) => s.push(c),
+                            None => break,
+                        },
+                        Some('\n') | None => {
+                            return Err(SceneError::invalid(
+                                format!("{file}:{start}"),
+                                "unterminated string",
+                            ))
+                        }
+                        Some(c) => s.push(c),
+                    }
+                }
+                tokens.push((Token::Str(s), line));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) =
+                    chars.next_if(|&c| !c.is_whitespace() && !matches!(c, '[' | ']' | '"' | '#'))
+                {
+                    word.push(c);
+                }
+                let token = if word.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    Token::Word(word)
+                } else {
+                    Token::Num(word.parse().map_err(|_| {
+                        SceneError::invalid(
+                            format!("{file}:{line}"),
+                            format!("expected a number, found \"{word}\""),
+                        )
+                    })?)
+                };
+                tokens.push((token, line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Num(f32),
+    Str(String),
+    Bool(bool),
+}
+
+#[derive(Debug)]
+enum Arg {
+    One(Value),
+    List(Vec<Value>),
+}
+
+/// A directive and everything up to the next one.
+struct Statement {
+    name: String,
+    line: usize,
+    args: Vec<Arg>,
+}
+
+fn statements(text: &str, file: &str) -> Result<Vec<Statement>, SceneError> {
+    let value = |token: &Token| match token {
+        Token::Num(n) => Some(Value::Num(*n)),
+        Token::Str(s) => Some(Value::Str(s.clone())),
+        Token::Word(w) if w == "true" || w == "false" => Some(Value::Bool(w == "true")),
+        _ => None,
+    };
+    let mut statements: Vec<Statement> = vec![];
+    let mut tokens = tokenize(text, file)?.into_iter().peekable();
+    while let Some((token, line)) = tokens.next() {
+        let at = || format!("{file}:{line}");
+        if let Some(v) = value(&token) {
+            let Some(statement) = statements.last_mut() else {
+                return Err(SceneError::invalid(at(), "expected a directive"));
+            };
+            statement.args.push(Arg::One(v));
+            continue;
+        }
+        match token {
+            Token::Word(name) => statements.push(Statement {
+                name,
+                line,
+                args: vec![],
+            }),
+            Token::Open => {
+                let mut list = vec![];
+                loop {
+                    match tokens.next() {
+                        Some((Token::Close, _)) => break,
+                        Some((t, _)) => match value(&t) {
+                            Some(v) => list.push(v),
+                            None => return Err(SceneError::invalid(at(), "unclosed [")),
+                        },
+                        None => return Err(SceneError::invalid(at(), "unclosed [")),
+                    }
+                }
+                let Some(statement) = statements.last_mut() else {
+                    return Err(SceneError::invalid(at(), "expected a directive"));
+                };
+                statement.args.push(Arg::List(list));
+            }
+            _ => return Err(SceneError::invalid(at(), "unexpected ]")),
+        }
+    }
+    Ok(statements)
+}
+
+impl Statement {
+    /// The leading string argument, such as the type of a `Shape`.
+    fn kind(&self, at: &str) -> Result<&str, SceneError> {
+        match self.args.first() {
+            Some(Arg::One(Value::Str(s))) => Ok(s),
+            _ => Err(SceneError::invalid(
+                at,
+                format!("{} needs a quoted name", self.name),
+            )),
+        }
+    }
+
+    /// Exactly `n` numbers, loose or in one list.
+    fn numbers(&self, n: usize, at: &str) -> Result<Vec<f32>, SceneError> {
+        let mut numbers = vec![];
+        for arg in &self.args {
+            let values = match arg {
+                Arg::One(v) => std::slice::from_ref(v),
+                Arg::List(list) => list,
+            };
+            for v in values {
+                match v {
+                    Value::Num(x) => numbers.push(*x),
+                    _ => numbers.clear(),
+                }
+            }
+        }
+        if numbers.len() != n {
+            return Err(SceneError::invalid(
+                at,
+                format!("{} takes {n} numbers", self.name),
+            ));
+        }
+        Ok(numbers)
+    }
+
+    /// Parameters after the first `skip` arguments.
+    fn params(&self, skip: usize, at: &str) -> Result<ParamSet, SceneError> {
+        let mut params = vec![];
+        let mut args = self.args.iter().skip(skip);
+        while let Some(arg) = args.next() {
+            let Arg::One(Value::Str(declaration)) = arg else {
+                return Err(SceneError::invalid(
+                    at,
+                    "expected a parameter such as \"float radius\"",
+                ));
+            };
+            let mut words = declaration.split_whitespace();
+            let (Some(ty), Some(name), None) = (words.next(), words.next(), words.next()) else {
+                return Err(SceneError::invalid(
+                    at,
+                    format!("bad parameter declaration \"{declaration}\""),
+                ));
+            };
+            let values = match args.next() {
+                Some(Arg::One(v)) => vec![v.clone()],
+                Some(Arg::List(list)) => list.clone(),
+                None => {
+                    return Err(SceneError::invalid(
+                        at,
+                        format!("parameter \"{declaration}\" has no value"),
+                    ))
+                }
+            };
+            params.push(Param {
+                ty: ty.to_string(),
+                name: name.to_string(),
+                values,
+                used: Cell::new(false),
+            });
+        }
+        Ok(ParamSet(params))
+    }
+}
+
+struct Param {
+    ty: String,
+    name: String,
+    values: Vec<Value>,
+    used: Cell<bool>,
+}
+
+/// Parameters of one directive, remembering which were read so the rest
+/// can be reported.
+struct ParamSet(Vec<Param>);
+
+const NUMERIC: &[&str] = &[
+    "float", "integer", "point", "point2", "point3", "vector", "vector2", "vector3", "normal",
+    "normal3",
+];
+
+enum Spectrum {
+    Rgb(Vec3),
+    /// Temperature in kelvin and scale.
+    Blackbody(f32, f32),
+    /// A named or file spectrum, or one given as samples.
+    Other(String),
+}
+
+impl ParamSet {
+    fn find(&self, name: &str, types: &[&str]) -> Option<&[Value]> {
+        let param = self
+            .0
+            .iter()
+            .find(|p| p.name == name && types.contains(&p.ty.as_str()))?;
+        param.used.set(true);
+        Some(&param.values)
+    }
+
+    fn floats(&self, name: &str) -> Option<Vec<f32>> {
+        let values = self.find(name, NUMERIC)?;
+        Some(
+            values
+                .iter()
+                .filter_map(|v| match v {
+                    Value::Num(x) => Some(*x),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    fn float(&self, name: &str) -> Option<f32> {
+        self.floats(name)?.first().copied()
+    }
+
+    fn point(&self, name: &str, default: Vec3) -> Vec3 {
+        match self.floats(name).as_deref() {
+            Some(&[x, y, z]) => Vec3::new(x, y, z),
+            _ => default,
+        }
+    }
+
+    fn string(&self, name: &str) -> Option<String> {
+        match self.find(name, &["string"])?.first() {
+            Some(Value::Str(s)) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    fn bool(&self, name: &str) -> Option<bool> {
+        match self.find(name, &["bool"])?.first() {
+            Some(Value::Bool(b)) => Some(*b),
+            Some(Value::Str(s)) => Some(s == "true"),
+            _ => None,
+        }
+    }
+
+    fn spectrum(&self, name: &str) -> Option<Spectrum> {
+        let param = self.0.iter().find(|p| {
+            p.name == name && matches!(p.ty.as_str(), "rgb" | "color" | "blackbody" | "spectrum")
+        })?;
+        param.used.set(true);
+        let numbers: Vec<f32> = param
+            .values
+            .iter()
+            .filter_map(|v| match v {
+                Value::Num(x) => Some(*x),
+                _ => None,
+            })
+            .collect();
+        Some(match (param.ty.as_str(), numbers.as_slice()) {
+            ("rgb" | "color", &[r, g, b]) => Spectrum::Rgb(Vec3::new(r, g, b)),
+            ("blackbody", &[kelvin]) => Spectrum::Blackbody(kelvin, 1.),
+            ("blackbody", &[kelvin, scale]) => Spectrum::Blackbody(kelvin, scale),
+            _ => match param.values.first() {
+                Some(Value::Str(s)) => Spectrum::Other(s.clone()),
+                _ => Spectrum::Other(format!("{} {name}", param.ty)),
+            },
+        })
+    }
+
+    fn unused(&self) -> impl Iterator<Item = &Param> {
+        self.0.iter().filter(|p| !p.used.get())
+    }
+}
+
+/// Row major 4×4 matrix acting on column vectors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Matrix([[f32; 4]; 4]);
+
+impl ops::Mul for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, o: Matrix) -> Matrix {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.0[i][k] * o.0[k][j]).sum();
+            }
+        }
+        Matrix(m)
+    }
+}
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix([
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ]);
+
+    fn translate(v: Vec3) -> Matrix {
+        let mut m = Self::IDENTITY;
+        (m.0[0][3], m.0[1][3], m.0[2][3]) = (v.x(), v.y(), v.z());
+        m
+    }
+
+    fn scale(v: Vec3) -> Matrix {
+        let mut m = Self::IDENTITY;
+        (m.0[0][0], m.0[1][1], m.0[2][2]) = (v.x(), v.y(), v.z());
+        m
+    }
+
+    /// Rotation by `degrees` about `axis`, counterclockwise looking down it.
+    fn rotate(degrees: f32, axis: Vec3) -> Matrix {
+        let a = Vec3::unit_vector(axis);
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        Matrix([
+            [
+                x * x + (1. - x * x) * cos,
+                x * y * (1. - cos) - z * sin,
+                x * z * (1. - cos) + y * sin,
+                0.,
+            ],
+            [
+                x * y * (1. - cos) + z * sin,
+                y * y + (1. - y * y) * cos,
+                y * z * (1. - cos) - x * sin,
+                0.,
+            ],
+            [
+                x * z * (1. - cos) - y * sin,
+                y * z * (1. - cos) + x * sin,
+                z * z + (1. - z * z) * cos,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// pbrt's world to camera transform: +z towards `look`, +y towards
+    /// `up` and +x to its left, as pbrt's camera space is left handed.
+    fn look_at(eye: Vec3, look: Vec3, up: Vec3) -> Option<Matrix> {
+        let dir = Vec3::unit_vector(look - eye);
+        let right = Vec3::cross(Vec3::unit_vector(up), dir);
+        if right.length() < 1e-6 || !dir.x().is_finite() {
+            return None;
+        }
+        let right = Vec3::unit_vector(right);
+        let new_up = Vec3::cross(dir, right);
+        let mut camera_to_world = Self::IDENTITY;
+        for (i, column) in [right, new_up, dir, eye].into_iter().enumerate() {
+            for row in 0..3 {
+                camera_to_world.0[row][i] = column[row];
+            }
+        }
+        camera_to_world.inverse()
+    }
+
+    /// The 16 numbers of `Transform` and `ConcatTransform`, column by column.
+    fn from_columns(m: &[f32]) -> Matrix {
+        let mut out = [[0.; 4]; 4];
+        for (i, row) in out.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = m[j * 4 + i];
+            }
+        }
+        Matrix(out)
+    }
+
+    fn transpose(&self) -> Matrix {
+        Matrix::from_columns(&self.0.concat())
+    }
+
+    /// Gauss-Jordan with partial pivoting, `None` for singular matrices.
+    fn inverse(&self) -> Option<Matrix> {
+        let mut a = self.0;
+        let mut inv = Self::IDENTITY.0;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let p = a[col][col];
+            for k in 0..4 {
+                a[col][k] /= p;
+                inv[col][k] /= p;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let f = a[row][col];
+                    for k in 0..4 {
+                        a[row][k] -= f * a[col][k];
+                        inv[row][k] -= f * inv[col][k];
+                    }
+                }
+            }
+        }
+        Some(Matrix(inv))
+    }
+
+    /// Determinant of the linear part, negative if it mirrors.
+    fn det3(&self) -> f32 {
+        let m = &self.0;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    fn point(&self, p: Vec3) -> Vec3 {
+        let m = &self.0;
+        let row = |r: [f32; 4]| r[0] * p.x() + r[1] * p.y() + r[2] * p.z() + r[3];
+        let w = row(m[3]);
+        Vec3::new(row(m[0]), row(m[1]), row(m[2])) / w
+    }
+
+    fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.0;
+        let row = |r: [f32; 4]| r[0] * v.x() + r[1] * v.y() + r[2] * v.z();
+        Vec3::new(row(m[0]), row(m[1]), row(m[2]))
+    }
+}
+
+/// Our camera looks down -z from the origin, pbrt's down +z.
+fn flip_z() -> Matrix {
+    Matrix::scale(Vec3::new(1., 1., -1.))
+}
+
+/// pbrt's `RoughnessToAlpha` from pbrt-v3, used by its metal and plastic.
+fn v3_roughness_to_alpha(roughness: f32) -> f32 {
+    let x = roughness.max(1e-3).ln();
+    1.62142 + 0.819955 * x + 0.1734 * x * x + 0.0171201 * x * x * x + 0.000640711 * x * x * x * x
+}
+
+#[derive(Clone)]
+enum MaterialDef {
+    Matte(Vec3),
+    ConductorPreset(fn(f32) -> Conductor, f32),
+    Conductor(Vec3, Vec3, f32),
+    Glass(f32, f32),
+    Coated(Vec3, f32, f32),
+    /// Boundary between media, not rendered.
+    Interface,
+}
+
+impl MaterialDef {
+    fn build(&self) -> Option<Box<dyn Material>> {
+        Some(match *self {
+            MaterialDef::Matte(albedo) => Box::new(Lambertian::new(albedo)),
+            MaterialDef::ConductorPreset(preset, roughness) => Box::new(preset(roughness)),
+            MaterialDef::Conductor(eta, k, roughness) => {
+                Box::new(Conductor::new(eta, k, roughness))
+            }
+            MaterialDef::Glass(ior, roughness) if roughness > 0. => {
+                Box::new(RoughDielectric::new(Ior::Constant(ior), roughness))
+            }
+            MaterialDef::Glass(ior, _) => Box::new(Dialectric::new(ior)),
+            MaterialDef::Coated(albedo, ior, roughness) => {
+                Box::new(Coated::new(Lambertian::new(albedo), ior, roughness))
+            }
+            MaterialDef::Interface => return None,
+        })
+    }
+}
+
+/// `AreaLightSource "diffuse"`.
+#[derive(Clone, Copy)]
+struct AreaLight {
+    emit: Vec3,
+    two_sided: bool,
+}
+
+/// What `AttributeBegin` saves and `AttributeEnd` restores.
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    reverse_orientation: bool,
+    material: MaterialDef,
+    /// Tags the shapes for the material id AOV.
+    material_id: u32,
+    area_light: Option<AreaLight>,
+}
+
+/// Shape already in our camera space, built once per list it goes into.
+#[derive(Clone)]
+enum Geometry {
+    Sphere(Vec3, f32),
+    Mesh {
+        positions: Vec<Vec3>,
+        indices: Vec<[usize; 3]>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<(f32, f32)>>,
+    },
+}
+
+impl Geometry {
+    fn build(&self, material: Box<dyn Material>, material_id: u32) -> Box<dyn Hittable> {
+        match self {
+            Geometry::Sphere(center, radius) => {
+                Box::new(Sphere::new(*center, *radius, material).with_material_id(material_id))
+            }
+            Geometry::Mesh {
+                positions,
+                indices,
+                normals,
+                uvs,
+            } => {
+                let mut mesh = TriangleMesh::new(positions.clone(), indices.clone(), material)
+                    .with_material_id(material_id);
+                if let Some(normals) = normals {
+                    mesh = mesh.with_normals(normals.clone());
+                }
+                if let Some(uvs) = uvs {
+                    mesh = mesh.with_uvs(uvs.clone());
+                }
+                Box::new(mesh)
+            }
+        }
+    }
+}
+
+struct Importer {
+    base_dir: PathBuf,
+    unsupported: Vec<String>,
+    /// Messages already in `unsupported`, each is reported once.
+    reported: HashSet<String>,
+    state: GraphicsState,
+    attributes: Vec<GraphicsState>,
+    transforms: Vec<Matrix>,
+    /// Named materials with their ids.
+    materials: HashMap<String, (MaterialDef, u32)>,
+    /// Materials declared so far, named or not. Ids count up in that order
+    /// after the default material's 1.
+    material_count: u32,
+    coordinate_systems: HashMap<String, Matrix>,
+    camera_from_world: Matrix,
+    /// Field of view and where the camera was declared.
+    fov: (f32, String),
+    resolution: (i32, i32),
+    samples_per_pixel: i32,
+    render: RenderSettings,
+    scene: Scene,
+    background: Vec3,
+    /// Inside `ObjectBegin`, whose shapes only appear through instances.
+    in_object: bool,
+    include_depth: usize,
+}
+
+impl Importer {
+    fn new(base_dir: &Path) -> Self {
+        Self {
+            base_dir: base_dir.to_path_buf(),
+            unsupported: vec![],
+            reported: HashSet::new(),
+            state: GraphicsState {
+                ctm: Matrix::IDENTITY,
+                reverse_orientation: false,
+                material: MaterialDef::Matte(Vec3::new(0.5, 0.5, 0.5)),
+                material_id: 1,
+                area_light: None,
+            },
+            attributes: vec![],
+            transforms: vec![],
+            materials: HashMap::new(),
+            material_count: 1,
+            coordinate_systems: HashMap::new(),
+            camera_from_world: Matrix::IDENTITY,
+            fov: (90., "default camera".to_string()),
+            resolution: (1280, 720),
+            samples_per_pixel: 16,
+            render: RenderSettings {
+                max_depth: 5,
+                ..RenderSettings::default()
+            },
+            scene: Scene::new(HittableList::new(), HittableList::new()),
+            background: Vec3::new(0., 0., 0.),
+            in_object: false,
+            include_depth: 0,
+        }
+    }
+
+    fn report(&mut self, at: &str, message: String) {
+        if self.reported.insert(message.clone()) {
+            self.unsupported.push(format!("{at}: {message}"));
+        }
+    }
+
+    fn report_unused(&mut self, at: &str, what: &str, params: &ParamSet) {
+        let unused: Vec<String> = params
+            .unused()
+            .map(|p| format!("{what}: parameter \"{} {}\" ignored", p.ty, p.name))
+            .collect();
+        for message in unused {
+            self.report(at, message);
+        }
+    }
+
+    /// Color parameter `name`, reporting spectra we can't turn into RGB.
+    fn color(&mut self, params: &ParamSet, name: &str, default: Vec3, at: &str) -> Vec3 {
+        match params.spectrum(name) {
+            None => default,
+            Some(Spectrum::Rgb(rgb)) => rgb,
+            Some(Spectrum::Blackbody(kelvin, scale)) => blackbody(kelvin) * scale,
+            Some(Spectrum::Other(spectrum)) => {
+                self.report(
+                    at,
+                    format!(
+                        "spectrum \"{spectrum}\" for \"{name}\" not supported, using the default"
+                    ),
+                );
+                default
+            }
+        }
+    }
+
+    /// Light `scale`, a float in pbrt-v4 and a color in pbrt-v3.
+    fn light_scale(&mut self, params: &ParamSet, at: &str) -> Vec3 {
+        match params.float("scale") {
+            Some(s) => Vec3::new(s, s, s),
+            None => self.color(params, "scale", Vec3::new(1., 1., 1.), at),
+        }
+    }
+
+    /// Object to our camera space for the current transform.
+    fn to_camera(&self) -> Matrix {
+        flip_z() * self.camera_from_world * self.state.ctm
+    }
+
+    fn run(&mut self, text: &str, file: &str) -> Result<(), SceneError> {
+        for statement in statements(text, file)? {
+            let at = format!("{file}:{}", statement.line);
+            self.statement(&statement, &at)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, s: &Statement, at: &str) -> Result<(), SceneError> {
+        let vec = |n: &[f32]| Vec3::new(n[0], n[1], n[2]);
+        match s.name.as_str() {
+            "Identity" => self.state.ctm = Matrix::IDENTITY,
+            "Translate" => {
+                self.state.ctm = self.state.ctm * Matrix::translate(vec(&s.numbers(3, at)?))
+            }
+            "Scale" => self.state.ctm = self.state.ctm * Matrix::scale(vec(&s.numbers(3, at)?)),
+            "Rotate" => {
+                let n = s.numbers(4, at)?;
+                self.state.ctm = self.state.ctm * Matrix::rotate(n[0], vec(&n[1..]))
+            }
+            "LookAt" => {
+                let n = s.numbers(9, at)?;
+                let look_at =
+                    Matrix::look_at(vec(&n), vec(&n[3..]), vec(&n[6..])).ok_or_else(|| {
+                        SceneError::invalid(at, "LookAt up vector is parallel to the view")
+                    })?;
+                self.state.ctm = self.state.ctm * look_at
+            }
+            "Transform" => self.state.ctm = Matrix::from_columns(&s.numbers(16, at)?),
+            "ConcatTransform" => {
+                self.state.ctm = self.state.ctm * Matrix::from_columns(&s.numbers(16, at)?)
+            }
+            "CoordinateSystem" => {
+                let name = s.kind(at)?.to_string();
+                self.coordinate_systems.insert(name, self.state.ctm);
+            }
+            "CoordSysTransform" => {
+                let name = s.kind(at)?;
+                match self.coordinate_systems.get(name) {
+                    Some(&m) => self.state.ctm = m,
+                    None => {
+                        return Err(SceneError::invalid(
+                            at,
+                            format!("no coordinate system named \"{name}\""),
+                        ))
+                    }
+                }
+            }
+            "ReverseOrientation" => {
+                self.state.reverse_orientation = !self.state.reverse_orientation
+            }
+            "WorldBegin" => {
+                self.state.ctm = Matrix::IDENTITY;
+                self.coordinate_systems
+                    .insert("world".to_string(), Matrix::IDENTITY);
+            }
+            // pbrt-v3 only, nothing to finish
+            "WorldEnd" => {}
+            "AttributeBegin" => self.attributes.push(self.state.clone()),
+            "AttributeEnd" => {
+                self.state = self
+                    .attributes
+                    .pop()
+                    .ok_or_else(|| SceneError::invalid(at, "AttributeEnd without AttributeBegin"))?
+            }
+            "TransformBegin" => self.transforms.push(self.state.ctm),
+            "TransformEnd" => {
+                self.state.ctm = self
+                    .transforms
+                    .pop()
+                    .ok_or_else(|| SceneError::invalid(at, "TransformEnd without TransformBegin"))?
+            }
+            "ObjectBegin" => {
+                self.report(
+                    at,
+                    "object instancing not supported, instanced shapes skipped".to_string(),
+                );
+                self.attributes.push(self.state.clone());
+                self.in_object = true;
+            }
+            "ObjectEnd" => {
+                self.state = self
+                    .attributes
+                    .pop()
+                    .ok_or_else(|| SceneError::invalid(at, "ObjectEnd without ObjectBegin"))?;
+                self.in_object = false;
+            }
+            "Include" | "Import" => self.include(s.kind(at)?, at)?,
+            "Camera" => {
+                let kind = s.kind(at)?;
+                let params = s.params(1, at)?;
+                self.camera_from_world = self.state.ctm;
+                if let Some(world_from_camera) = self.state.ctm.inverse() {
+                    self.coordinate_systems
+                        .insert("camera".to_string(), world_from_camera);
+                }
+                if kind == "perspective" {
+                    self.fov = (params.float("fov").unwrap_or(90.), at.to_string());
+                } else {
+                    self.report(
+                        at,
+                        format!("Camera \"{kind}\" not supported, using perspective"),
+                    );
+                }
+                self.report_unused(at, &format!("Camera \"{kind}\""), &params);
+            }
+            "Film" => {
+                let kind = s.kind(at)?;
+                let params = s.params(1, at)?;
+                let x = params.float("xresolution").unwrap_or(1280.) as i32;
+                let y = params.float("yresolution").unwrap_or(720.) as i32;
+                if x <= 0 || y <= 0 {
+                    return Err(SceneError::invalid(at, "resolution must be positive"));
+                }
+                self.resolution = (x, y);
+                self.report_unused(at, &format!("Film \"{kind}\""), &params);
+            }
+            "Sampler" => {
+                let kind = s.kind(at)?;
+                let params = s.params(1, at)?;
+                self.samples_per_pixel = params.float("pixelsamples").unwrap_or(16.).max(1.) as i32;
+                if kind != "random" {
+                    self.report(
+                        at,
+                        format!("Sampler \"{kind}\" not supported, sampling randomly"),
+                    );
+                }
+                self.report_unused(at, &format!("Sampler \"{kind}\""), &params);
+            }
+            "Integrator" => {
+                let kind = s.kind(at)?;
+                let params = s.params(1, at)?;
+                self.integrator(kind, &params, at);
+                self.report_unused(at, &format!("Integrator \"{kind}\""), &params);
+            }
+            "Material" => {
+                let kind = s.kind(at)?.to_string();
+                let params = s.params(1, at)?;
+                self.state.material = self.material(&kind, &params, at);
+                self.material_count += 1;
+                self.state.material_id = self.material_count;
+            }
+            "MakeNamedMaterial" => {
+                let name = s.kind(at)?.to_string();
+                let params = s.params(1, at)?;
+                let kind = params.string("type").unwrap_or_default();
+                let material = self.material(&kind, &params, at);
+                self.material_count += 1;
+                self.materials.insert(name, (material, self.material_count));
+            }
+            "NamedMaterial" => {
+                let name = s.kind(at)?;
+                let (material, id) = self.materials.get(name).cloned().ok_or_else(|| {
+                    SceneError::invalid(at, format!("no material named \"{name}\""))
+                })?;
+                self.state.material = material;
+                self.state.material_id = id;
+            }
+            "AreaLightSource" => {
+                let kind = s.kind(at)?;
+                let params = s.params(1, at)?;
+                if kind == "diffuse" {
+                    let emit = self.color(&params, "L", Vec3::new(1., 1., 1.), at)
+                        * self.light_scale(&params, at);
+                    self.state.area_light = Some(AreaLight {
+                        emit,
+                        two_sided: params.bool("twosided").unwrap_or(false),
+                    });
+                } else {
+                    self.report(at, format!("AreaLightSource \"{kind}\" not supported"));
+                }
+                self.report_unused(at, &format!("AreaLightSource \"{kind}\""), &params);
+            }
+            "LightSource" => {
+                let kind = s.kind(at)?;
+                let params = s.params(1, at)?;
+                self.light(kind, &params, at);
+                self.report_unused(at, &format!("LightSource \"{kind}\""), &params);
+            }
+            "Shape" => {
+                let kind = s.kind(at)?;
+                let params = s.params(1, at)?;
+                if !self.in_object {
+                    self.shape(kind, &params, at)?;
+                    self.report_unused(at, &format!("Shape \"{kind}\""), &params);
+                }
+            }
+            // Acceleration structures are ours to choose
+            "Accelerator" => {}
+            "ColorSpace" if s.kind(at)? == "srgb" => {}
+            name => self.report(at, format!("{name} not supported, ignored")),
+        }
+        Ok(())
+    }
+
+    fn include(&mut self, file: &str, at: &str) -> Result<(), SceneError> {
+        if self.include_depth == MAX_INCLUDE_DEPTH {
+            return Err(SceneError::invalid(at, "Include nested too deeply"));
+        }
+        let path = self.base_dir.join(file);
+        let text = fs::read_to_string(&path).map_err(|e| SceneError::Io(path.clone(), e))?;
+        self.include_depth += 1;
+        self.run(&text, file)?;
+        self.include_depth -= 1;
+        Ok(())
+    }
+
+    fn integrator(&mut self, kind: &str, params: &ParamSet, at: &str) {
+        self.render.max_depth = params.float("maxdepth").unwrap_or(5.) as i32;
+        self.render.integrator = match kind {
+            "path" | "volpath" => IntegratorKind::Path,
+            "bdpt" => IntegratorKind::Bdpt,
+            "sppm" => {
+                let (w, h) = self.resolution;
+                self.render.photons_per_pass = match params.float("photonsperiteration") {
+                    Some(n) if n > 0. => n as i32,
+                    _ => w * h,
+                };
+                self.render.initial_radius = params.float("radius").unwrap_or(1.);
+                IntegratorKind::Sppm
+            }
+            "directlighting" => IntegratorKind::Direct,
+            "whitted" => IntegratorKind::Whitted,
+            "ambientocclusion" => {
+                self.render.ao_distance = params.float("maxdistance").unwrap_or(f32::INFINITY);
+                IntegratorKind::AmbientOcclusion
+            }
+            _ => {
+                self.report(
+                    at,
+                    format!("Integrator \"{kind}\" not supported, using path"),
+                );
+                IntegratorKind::Path
+            }
+        };
+    }
+
+    fn material(&mut self, kind: &str, params: &ParamSet, at: &str) -> MaterialDef {
+        let grey = |x: f32| Vec3::new(x, x, x);
+        // Our roughness is the square root of the GGX alpha
+        let roughness = |default: f32, v3: bool| {
+            let r = params.float("roughness").unwrap_or_else(|| {
+                let u = params.float("uroughness");
+                let v = params.float("vroughness");
+                (u.unwrap_or(default) + v.unwrap_or(default)) / 2.
+            });
+            if r <= 0. || !params.bool("remaproughness").unwrap_or(true) {
+                r.max(0.).sqrt()
+            } else if v3 {
+                v3_roughness_to_alpha(r).sqrt()
+            } else {
+                r.sqrt().sqrt()
+            }
+        };
+        let material = match kind {
+            "matte" => MaterialDef::Matte(self.color(params, "Kd", grey(0.5), at)),
+            "diffuse" => MaterialDef::Matte(self.color(params, "reflectance", grey(0.5), at)),
+            "metal" | "conductor" => {
+                let roughness = roughness(if kind == "metal" { 0.01 } else { 0. }, kind == "metal");
+                let preset = |name: &str| -> Option<fn(f32) -> Conductor> {
+                    [
+                        ("Au", Conductor::gold as fn(f32) -> Conductor),
+                        ("Cu", Conductor::copper),
+                        ("Al", Conductor::aluminum),
+                    ]
+                    .into_iter()
+                    .find(|(symbol, _)| name.contains(symbol))
+                    .map(|(_, preset)| preset)
+                };
+                match (params.spectrum("eta"), params.spectrum("k")) {
+                    (None, None) => MaterialDef::ConductorPreset(Conductor::copper, roughness),
+                    (Some(Spectrum::Rgb(eta)), Some(Spectrum::Rgb(k))) => {
+                        MaterialDef::Conductor(eta, k, roughness)
+                    }
+                    (Some(Spectrum::Other(name)), _) if preset(&name).is_some() => {
+                        MaterialDef::ConductorPreset(preset(&name).unwrap(), roughness)
+                    }
+                    _ => {
+                        self.report(
+                            at,
+                            format!("Material \"{kind}\": eta and k other than rgb or Au, Cu, Al not supported, using copper"),
+                        );
+                        MaterialDef::ConductorPreset(Conductor::copper, roughness)
+                    }
+                }
+            }
+            "glass" | "dielectric" => {
+                let ior = params
+                    .float("eta")
+                    .or_else(|| params.float("index"))
+                    .unwrap_or(1.5);
+                MaterialDef::Glass(ior, roughness(0., kind == "glass"))
+            }
+            "plastic" => MaterialDef::Coated(
+                self.color(params, "Kd", grey(0.25), at),
+                1.5,
+                roughness(0.1, true),
+            ),
+            "coateddiffuse" => MaterialDef::Coated(
+                self.color(params, "reflectance", grey(0.5), at),
+                params.float("eta").unwrap_or(1.5),
+                roughness(0., false),
+            ),
+            "" | "none" | "interface" => MaterialDef::Interface,
+            _ => {
+                self.report(
+                    at,
+                    format!("Material \"{kind}\" not supported, using matte"),
+                );
+                return MaterialDef::Matte(grey(0.5));
+            }
+        };
+        // `type` names the material of `MakeNamedMaterial`
+        params.string("type");
+        self.report_unused(at, &format!("Material \"{kind}\""), params);
+        material
+    }
+
+    fn light(&mut self, kind: &str, params: &ParamSet, at: &str) {
+        let t = self.to_camera();
+        let scale = self.light_scale(params, at);
+        let from = params.point("from", Vec3::new(0., 0., 0.));
+        let to = params.point("to", Vec3::new(0., 0., 1.));
+        match kind {
+            "point" => {
+                let intensity = self.color(params, "I", Vec3::new(1., 1., 1.), at) * scale;
+                self.scene
+                    .analytic_lights
+                    .push(Box::new(PointLight::new(t.point(from), intensity)));
+            }
+            "spot" => {
+                let intensity = self.color(params, "I", Vec3::new(1., 1., 1.), at) * scale;
+                let cone = params.float("coneangle").unwrap_or(30.);
+                let delta = params.float("conedelta").unwrap_or(5.);
+                self.scene.analytic_lights.push(Box::new(SpotLight::new(
+                    t.point(from),
+                    t.point(to),
+                    intensity,
+                    (cone - delta).max(0.),
+                    cone,
+                )));
+            }
+            "distant" => {
+                let irradiance = self.color(params, "L", Vec3::new(1., 1., 1.), at) * scale;
+                self.scene
+                    .analytic_lights
+                    .push(Box::new(DirectionalLight::new(
+                        t.vector(to - from),
+                        irradiance,
+                    )));
+            }
+            "infinite" => {
+                if let Some(map) = params.string("filename") {
+                    self.report(
+                        at,
+                        format!("environment map \"{map}\" not supported, using a constant"),
+                    );
+                }
+                let radiance = self.color(params, "L", Vec3::new(1., 1., 1.), at);
+                self.background += radiance * scale;
+            }
+            _ => self.report(at, format!("LightSource \"{kind}\" not supported, ignored")),
+        }
+    }
+
+    fn shape(&mut self, kind: &str, params: &ParamSet, at: &str) -> Result<(), SceneError> {
+        let t = self.to_camera();
+        let geometry = match kind {
+            "sphere" => {
+                let radius = params.float("radius").unwrap_or(1.);
+                let axes = [
+                    Vec3::new(1., 0., 0.),
+                    Vec3::new(0., 1., 0.),
+                    Vec3::new(0., 0., 1.),
+                ]
+                .map(|a| t.vector(a).length());
+                let scale = (axes[0] + axes[1] + axes[2]) / 3.;
+                if axes.iter().any(|a| (a - scale).abs() > 1e-3 * scale) {
+                    self.report(
+                        at,
+                        "Shape \"sphere\": non-uniform scale not supported, using the mean"
+                            .to_string(),
+                    );
+                }
+                if self.state.reverse_orientation && self.state.area_light.is_some() {
+                    self.report(
+                        at,
+                        "Shape \"sphere\": inward facing emitters not supported".to_string(),
+                    );
+                }
+                Geometry::Sphere(t.point(Vec3::new(0., 0., 0.)), radius * scale)
+            }
+            "trianglemesh" => {
+                let positions = params.floats("P").unwrap_or_default();
+                let indices = match params.floats("indices") {
+                    Some(indices) => indices.into_iter().map(|i| i as usize).collect(),
+                    None if positions.len() == 9 => vec![0, 1, 2],
+                    None => vec![],
+                };
+                let normals = params.floats("N");
+                let uvs = params.floats("uv").or_else(|| params.floats("st"));
+                self.mesh(t, &positions, &indices, normals, uvs, at)?
+            }
+            "plymesh" => {
+                let Some(file) = params.string("filename") else {
+                    return Err(SceneError::invalid(at, "plymesh needs a filename"));
+                };
+                let path = self.base_dir.join(&file);
+                let mesh = ply::read(&path)
+                    .map_err(|e| SceneError::invalid(at, format!("{}: {e}", path.display())))?;
+                let positions: Vec<f32> = mesh
+                    .positions
+                    .iter()
+                    .flat_map(|p| [p.x(), p.y(), p.z()])
+                    .collect();
+                let indices: Vec<usize> = mesh.indices.concat();
+                let normals = mesh
+                    .normals
+                    .map(|n| n.iter().flat_map(|n| [n.x(), n.y(), n.z()]).collect());
+                let uvs = mesh
+                    .uvs
+                    .map(|uv| uv.iter().flat_map(|&(u, v)| [u, v]).collect());
+                self.mesh(t, &positions, &indices, normals, uvs, at)?
+            }
+            _ => {
+                self.report(at, format!("Shape \"{kind}\" not supported, skipped"));
+                params.0.iter().for_each(|p| p.used.set(true));
+                return Ok(());
+            }
+        };
+
+        let surface = self.state.material.build();
+        match self.state.area_light {
+            Some(AreaLight { emit, two_sided }) => {
+                // Emitters still reflect through their material, unless
+                // it is an interface
+                let light = || -> Box<dyn Material> {
+                    let light: Box<dyn Material> = if two_sided {
+                        Box::new(DiffuseLight::two_sided(emit))
+                    } else {
+                        Box::new(DiffuseLight::new(emit))
+                    };
+                    match self.state.material.build() {
+                        Some(surface) => Box::new(Emissive::new(light, surface)),
+                        None => light,
+                    }
+                };
+                let id = self.state.material_id;
+                self.scene.world.push(geometry.build(light(), id));
+                self.scene.lights.push(geometry.build(light(), id));
+            }
+            None => {
+                if let Some(material) = surface {
+                    let id = self.state.material_id;
+                    self.scene.world.push(geometry.build(material, id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Mesh from flat arrays in object space, moved into our camera space.
+    fn mesh(
+        &mut self,
+        t: Matrix,
+        positions: &[f32],
+        indices: &[usize],
+        normals: Option<Vec<f32>>,
+        uvs: Option<Vec<f32>>,
+        at: &str,
+    ) -> Result<Geometry, SceneError> {
+        if positions.is_empty() || !positions.len().is_multiple_of(3) {
+            return Err(SceneError::invalid(at, "\"P\" needs one point per vertex"));
+        }
+        if indices.is_empty() || !indices.len().is_multiple_of(3) {
+            return Err(SceneError::invalid(
+                at,
+                "\"indices\" needs three per triangle",
+            ));
+        }
+        let count = positions.len() / 3;
+        if let Some(&bad) = indices.iter().find(|&&i| i >= count) {
+            return Err(SceneError::invalid(
+                at,
+                format!("index {bad} out of range, there are {count} vertices"),
+            ));
+        }
+
+        // The winding decides which side emits, keep it facing pbrt's way
+        // even where the transform or ReverseOrientation mirrors it
+        let flip = self.state.reverse_orientation != (t.det3() < 0.);
+        let indices = indices
+            .chunks(3)
+            .map(|c| {
+                if flip {
+                    [c[0], c[2], c[1]]
+                } else {
+                    [c[0], c[1], c[2]]
+                }
+            })
+            .collect();
+        let positions = positions
+            .chunks(3)
+            .map(|p| t.point(Vec3::new(p[0], p[1], p[2])))
+            .collect();
+        let normals = match (normals, t.inverse()) {
+            (Some(n), Some(inverse)) if n.len() == count * 3 => {
+                let normal_matrix = inverse.transpose();
+                Some(
+                    n.chunks(3)
+                        .map(|n| {
+                            Vec3::unit_vector(normal_matrix.vector(Vec3::new(n[0], n[1], n[2])))
+                        })
+                        .collect(),
+                )
+            }
+            (Some(_), _) => {
+                self.report(at, "normals not matching \"P\" ignored".to_string());
+                None
+            }
+            (None, _) => None,
+        };
+        let uvs = match uvs {
+            Some(uv) if uv.len() == count * 2 => Some(uv.chunks(2).map(|c| (c[0], c[1])).collect()),
+            Some(_) => {
+                self.report(at, "uvs not matching \"P\" ignored".to_string());
+                None
+            }
+            None => None,
+        };
+        Ok(Geometry::Mesh {
+            positions,
+            indices,
+            normals,
+            uvs,
+        })
+    }
+
+    fn finish(mut self) -> PbrtImport {
+        let (w, h) = self.resolution;
+        let aspect = w as f32 / h as f32;
+        let (fov, at) = self.fov.clone();
+        // pbrt's field of view spans the shorter side
+        let vfov = if w >= h {
+            fov
+        } else {
+            2. * (degrees_to_radians(fov / 2.).tan() / aspect)
+                .atan()
+                .to_degrees()
+        };
+        let clamped = vfov.clamp(1., 179.);
+        if clamped != vfov {
+            self.report(
+                &at,
+                format!("field of view {vfov:.2}° clamped to {clamped}° vertically"),
+            );
+        }
+        let camera = Camera::new(aspect, w, self.samples_per_pixel).with_vfov(clamped);
+        let scene = self.scene.with_background(self.background);
+        PbrtImport {
+            description: SceneDescription {
+                camera,
+                scene,
+                render: self.render,
+            },
+            unsupported: self.unsupported,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::{interval::Interval, ray::Ray, utils::INFINITY};
+
+    fn hit_t(scene: &Scene, direction: Vec3) -> Option<f32> {
+        let ray = Ray::new(Vec3::new(0., 0., 0.), direction);
+        scene
+            .world
+            .hit(&ray, &Interval::new(0.001, INFINITY))
+            .map(|h| h.t)
+    }
+
+    #[test]
+    fn look_at_moves_the_world_in_front_of_the_camera() {
+        let text = r#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective" "float fov" [45]
+            Film "image" "integer xresolution" [200] "integer yresolution" [100]
+            WorldBegin
+            Material "matte" "rgb Kd" [0.2 0.4 0.6]
+            Shape "sphere" "float radius" 1
+            AttributeBegin
+              Translate 3 0 0
+              Scale 0.5 0.5 0.5
+              Shape "sphere"
+            AttributeEnd
+        "#;
+        let import = parse(text, Path::new(".")).unwrap();
+        let d = import.description;
+        assert_eq!((d.camera.image_width, d.camera.image_height()), (200, 100));
+        assert_eq!(d.camera.vfov, 45.);
+        let t = hit_t(&d.scene, Vec3::new(0., 0., -1.)).unwrap();
+        assert!((t - 4.).abs() < 1e-4, "{t}");
+        // pbrt's camera looks down +z with +x on the left, so world +x
+        // shows up on the left of the image
+        assert!(hit_t(&d.scene, Vec3::new(-3., 0., -5.)).is_some());
+        assert!(hit_t(&d.scene, Vec3::new(3., 0., -5.)).is_none());
+        assert!(import.unsupported.is_empty(), "{:?}", import.unsupported);
+    }
+
+    #[test]
+    fn materials_get_ids_in_declaration_order() {
+        let text = r#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective"
+            WorldBegin
+            MakeNamedMaterial "red" "string type" "diffuse" "rgb reflectance" [1 0 0]
+            MakeNamedMaterial "blue" "string type" "diffuse" "rgb reflectance" [0 0 1]
+            Shape "sphere" "float radius" 0.5
+            AttributeBegin
+              Translate -2 0 0
+              NamedMaterial "blue"
+              Shape "sphere" "float radius" 0.5
+            AttributeEnd
+            Translate 2 0 0
+            NamedMaterial "red"
+            Shape "sphere" "float radius" 0.5
+        "#;
+        let scene = parse(text, Path::new(".")).unwrap().description.scene;
+        let id = |x: f32| {
+            let ray = Ray::new(Vec3::new(x, 0., 0.), Vec3::new(0., 0., -1.));
+            scene
+                .world
+                .hit(&ray, &Interval::new(0.001, INFINITY))
+                .unwrap()
+                .material_id
+        };
+        // pbrt's +x is our -x; the default material is 1
+        assert_eq!((id(0.), id(2.), id(-2.)), (1, 3, 2));
+    }
+
+    #[test]
+    fn area_lights_emit_on_the_front() {
+        let quad = r#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "perspective"
+            WorldBegin
+            AttributeBegin
+              REVERSE
+              AreaLightSource "diffuse" "rgb L" [4 4 4]
+              Shape "trianglemesh" "point P" [-1 -1 0  1 -1 0  1 1 0  -1 1 0]
+                "integer indices" [0 1 2  0 2 3]
+            AttributeEnd
+        "#;
+        let emitted = |text: &str| {
+            let scene = parse(text, Path::new(".")).unwrap().description.scene;
+            assert_eq!(scene.lights.objects.len(), 1);
+            let ray = Ray::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., -1.));
+            let hit = scene
+                .world
+                .hit(&ray, &Interval::new(0.001, INFINITY))
+                .unwrap();
+            hit.material.emitted(&ray, &hit).x()
+        };
+        assert_eq!(emitted(&quad.replace("REVERSE", "")), 4.);
+        // and reflect through the default matte
+        let scene = parse(&quad.replace("REVERSE", ""), Path::new("."))
+            .unwrap()
+            .description
+            .scene;
+        let ray = Ray::new(Vec3::new(0., 0., 0.), Vec3::new(0., 0., -1.));
+        let hit = scene
+            .world
+            .hit(&ray, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        assert!(hit.material.scatter(&ray, &hit).is_some());
+        assert_eq!(emitted(&quad.replace("REVERSE", "ReverseOrientation")), 0.);
+        // unless they are two sided
+        let two_sided = quad.replace("[4 4 4]", "[4 4 4] \"bool twosided\" true");
+        assert_eq!(emitted(&two_sided.replace("REVERSE", "")), 4.);
+        assert_eq!(
+            emitted(&two_sided.replace("REVERSE", "ReverseOrientation")),
+            4.
+        );
+        // Mirroring the world keeps the light facing the same way
+        assert_eq!(emitted(&quad.replace("REVERSE", "Scale -1 1 1")), 4.);
+    }
+
+    #[test]
+    fn unsupported_features_are_reported() {
+        let text = r#"
+            Camera "perspective" "float fov" 30 "float lensradius" 0.1
+            Film "rgb" "integer xresolution" 100 "integer yresolution" 200
+            WorldBegin
+            Texture "checks" "spectrum" "checkerboard"
+            Material "hair"
+            Shape "disk"
+            Shape "sphere" "float zmax" 0.5
+        "#;
+        let import = parse(text, Path::new(".")).unwrap();
+        let expected = [
+            "input:2: Camera \"perspective\": parameter \"float lensradius\" ignored",
+            "input:5: Texture not supported, ignored",
+            "input:6: Material \"hair\" not supported, using matte",
+            "input:7: Shape \"disk\" not supported, skipped",
+            "input:8: Shape \"sphere\": parameter \"float zmax\" ignored",
+        ];
+        let mut reported = import.unsupported.clone();
+        reported.sort();
+        assert_eq!(reported, expected);
+        assert!((import.description.camera.vfov - 56.37).abs() < 0.01);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let key = |text: &str| match parse(text, Path::new(".")) {
+            Err(SceneError::Invalid { key, .. }) => key,
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("loaded"),
+        };
+        assert_eq!(key("WorldBegin\n\nAttributeEnd"), "input:3");
+        assert_eq!(key("WorldBegin\nNamedMaterial \"gold\""), "input:2");
+        assert_eq!(key("Shape \"sphere\" \"float radius [1]"), "input:1");
+        assert_eq!(
+            key("Shape \"trianglemesh\"\n \"point P\" [0 0 0 1 0 0 0 1 0] \"integer indices\" [0 1 3]"),
+            "input:1"
+        );
+    }
+
+    #[test]
+    fn includes_and_ply_files_are_relative_to_the_scene() {
+        let dir = std::env::temp_dir().join(format!("rstracer_pbrt_{}", std::process::id()));
+        fs::create_dir_all(dir.join("geometry")).unwrap();
+        fs::write(
+            dir.join("scene.pbrt"),
+            "Camera \"perspective\"\nWorldBegin\nInclude \"geometry/parts.pbrt\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("geometry/parts.pbrt"),
+            "Translate 0 0 3\nShape \"plymesh\" \"string filename\" \"geometry/quad.ply\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("geometry/quad.ply"),
+            "ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\nproperty float y\n\
+             property float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+             end_header\n-1 -1 0\n1 -1 0\n1 1 0\n-1 1 0\n4 0 1 2 3\n",
+        )
+        .unwrap();
+        let import = load(dir.join("scene.pbrt"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let scene = import.unwrap().description.scene;
+        let t = hit_t(&scene, Vec3::new(0., 0., -1.)).unwrap();
+        assert!((t - 3.).abs() < 1e-4, "{t}");
+    }
+}
@@ -0,0 +1,350 @@
+use std::{fs, io, path::Path};
+
+use super::vec3::Vec3;
+
+/// Triangle mesh read from a PLY file, polygons split into fans.
+#[derive(Debug, Default)]
+pub struct PlyMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<(f32, f32)>>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+pub fn read(path: impl AsRef<Path>) -> io::Result<PlyMesh> {
+    parse(&fs::read(path)?)
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> io::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(invalid(format!("unknown property type {name}"))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    /// Type of the count for list properties.
+    count: Option<Scalar>,
+    value: Scalar,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Values in file order, from text or binary data.
+struct Values<'a> {
+    format: Format,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Values<'_> {
+    fn next(&mut self, ty: Scalar) -> io::Result<f64> {
+        if self.format == Format::Ascii {
+            while self.data.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+                self.pos += 1;
+            }
+            let start = self.pos;
+            while self
+                .data
+                .get(self.pos)
+                .is_some_and(|c| !c.is_ascii_whitespace())
+            {
+                self.pos += 1;
+            }
+            let token = std::str::from_utf8(&self.data[start..self.pos]).unwrap_or("");
+            return token
+                .parse()
+                .map_err(|_| invalid(format!("expected a number, found \"{token}\"")));
+        }
+
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + ty.size())
+            .ok_or_else(|| invalid("file ends early"))?;
+        self.pos += ty.size();
+        let mut buf = [0u8; 8];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        if self.format == Format::BigEndian {
+            buf[..bytes.len()].reverse();
+        }
+        Ok(match ty {
+            Scalar::I8 => buf[0] as i8 as f64,
+            Scalar::U8 => buf[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(buf),
+        })
+    }
+}
+
+/// Parses ASCII and binary PLY. Reads vertex positions, normals and uvs
+/// (`u`/`v`, `s`/`t` or `texture_u`/`texture_v`) and face vertex lists,
+/// skipping any other element.
+pub fn parse(bytes: &[u8]) -> io::Result<PlyMesh> {
+    let (format, elements, body) = header(bytes)?;
+    let mut values = Values {
+        format,
+        data: &bytes[body..],
+        pos: 0,
+    };
+
+    let mut mesh = PlyMesh::default();
+    for element in &elements {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| p.count.is_none() && names.contains(&p.name.as_str()))
+        };
+        let (x, y, z) = (find(&["x"]), find(&["y"]), find(&["z"]));
+        let normal = (find(&["nx"]), find(&["ny"]), find(&["nz"]));
+        let uv = (
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        );
+        let is_vertex = element.name == "vertex";
+        if is_vertex {
+            if x.is_none() || y.is_none() || z.is_none() {
+                return Err(invalid("vertex element without x, y and z"));
+            }
+            if let (Some(_), Some(_), Some(_)) = normal {
+                mesh.normals = Some(Vec::with_capacity(element.count));
+            }
+            if let (Some(_), Some(_)) = uv {
+                mesh.uvs = Some(Vec::with_capacity(element.count));
+            }
+        }
+
+        let mut row = vec![0.; element.properties.len()];
+        for _ in 0..element.count {
+            let mut polygon = vec![];
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.count {
+                    None => row[i] = values.next(property.value)?,
+                    Some(count_type) => {
+                        let count = values.next(count_type)? as usize;
+                        let list = (0..count)
+                            .map(|_| values.next(property.value))
+                            .collect::<io::Result<Vec<_>>>()?;
+                        let is_indices =
+                            matches!(property.name.as_str(), "vertex_indices" | "vertex_index");
+                        if element.name == "face" && is_indices {
+                            polygon = list;
+                        }
+                    }
+                }
+            }
+
+            let get = |i: Option<usize>| i.map(|i| row[i] as f32).unwrap_or(0.);
+            if is_vertex {
+                mesh.positions.push(Vec3::new(get(x), get(y), get(z)));
+                if let Some(normals) = &mut mesh.normals {
+                    normals.push(Vec3::new(get(normal.0), get(normal.1), get(normal.2)));
+                }
+                if let Some(uvs) = &mut mesh.uvs {
+                    uvs.push((get(uv.0), get(uv.1)));
+                }
+            }
+            for k in 1..polygon.len().saturating_sub(1) {
+                mesh.indices.push([
+                    polygon[0] as usize,
+                    polygon[k] as usize,
+                    polygon[k + 1] as usize,
+                ]);
+            }
+        }
+    }
+
+    if let Some(&bad) = mesh
+        .indices
+        .iter()
+        .flatten()
+        .find(|&&i| i >= mesh.positions.len())
+    {
+        return Err(invalid(format!(
+            "face uses vertex {bad}, there are {}",
+            mesh.positions.len()
+        )));
+    }
+    Ok(mesh)
+}
+
+/// Format, elements and where the data starts.
+fn header(bytes: &[u8]) -> io::Result<(Format, Vec<Element>, usize)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut pos = 0;
+    let mut first = true;
+    loop {
+        let end = bytes[pos..]
+            .iter()
+            .position(|&c| c == b'\n')
+            .ok_or_else(|| invalid("header without end_header"))?;
+        let line = String::from_utf8_lossy(&bytes[pos..pos + end]);
+        pos += end + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if first {
+            if words != ["ply"] {
+                return Err(invalid("not a PLY file"));
+            }
+            first = false;
+            continue;
+        }
+        match words.as_slice() {
+            ["format", f, _] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(invalid(format!("unknown format {f}"))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("bad element count {count}")))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, value, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("property before any element"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    count: Some(Scalar::parse(count)?),
+                    value: Scalar::parse(value)?,
+                });
+            }
+            ["property", value, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("property before any element"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    count: None,
+                    value: Scalar::parse(value)?,
+                });
+            }
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid(format!("unexpected header line \"{line}\""))),
+        }
+    }
+    let format = format.ok_or_else(|| invalid("header without format"))?;
+    Ok((format, elements, pos))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property float u
+property float v
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    #[test]
+    fn ascii_quad() {
+        let text = format!(
+            "ply\nformat ascii 1.0\ncomment a quad\n{HEADER}0 0 0 0 0\n1 0 0 1 0\n1 1 0 1 1\n0 1 0 0 1\n4 0 1 2 3\n"
+        );
+        let mesh = parse(text.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.uvs.unwrap()[2], (1., 1.));
+        assert!(mesh.normals.is_none());
+    }
+
+    #[test]
+    fn binary_matches_ascii() {
+        let positions = [[0f32, 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]];
+        for (name, big) in [("binary_little_endian", false), ("binary_big_endian", true)] {
+            let mut bytes = format!("ply\nformat {name} 1.0\n{HEADER}").into_bytes();
+            let mut push = |b: &[u8]| bytes.extend(b);
+            for p in positions {
+                for c in [p[0], p[1], p[2], p[0], p[1]] {
+                    push(&if big {
+                        c.to_be_bytes()
+                    } else {
+                        c.to_le_bytes()
+                    });
+                }
+            }
+            push(&[3]);
+            for i in [0i32, 1, 2] {
+                push(&if big {
+                    i.to_be_bytes()
+                } else {
+                    i.to_le_bytes()
+                });
+            }
+            let mesh = parse(&bytes).unwrap();
+            assert_eq!(mesh.positions[2], Vec3::new(1., 1., 0.));
+            assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_indices() {
+        let text = format!(
+            "ply\nformat ascii 1.0\n{HEADER}0 0 0 0 0\n1 0 0 1 0\n1 1 0 1 1\n0 1 0 0 1\n3 0 1 7\n"
+        );
+        assert!(parse(text.as_bytes()).is_err());
+    }
+}
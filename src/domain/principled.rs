@@ -0,0 +1,231 @@
+use super::{
+    hittable::Hit,
+    material::{fr_dielectric, shading_frame, Material},
+    microfacet::{reflect, refract, TrowbridgeReitz},
+    ray::Ray,
+    spectrum::rgb_at,
+    utils::{random_f32, PI},
+    vec3::Vec3,
+};
+
+/// Disney style principled BSDF. A diffuse base with sheen, a GGX specular
+/// lobe blending from dielectric to metal, GGX transmission and a clear coat,
+/// every lobe importance sampled and combined by one-sample MIS.
+#[derive(Clone)]
+pub struct Principled {
+    pub base_color: Vec3,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Dielectric reflectance, 0.5 is the usual 4% at normal incidence.
+    pub specular: f32,
+    /// Grazing retro-reflection for cloth.
+    pub sheen: f32,
+    pub clearcoat: f32,
+    pub transmission: f32,
+}
+
+/// Lobes `scatter` picks from.
+#[derive(Clone, Copy)]
+enum Lobe {
+    Diffuse,
+    Specular,
+    Transmission,
+    Clearcoat,
+}
+
+impl Principled {
+    /// Roughness of the clear coat layer.
+    const CLEARCOAT_ROUGHNESS: f32 = 0.1;
+    /// Index of refraction of the clear coat layer.
+    const CLEARCOAT_IOR: f32 = 1.5;
+
+    pub fn new(base_color: Vec3) -> Self {
+        Self {
+            base_color,
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            sheen: 0.,
+            clearcoat: 0.,
+            transmission: 0.,
+        }
+    }
+
+    /// Relative index of refraction implied by `specular`, inverted when
+    /// leaving the surface.
+    fn eta(&self, hit: &Hit) -> f32 {
+        let f0 = (0.08 * self.specular).clamp(0., 0.99).sqrt();
+        let ior = (1. + f0) / (1. - f0);
+        if hit.front_face {
+            ior
+        } else {
+            1. / ior
+        }
+    }
+
+    /// Lobe selection weights, roughly their share of reflected energy.
+    fn lobe_weights(&self, wo: Vec3, eta: f32) -> [(Lobe, f32); 4] {
+        let dielectric = 1. - self.metallic;
+        let fresnel = fr_dielectric(wo.z(), eta);
+        [
+            (Lobe::Diffuse, dielectric * (1. - self.transmission)),
+            (Lobe::Specular, self.metallic + dielectric * fresnel),
+            (
+                Lobe::Transmission,
+                dielectric * self.transmission * (1. - fresnel),
+            ),
+            (Lobe::Clearcoat, 0.25 * self.clearcoat),
+        ]
+    }
+
+    /// BSDF times cosine and the pdf of `scatter` producing `wi`, in the
+    /// shading frame.
+    fn evaluate(&self, wo: Vec3, wi: Vec3, eta: f32, wavelength: f32) -> (Vec3, f32) {
+        let zero = (Vec3::new(0., 0., 0.), 0.);
+        let (cos_o, cos_i) = (wo.z(), wi.z());
+        if cos_o <= 0. || cos_i == 0. {
+            return zero;
+        }
+        let base = rgb_at(self.base_color, wavelength);
+        let dielectric = 1. - self.metallic;
+        let specular = TrowbridgeReitz::new(self.roughness);
+
+        let weights = self.lobe_weights(wo, eta);
+        let total: f32 = weights.iter().map(|(_, w)| w).sum();
+        if total <= 0. {
+            return zero;
+        }
+        let weight = |lobe: usize| weights[lobe].1 / total;
+
+        let mut f = Vec3::new(0., 0., 0.);
+        let mut pdf = 0.;
+        if cos_i > 0. {
+            let wm = Vec3::unit_vector(wo + wi);
+            let cos_d = wi.dot(wm);
+            let diffuse_weight = dielectric * (1. - self.transmission);
+
+            // What the specular lobe and the clear coat reflect on the way in
+            // or out never reaches the base
+            let coat = |c: f32| 1. - 0.25 * self.clearcoat * fr_dielectric(c, Self::CLEARCOAT_IOR);
+            let transmitted = |c: f32| (1. - fr_dielectric(c, eta)) * coat(c);
+
+            // Burley diffuse with grazing retro-reflection, plus sheen
+            let fd90 = 0.5 + 2. * self.roughness * cos_d * cos_d;
+            let fd = |c: f32| 1. + (fd90 - 1.) * (1. - c).powi(5);
+            let diffuse = base * (fd(cos_o) * fd(cos_i) / PI);
+            let sheen = self.sheen * (1. - cos_d).powi(5);
+            let under = diffuse_weight * transmitted(cos_o) * transmitted(cos_i);
+            f += (diffuse + Vec3::new(sheen, sheen, sheen)) * (under * cos_i);
+            pdf += weight(0) * cos_i / PI;
+
+            // Specular, dielectric Fresnel blended with tinted Schlick for metal
+            let cos_m = wo.dot(wm);
+            if cos_m > 0. {
+                let schlick = base + (Vec3::new(1., 1., 1.) - base) * (1. - cos_m).powi(5);
+                let fr = fr_dielectric(cos_m, eta);
+                let fresnel = Vec3::new(fr, fr, fr) * dielectric + schlick * self.metallic;
+                let d = specular.d(wm) * specular.g(wo, wi) / (4. * cos_o);
+                f += fresnel * d;
+                pdf += weight(1) * specular.d_visible(wo, wm) / (4. * cos_m);
+
+                let coat = TrowbridgeReitz::new(Self::CLEARCOAT_ROUGHNESS);
+                let fc = fr_dielectric(cos_m, Self::CLEARCOAT_IOR);
+                let dc = coat.d(wm) * coat.g(wo, wi) / (4. * cos_o);
+                let c = 0.25 * self.clearcoat * fc * dc;
+                f += Vec3::new(c, c, c);
+                pdf += weight(3) * coat.d_visible(wo, wm) / (4. * cos_m);
+            }
+        } else {
+            // Generalized half vector, facing the outgoing side
+            let wm = wi * eta + wo;
+            if wm.near_zero() {
+                return zero;
+            }
+            let mut wm = Vec3::unit_vector(wm);
+            if wm.z() < 0. {
+                wm = -wm;
+            }
+            if wm.dot(wi) >= 0. || wm.dot(wo) <= 0. {
+                return zero;
+            }
+            let denom = (wi.dot(wm) + wo.dot(wm) / eta).powi(2);
+            let fr = fr_dielectric(wo.dot(wm), eta);
+            let t = specular.d(wm)
+                * specular.g(wo, wi)
+                * (1. - fr)
+                * (wi.dot(wm) * wo.dot(wm) / (cos_o * denom)).abs();
+            f += base * (dielectric * self.transmission * t);
+            pdf += weight(2) * specular.d_visible(wo, wm) * wi.dot(wm).abs() / denom;
+        }
+        (f, pdf)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        let (frame, wo) = shading_frame(ray, hit);
+        if wo.z() <= 0. {
+            return None;
+        }
+        let eta = self.eta(hit);
+
+        let weights = self.lobe_weights(wo, eta);
+        let total: f32 = weights.iter().map(|(_, w)| w).sum();
+        let mut u = random_f32() * total;
+        let mut lobe = weights[0].0;
+        for (l, w) in weights {
+            if w > 0. {
+                lobe = l;
+                if u < w {
+                    break;
+                }
+                u -= w;
+            }
+        }
+
+        let wi = match lobe {
+            Lobe::Diffuse => Vec3::random_cosine_direction(),
+            Lobe::Specular => {
+                let wm =
+                    TrowbridgeReitz::new(self.roughness).sample_wm(wo, random_f32(), random_f32());
+                reflect(wo, wm)
+            }
+            Lobe::Transmission => {
+                let wm =
+                    TrowbridgeReitz::new(self.roughness).sample_wm(wo, random_f32(), random_f32());
+                refract(wo, wm, eta)?
+            }
+            Lobe::Clearcoat => {
+                let wm = TrowbridgeReitz::new(Self::CLEARCOAT_ROUGHNESS).sample_wm(
+                    wo,
+                    random_f32(),
+                    random_f32(),
+                );
+                reflect(wo, wm)
+            }
+        };
+
+        let (f, pdf) = self.evaluate(wo, wi, eta, ray.wavelength());
+        if pdf <= 0. {
+            return None;
+        }
+        let scattered = Ray::new(hit.p, frame.local(wi)).with_wavelength(ray.wavelength());
+        Some((f / pdf, scattered))
+    }
+
+    fn albedo(&self, _hit: &Hit) -> Vec3 {
+        self.base_color
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        let (frame, wo) = shading_frame(ray, hit);
+        let wi = frame.to_local(Vec3::unit_vector(scattered.direction()));
+        self.evaluate(wo, wi, self.eta(hit), ray.wavelength()).0
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        let (frame, wo) = shading_frame(ray, hit);
+        let wi = frame.to_local(Vec3::unit_vector(scattered.direction()));
+        self.evaluate(wo, wi, self.eta(hit), ray.wavelength()).1
+    }
+}
@@ -4,11 +4,25 @@ use super::vec3::Vec3;
 pub struct Ray {
     u: Vec3,
     v: Vec3,
+    /// Wavelength in nm carried by spectral paths, zero for RGB.
+    wavelength: f32,
 }
 
 impl Ray {
     pub fn new(u: Vec3, v: Vec3) -> Ray {
-        Ray { u, v }
+        Ray {
+            u,
+            v,
+            wavelength: 0.,
+        }
+    }
+
+    pub fn with_wavelength(self, wavelength: f32) -> Ray {
+        Ray { wavelength, ..self }
+    }
+
+    pub fn wavelength(self) -> f32 {
+        self.wavelength
     }
 
     pub fn origin(self) -> Vec3 {
@@ -0,0 +1,20 @@
+use super::utils::random_f32;
+
+/// Source of the sample values an integrator spends on its own decisions.
+pub trait Sampler {
+    fn get_1d(&mut self) -> f32;
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// Independent uniform samples from the global generator.
+#[derive(Clone, Copy, Default)]
+pub struct RandomSampler;
+
+impl Sampler for RandomSampler {
+    fn get_1d(&mut self) -> f32 {
+        random_f32()
+    }
+}
@@ -0,0 +1,83 @@
+use super::{
+    hittable::{Hit, Hittable},
+    hittable_list::HittableList,
+    interval::Interval,
+    light::Light,
+    light_bvh::LightBvh,
+    ray::Ray,
+    sky::Sky,
+    spectrum::rgb_at,
+    stats::{self, Counter},
+    vec3::Vec3,
+};
+
+pub struct Scene {
+    pub world: HittableList,
+    /// Emitters of `world` that integrators sample directly.
+    pub lights: HittableList,
+    /// Point, spot and distant lights, reached only through shadow rays.
+    pub analytic_lights: Vec<Box<dyn Light>>,
+    /// Daylight replacing the gradient background.
+    pub sky: Option<Sky>,
+    /// Uniform radiance replacing the gradient background, unless there is
+    /// a sky.
+    pub background_color: Option<Vec3>,
+}
+
+impl Scene {
+    pub fn new(world: HittableList, lights: HittableList) -> Self {
+        Self {
+            world,
+            lights,
+            analytic_lights: vec![],
+            sky: None,
+            background_color: None,
+        }
+    }
+
+    /// Samples `lights` through a `LightBvh`, for scenes with many of them.
+    pub fn with_light_bvh(mut self) -> Self {
+        let lights = std::mem::take(&mut self.lights);
+        self.lights.push(Box::new(LightBvh::new(lights)));
+        self
+    }
+
+    /// Uniform background, black for closed scenes lit only by their lights.
+    pub fn with_background(mut self, color: Vec3) -> Self {
+        self.background_color = Some(color);
+        self
+    }
+
+    /// Lights the scene with `sky` and its sun.
+    pub fn with_sky(mut self, sky: Sky) -> Self {
+        self.analytic_lights.push(Box::new(sky.sun()));
+        self.sky = Some(sky);
+        self
+    }
+
+    pub fn with_light(mut self, light: impl Light + 'static) -> Self {
+        self.analytic_lights.push(Box::new(light));
+        self
+    }
+
+    /// Closest hit in `world`. Integrators trace through here so the ray
+    /// is counted.
+    pub fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit<'_>> {
+        stats::count(Counter::Rays);
+        self.world.hit(ray, interval)
+    }
+
+    /// Radiance arriving along rays that leave the scene.
+    pub fn background(&self, ray: &Ray) -> Vec3 {
+        if let Some(sky) = &self.sky {
+            return rgb_at(sky.radiance(ray.direction()), ray.wavelength());
+        }
+        if let Some(color) = self.background_color {
+            return rgb_at(color, ray.wavelength());
+        }
+        let unit_direction = Vec3::unit_vector(ray.direction());
+        let a = (unit_direction.y() + 1.0) * 0.5;
+        let color = Vec3::new(1., 1., 1.) * (1.0 - a) + Vec3::new(0.5, 0.7, 1.) * a;
+        rgb_at(color, ray.wavelength())
+    }
+}
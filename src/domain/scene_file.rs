@@ -0,0 +1,1154 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use toml::{Spanned, Table};
+
+use super::{
+    alpha_mask::AlphaMask,
+    aov::Aov,
+    bdpt::BdptIntegrator,
+    camera::Camera,
+    coated::Coated,
+    hittable::Hittable,
+    hittable_list::HittableList,
+    integrator::{AmbientOcclusion, DirectLighting, Integrator, PathIntegrator, WhittedIntegrator},
+    light::{DirectionalLight, PointLight, SpotLight},
+    material::{
+        Conductor, Dialectric, DiffuseLight, Ior, Lambertian, Material, Metal, RoughDielectric,
+        Subsurface, ThinFilm,
+    },
+    medium::Medium,
+    mesh::TriangleMesh,
+    normal_map::{BumpMap, NormalMap},
+    principled::Principled,
+    scene::Scene,
+    sky::Sky,
+    sphere::Sphere,
+    sppm::SppmIntegrator,
+    texture::ImageTexture,
+    two_sided::TwoSided,
+    utils::degrees_to_radians,
+    vec3::Vec3,
+};
+
+/// Schema version this loader reads, the `version` key of every file.
+pub const VERSION: u32 = 1;
+
+/// Why a scene file couldn't be loaded. Every variant says where: the file,
+/// the line, or the dotted key of the offending value.
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, io::Error),
+    /// Bad syntax, a missing or unknown key or a value of the wrong type.
+    Parse(toml::de::Error),
+    /// Well formed but meaningless, e.g. a material name nobody defined.
+    Invalid {
+        key: String,
+        message: String,
+    },
+}
+
+impl SceneError {
+    pub fn invalid(key: impl Into<String>, message: impl Into<String>) -> Self {
+        SceneError::Invalid {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(path, e) => write!(f, "{}: {e}", path.display()),
+            SceneError::Parse(e) => write!(f, "{e}"),
+            SceneError::Invalid { key, message } => write!(f, "{key}: {message}"),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io(_, e) => Some(e),
+            SceneError::Parse(e) => Some(e),
+            SceneError::Invalid { .. } => None,
+        }
+    }
+}
+
+impl From<toml::de::Error> for SceneError {
+    fn from(e: toml::de::Error) -> Self {
+        SceneError::Parse(e)
+    }
+}
+
+/// Everything a scene file describes, ready to render.
+pub struct SceneDescription {
+    pub camera: Camera,
+    pub scene: Scene,
+    pub render: RenderSettings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorKind {
+    Path,
+    /// Path tracing with one wavelength per sample.
+    Spectral,
+    Bdpt,
+    Sppm,
+    Whitted,
+    Direct,
+    AmbientOcclusion,
+}
+
+impl IntegratorKind {
+    pub const ALL: [IntegratorKind; 7] = [
+        IntegratorKind::Path,
+        IntegratorKind::Spectral,
+        IntegratorKind::Bdpt,
+        IntegratorKind::Sppm,
+        IntegratorKind::Whitted,
+        IntegratorKind::Direct,
+        IntegratorKind::AmbientOcclusion,
+    ];
+
+    /// Name as written in scene files.
+    pub fn name(self) -> &'static str {
+        match self {
+            IntegratorKind::Path => "path",
+            IntegratorKind::Spectral => "spectral",
+            IntegratorKind::Bdpt => "bdpt",
+            IntegratorKind::Sppm => "sppm",
+            IntegratorKind::Whitted => "whitted",
+            IntegratorKind::Direct => "direct",
+            IntegratorKind::AmbientOcclusion => "ambient_occlusion",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<IntegratorKind> {
+        IntegratorKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+/// The `[render]` table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    pub integrator: IntegratorKind,
+    pub max_depth: i32,
+    /// Pick lights with a `LightBvh` rather than uniformly.
+    pub light_bvh: bool,
+    pub photons_per_pass: i32,
+    pub initial_radius: f32,
+    pub ao_samples: i32,
+    pub ao_distance: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            integrator: IntegratorKind::Path,
+            max_depth: 50,
+            light_bvh: false,
+            photons_per_pass: 20_000,
+            initial_radius: 0.1,
+            ao_samples: 8,
+            ao_distance: 1.,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn integrator(&self, camera: &Camera) -> Box<dyn Integrator> {
+        match self.integrator {
+            IntegratorKind::Path => Box::new(PathIntegrator::new(self.max_depth)),
+            IntegratorKind::Spectral => Box::new(PathIntegrator {
+                spectral: true,
+                ..PathIntegrator::new(self.max_depth)
+            }),
+            IntegratorKind::Bdpt => Box::new(BdptIntegrator::new(camera, self.max_depth)),
+            IntegratorKind::Sppm => Box::new(SppmIntegrator::new(
+                self.photons_per_pass,
+                self.initial_radius,
+                self.max_depth,
+            )),
+            IntegratorKind::Whitted => Box::new(WhittedIntegrator::new(self.max_depth)),
+            IntegratorKind::Direct => Box::new(DirectLighting::new(self.max_depth)),
+            IntegratorKind::AmbientOcclusion => {
+                Box::new(AmbientOcclusion::new(self.ao_samples, self.ao_distance))
+            }
+        }
+    }
+}
+
+/// Reads a TOML scene file. Image paths in it are relative to the file.
+pub fn load(path: impl AsRef<Path>) -> Result<SceneDescription, SceneError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|e| SceneError::Io(path.to_path_buf(), e))?;
+    parse(&text, path.parent().unwrap_or(Path::new(".")))
+}
+
+/// Builds the scene in `text`, with image paths relative to `base_dir`.
+pub fn parse(text: &str, base_dir: &Path) -> Result<SceneDescription, SceneError> {
+    // Check the version before the schema it decides
+    #[derive(Deserialize)]
+    struct Header {
+        version: Option<u32>,
+    }
+    match toml::from_str::<Header>(text)?.version {
+        Some(VERSION) => {}
+        Some(v) => {
+            return Err(SceneError::invalid(
+                "version",
+                format!("unsupported version {v}, this build reads version {VERSION}"),
+            ))
+        }
+        None => {
+            return Err(SceneError::invalid(
+                "version",
+                format!("missing, add `version = {VERSION}` at the top"),
+            ))
+        }
+    }
+    let file: SceneFile = toml::from_str(text)?;
+    let objects: Vec<ObjectSpec> = entries(text, "objects", &file.objects)?;
+    let analytic: Vec<LightSpec> = entries(text, "lights", &file.lights)?;
+
+    let camera = file.camera.build()?;
+    let mut materials = Materials {
+        specs: &file.materials,
+        base_dir,
+        images: HashMap::new(),
+    };
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+    for (i, object) in objects.iter().enumerate() {
+        let key = format!("objects[{i}]");
+        world.push(object.build(&key, &mut materials)?);
+        if materials.emits(object.material(), &format!("{key}.material"))? {
+            lights.push(object.build(&key, &mut materials)?);
+        }
+    }
+
+    let mut scene = Scene::new(world, lights);
+    for light in &analytic {
+        scene = match *light {
+            LightSpec::Point {
+                position,
+                intensity,
+            } => scene.with_light(PointLight::new(vec3(position), vec3(intensity))),
+            LightSpec::Spot {
+                position,
+                target,
+                intensity,
+                inner_angle,
+                outer_angle,
+            } => scene.with_light(SpotLight::new(
+                vec3(position),
+                vec3(target),
+                vec3(intensity),
+                inner_angle,
+                outer_angle,
+            )),
+            LightSpec::Directional {
+                direction,
+                irradiance,
+                angular_diameter,
+            } => scene.with_light(
+                DirectionalLight::new(vec3(direction), vec3(irradiance))
+                    .with_angular_diameter(angular_diameter),
+            ),
+        };
+    }
+    if let Some(color) = file.background {
+        scene = scene.with_background(vec3(color));
+    }
+    if let Some(sky) = &file.sky {
+        let mut built = Sky::new(sky.elevation, sky.azimuth, sky.turbidity);
+        if let Some(albedo) = sky.ground_albedo {
+            built = built.with_ground_albedo(vec3(albedo));
+        }
+        if let Some(exposure) = sky.exposure {
+            built = built.with_exposure(exposure);
+        }
+        scene = scene.with_sky(built);
+    }
+    if file.render.light_bvh {
+        scene = scene.with_light_bvh();
+    }
+
+    Ok(SceneDescription {
+        camera,
+        scene,
+        render: file.render,
+    })
+}
+
+/// Reads each table of the array `key` on its own, so errors name the entry
+/// and the line of the offending key rather than the start of the array.
+fn entries<T: DeserializeOwned>(
+    text: &str,
+    key: &str,
+    tables: &[Spanned<Table>],
+) -> Result<Vec<T>, SceneError> {
+    tables
+        .iter()
+        .enumerate()
+        .map(|(i, table)| {
+            read_table::<T>(table.get_ref()).map_err(|message| {
+                let culprit = offending_key::<T>(table.get_ref(), &message);
+                let line = line_of(text, table.span(), culprit.as_deref());
+                SceneError::invalid(format!("{key}[{i}]"), format!("{message} (line {line})"))
+            })
+        })
+        .collect()
+}
+
+fn read_table<T: DeserializeOwned>(table: &Table) -> Result<T, String> {
+    toml::Value::Table(table.clone())
+        .try_into()
+        .map_err(|e: toml::de::Error| e.message().to_string())
+}
+
+/// The key whose removal changes the error `message`, since values read
+/// out of the document no longer know where they were.
+fn offending_key<T: DeserializeOwned>(table: &Table, message: &str) -> Option<String> {
+    if message.starts_with("missing field") {
+        return None;
+    }
+    table
+        .keys()
+        .find(|key| {
+            let mut rest = table.clone();
+            rest.remove(*key);
+            read_table::<T>(&rest).err().as_deref() != Some(message)
+        })
+        .cloned()
+}
+
+/// Line of `key` within `span`, or of the span's start without one.
+fn line_of(text: &str, span: std::ops::Range<usize>, key: Option<&str>) -> usize {
+    let first = text[..span.start].matches('\n').count() + 1;
+    let Some(key) = key else {
+        return first;
+    };
+    text[span]
+        .lines()
+        .position(|line| {
+            line.trim_start()
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with(['=', '.']))
+        })
+        .map_or(first, |i| first + i)
+}
+
+fn vec3([x, y, z]: [f32; 3]) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[allow(dead_code)]
+    version: u32,
+    #[serde(default)]
+    camera: CameraSpec,
+    #[serde(default)]
+    render: RenderSettings,
+    #[serde(default)]
+    materials: HashMap<String, MaterialSpec>,
+    #[serde(default)]
+    objects: Vec<Spanned<Table>>,
+    #[serde(default)]
+    lights: Vec<Spanned<Table>>,
+    sky: Option<SkySpec>,
+    /// Uniform background color instead of the default gradient.
+    background: Option<[f32; 3]>,
+}
+
+/// The fields of `Camera`.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraSpec {
+    aspect_ratio: f32,
+    image_width: i32,
+    samples_per_pixel: i32,
+    vfov: f32,
+    /// Names as written by `Aov::name`.
+    aovs: Vec<String>,
+}
+
+impl Default for CameraSpec {
+    fn default() -> Self {
+        Self {
+            aspect_ratio: 16. / 9.,
+            image_width: 400,
+            samples_per_pixel: 100,
+            vfov: 90.,
+            aovs: vec![],
+        }
+    }
+}
+
+impl CameraSpec {
+    fn build(&self) -> Result<Camera, SceneError> {
+        if !self.aspect_ratio.is_finite() || self.aspect_ratio <= 0. {
+            return Err(SceneError::invalid(
+                "camera.aspect_ratio",
+                "must be positive",
+            ));
+        }
+        if self.image_width <= 0 {
+            return Err(SceneError::invalid(
+                "camera.image_width",
+                "must be positive",
+            ));
+        }
+        if self.samples_per_pixel <= 0 {
+            return Err(SceneError::invalid(
+                "camera.samples_per_pixel",
+                "must be positive",
+            ));
+        }
+        if !(self.vfov > 0. && self.vfov < 180.) {
+            return Err(SceneError::invalid(
+                "camera.vfov",
+                "must be between 0 and 180 degrees",
+            ));
+        }
+        let mut camera = Camera::new(self.aspect_ratio, self.image_width, self.samples_per_pixel)
+            .with_vfov(self.vfov);
+        for (i, name) in self.aovs.iter().enumerate() {
+            let aov = Aov::ALL
+                .into_iter()
+                .find(|a| a.name() == name)
+                .ok_or_else(|| {
+                    let names: Vec<_> = Aov::ALL.iter().map(|a| a.name()).collect();
+                    SceneError::invalid(
+                        format!("camera.aovs[{i}]"),
+                        format!(
+                            "unknown AOV \"{name}\", expected one of {}",
+                            names.join(", ")
+                        ),
+                    )
+                })?;
+            camera.aovs.push(aov);
+        }
+        Ok(camera)
+    }
+}
+
+/// A fixed index or the name of a glass in `Ior`.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+enum IorSpec {
+    Value(f32),
+    Named(String),
+}
+
+impl IorSpec {
+    fn build(&self, key: &str) -> Result<Ior, SceneError> {
+        match self {
+            IorSpec::Value(n) => Ok(Ior::Constant(*n)),
+            IorSpec::Named(name) => match name.as_str() {
+                "bk7" => Ok(Ior::bk7()),
+                "flint" => Ok(Ior::flint()),
+                "diamond" => Ok(Ior::diamond()),
+                _ => Err(SceneError::invalid(
+                    key,
+                    format!("unknown glass \"{name}\", expected a number or bk7, flint, diamond"),
+                )),
+            },
+        }
+    }
+}
+
+fn default_ior() -> IorSpec {
+    IorSpec::Value(1.5)
+}
+
+fn default_air() -> IorSpec {
+    IorSpec::Value(1.)
+}
+
+fn one() -> f32 {
+    1.
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialSpec {
+    Lambertian {
+        albedo: [f32; 3],
+    },
+    Metal {
+        albedo: [f32; 3],
+        #[serde(default)]
+        fuzz: f32,
+    },
+    Dielectric {
+        #[serde(default = "default_ior")]
+        ior: IorSpec,
+    },
+    DiffuseLight {
+        emit: [f32; 3],
+    },
+    /// Either a `preset` metal or its complex index `eta` and `k`.
+    Conductor {
+        preset: Option<String>,
+        eta: Option<[f32; 3]>,
+        k: Option<[f32; 3]>,
+        #[serde(default)]
+        roughness: f32,
+    },
+    RoughDielectric {
+        #[serde(default = "default_ior")]
+        ior: IorSpec,
+        roughness: f32,
+    },
+    ThinFilm {
+        thickness: f32,
+        film_ior: f32,
+        #[serde(default = "default_air")]
+        base_ior: IorSpec,
+    },
+    Principled {
+        base_color: [f32; 3],
+        metallic: Option<f32>,
+        roughness: Option<f32>,
+        specular: Option<f32>,
+        sheen: Option<f32>,
+        clearcoat: Option<f32>,
+        transmission: Option<f32>,
+    },
+    /// Dielectric layer over the material named `base`.
+    Coated {
+        base: String,
+        #[serde(default = "one_and_a_half")]
+        ior: f32,
+        #[serde(default)]
+        roughness: f32,
+        absorption: Option<[f32; 3]>,
+        #[serde(default = "one")]
+        thickness: f32,
+    },
+    Subsurface {
+        #[serde(default = "one_and_a_half")]
+        ior: f32,
+        albedo: [f32; 3],
+        mean_free_path: [f32; 3],
+        #[serde(default)]
+        g: f32,
+    },
+    TwoSided {
+        front: String,
+        back: String,
+    },
+    /// Cutout by the alpha channel of the `mask` image.
+    AlphaMask {
+        material: String,
+        mask: PathBuf,
+    },
+    NormalMap {
+        material: String,
+        map: PathBuf,
+    },
+    BumpMap {
+        material: String,
+        height: PathBuf,
+        #[serde(default = "one")]
+        scale: f32,
+    },
+}
+
+fn one_and_a_half() -> f32 {
+    1.5
+}
+
+/// Builds named materials on demand, a fresh one per object using it.
+struct Materials<'a> {
+    specs: &'a HashMap<String, MaterialSpec>,
+    base_dir: &'a Path,
+    images: HashMap<(PathBuf, bool), ImageTexture>,
+}
+
+impl Materials<'_> {
+    fn spec(&self, name: &str, key: &str) -> Result<&MaterialSpec, SceneError> {
+        self.specs.get(name).ok_or_else(|| {
+            let mut known: Vec<_> = self.specs.keys().map(String::as_str).collect();
+            known.sort();
+            SceneError::invalid(
+                key,
+                format!(
+                    "no material named \"{name}\" (defined: {})",
+                    if known.is_empty() {
+                        "none".to_string()
+                    } else {
+                        known.join(", ")
+                    }
+                ),
+            )
+        })
+    }
+
+    /// Index + 1 of `name` among the sorted material names, for the
+    /// material id AOV.
+    fn id(&self, name: &str) -> u32 {
+        self.specs.keys().filter(|n| n.as_str() < name).count() as u32 + 1
+    }
+
+    /// The material `name`, referenced from `key`.
+    fn build(&mut self, name: &str, key: &str) -> Result<Box<dyn Material>, SceneError> {
+        self.build_nested(name, key, &mut vec![])
+    }
+
+    fn build_nested(
+        &mut self,
+        name: &str,
+        key: &str,
+        stack: &mut Vec<String>,
+    ) -> Result<Box<dyn Material>, SceneError> {
+        if stack.iter().any(|n| n == name) {
+            return Err(SceneError::invalid(
+                key,
+                format!(
+                    "material \"{name}\" contains itself ({} -> {name})",
+                    stack.join(" -> ")
+                ),
+            ));
+        }
+        let spec = self.spec(name, key)?.clone();
+        let at = |field: &str| format!("materials.{name}.{field}");
+        stack.push(name.to_string());
+
+        let material: Box<dyn Material> = match spec {
+            MaterialSpec::Lambertian { albedo } => Box::new(Lambertian::new(vec3(albedo))),
+            MaterialSpec::Metal { albedo, fuzz } => Box::new(Metal::new(vec3(albedo), fuzz)),
+            MaterialSpec::Dielectric { ior } => match ior.build(&at("ior"))? {
+                Ior::Constant(n) => Box::new(Dialectric::new(n)),
+                ior => Box::new(Dialectric::with_dispersion(ior)),
+            },
+            MaterialSpec::DiffuseLight { emit } => Box::new(DiffuseLight::new(vec3(emit))),
+            MaterialSpec::Conductor {
+                preset,
+                eta,
+                k,
+                roughness,
+            } => Box::new(match (preset.as_deref(), eta, k) {
+                (Some("gold"), None, None) => Conductor::gold(roughness),
+                (Some("copper"), None, None) => Conductor::copper(roughness),
+                (Some("aluminum"), None, None) => Conductor::aluminum(roughness),
+                (Some(other), None, None) => {
+                    return Err(SceneError::invalid(
+                        at("preset"),
+                        format!("unknown metal \"{other}\", expected gold, copper or aluminum"),
+                    ))
+                }
+                (None, Some(eta), Some(k)) => Conductor::new(vec3(eta), vec3(k), roughness),
+                _ => {
+                    return Err(SceneError::invalid(
+                        format!("materials.{name}"),
+                        "a conductor needs either `preset` or both `eta` and `k`",
+                    ))
+                }
+            }),
+            MaterialSpec::RoughDielectric { ior, roughness } => {
+                Box::new(RoughDielectric::new(ior.build(&at("ior"))?, roughness))
+            }
+            MaterialSpec::ThinFilm {
+                thickness,
+                film_ior,
+                base_ior,
+            } => Box::new(ThinFilm::new(
+                thickness,
+                film_ior,
+                base_ior.build(&at("base_ior"))?,
+            )),
+            MaterialSpec::Principled {
+                base_color,
+                metallic,
+                roughness,
+                specular,
+                sheen,
+                clearcoat,
+                transmission,
+            } => {
+                let mut m = Principled::new(vec3(base_color));
+                m.metallic = metallic.unwrap_or(m.metallic);
+                m.roughness = roughness.unwrap_or(m.roughness);
+                m.specular = specular.unwrap_or(m.specular);
+                m.sheen = sheen.unwrap_or(m.sheen);
+                m.clearcoat = clearcoat.unwrap_or(m.clearcoat);
+                m.transmission = transmission.unwrap_or(m.transmission);
+                Box::new(m)
+            }
+            MaterialSpec::Coated {
+                base,
+                ior,
+                roughness,
+                absorption,
+                thickness,
+            } => {
+                let base = self.build_nested(&base, &at("base"), stack)?;
+                let coated = Coated::new(base, ior, roughness);
+                Box::new(match absorption {
+                    Some(color) => coated.with_absorption(thickness, vec3(color)),
+                    None => coated,
+                })
+            }
+            MaterialSpec::Subsurface {
+                ior,
+                albedo,
+                mean_free_path,
+                g,
+            } => Box::new(Subsurface::new(
+                ior,
+                Medium::from_albedo(vec3(albedo), vec3(mean_free_path), g),
+            )),
+            MaterialSpec::TwoSided { front, back } => {
+                let front = self.build_nested(&front, &at("front"), stack)?;
+                let back = self.build_nested(&back, &at("back"), stack)?;
+                Box::new(TwoSided::new(front, back))
+            }
+            MaterialSpec::AlphaMask { material, mask } => {
+                let inner = self.build_nested(&material, &at("material"), stack)?;
+                Box::new(AlphaMask::new(inner, self.image(&mask, true, &at("mask"))?))
+            }
+            MaterialSpec::NormalMap { material, map } => {
+                let inner = self.build_nested(&material, &at("material"), stack)?;
+                Box::new(NormalMap::new(inner, self.image(&map, false, &at("map"))?))
+            }
+            MaterialSpec::BumpMap {
+                material,
+                height,
+                scale,
+            } => {
+                let inner = self.build_nested(&material, &at("material"), stack)?;
+                let height = self.image(&height, false, &at("height"))?;
+                Box::new(BumpMap::new(inner, height, scale))
+            }
+        };
+        stack.pop();
+        Ok(material)
+    }
+
+    /// Whether objects made of material `name` give off light.
+    fn emits(&self, name: &str, key: &str) -> Result<bool, SceneError> {
+        Ok(match self.spec(name, key)? {
+            MaterialSpec::DiffuseLight { .. } => true,
+            MaterialSpec::TwoSided { front, back } => {
+                self.emits(front, key)? || self.emits(back, key)?
+            }
+            MaterialSpec::AlphaMask { material, .. }
+            | MaterialSpec::NormalMap { material, .. }
+            | MaterialSpec::BumpMap { material, .. } => self.emits(material, key)?,
+            _ => false,
+        })
+    }
+
+    /// Data image (or its alpha) at `path` relative to the scene file,
+    /// read once however many materials share it.
+    fn image(&mut self, path: &Path, alpha: bool, key: &str) -> Result<ImageTexture, SceneError> {
+        let full = self.base_dir.join(path);
+        if let Some(image) = self.images.get(&(full.clone(), alpha)) {
+            return Ok(image.clone());
+        }
+        let image = if alpha {
+            ImageTexture::load_alpha(&full)
+        } else {
+            ImageTexture::load_raw(&full)
+        }
+        .map_err(|e| SceneError::invalid(key, format!("{}: {e}", full.display())))?;
+        self.images.insert((full, alpha), image.clone());
+        Ok(image)
+    }
+}
+
+/// Scale, then rotation about x, y and z (degrees), then translation.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Transform {
+    translate: [f32; 3],
+    rotate: [f32; 3],
+    scale: Scale,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+enum Scale {
+    Uniform(f32),
+    Axes([f32; 3]),
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translate: [0.; 3],
+            rotate: [0.; 3],
+            scale: Scale::Uniform(1.),
+        }
+    }
+}
+
+impl Transform {
+    fn scale(&self) -> Vec3 {
+        match self.scale {
+            Scale::Uniform(s) => Vec3::new(s, s, s),
+            Scale::Axes(s) => vec3(s),
+        }
+    }
+
+    fn rotate(&self, v: Vec3) -> Vec3 {
+        let [x, y, z] = self.rotate.map(degrees_to_radians);
+        let v = Vec3::new(
+            v.x(),
+            v.y() * x.cos() - v.z() * x.sin(),
+            v.y() * x.sin() + v.z() * x.cos(),
+        );
+        let v = Vec3::new(
+            v.x() * y.cos() + v.z() * y.sin(),
+            v.y(),
+            -v.x() * y.sin() + v.z() * y.cos(),
+        );
+        Vec3::new(
+            v.x() * z.cos() - v.y() * z.sin(),
+            v.x() * z.sin() + v.y() * z.cos(),
+            v.z(),
+        )
+    }
+
+    fn point(&self, p: Vec3) -> Vec3 {
+        self.vector(p) + vec3(self.translate)
+    }
+
+    fn vector(&self, v: Vec3) -> Vec3 {
+        self.rotate(v * self.scale())
+    }
+
+    /// Normals take the inverse scale to stay perpendicular.
+    fn normal(&self, n: Vec3) -> Vec3 {
+        Vec3::unit_vector(self.rotate(n / self.scale()))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectSpec {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+        material: String,
+        #[serde(default)]
+        transform: Transform,
+    },
+    /// Parallelogram from corner `q` spanned by `u` and `v`, facing u × v.
+    Quad {
+        q: [f32; 3],
+        u: [f32; 3],
+        v: [f32; 3],
+        material: String,
+        #[serde(default)]
+        transform: Transform,
+    },
+    Mesh {
+        positions: Vec<[f32; 3]>,
+        indices: Vec<[usize; 3]>,
+        normals: Option<Vec<[f32; 3]>>,
+        uvs: Option<Vec<[f32; 2]>>,
+        material: String,
+        #[serde(default)]
+        transform: Transform,
+    },
+}
+
+impl ObjectSpec {
+    fn material(&self) -> &str {
+        match self {
+            ObjectSpec::Sphere { material, .. }
+            | ObjectSpec::Quad { material, .. }
+            | ObjectSpec::Mesh { material, .. } => material,
+        }
+    }
+
+    fn build(&self, key: &str, materials: &mut Materials) -> Result<Box<dyn Hittable>, SceneError> {
+        let material = materials.build(self.material(), &format!("{key}.material"))?;
+        let material_id = materials.id(self.material());
+        Ok(match self {
+            ObjectSpec::Sphere {
+                center,
+                radius,
+                transform,
+                ..
+            } => {
+                let s = transform.scale();
+                if s.x() != s.y() || s.y() != s.z() {
+                    return Err(SceneError::invalid(
+                        format!("{key}.transform.scale"),
+                        "spheres only take a uniform scale",
+                    ));
+                }
+                if !radius.is_finite() || *radius <= 0. {
+                    return Err(SceneError::invalid(
+                        format!("{key}.radius"),
+                        "must be positive",
+                    ));
+                }
+                Box::new(
+                    Sphere::new(
+                        transform.point(vec3(*center)),
+                        radius * s.x().abs(),
+                        material,
+                    )
+                    .with_material_id(material_id),
+                )
+            }
+            ObjectSpec::Quad {
+                q, u, v, transform, ..
+            } => Box::new(
+                TriangleMesh::quad(
+                    transform.point(vec3(*q)),
+                    transform.vector(vec3(*u)),
+                    transform.vector(vec3(*v)),
+                    material,
+                )
+                .with_material_id(material_id),
+            ),
+            ObjectSpec::Mesh {
+                positions,
+                indices,
+                normals,
+                uvs,
+                transform,
+                ..
+            } => {
+                for (i, triangle) in indices.iter().enumerate() {
+                    if let Some(&vertex) = triangle.iter().find(|&&v| v >= positions.len()) {
+                        return Err(SceneError::invalid(
+                            format!("{key}.indices[{i}]"),
+                            format!(
+                                "vertex {vertex} out of range, there are {}",
+                                positions.len()
+                            ),
+                        ));
+                    }
+                }
+                let count = |field: &str, len: usize| {
+                    if len == positions.len() {
+                        Ok(())
+                    } else {
+                        Err(SceneError::invalid(
+                            format!("{key}.{field}"),
+                            format!("{len} entries for {} positions", positions.len()),
+                        ))
+                    }
+                };
+                let positions = positions
+                    .iter()
+                    .map(|&p| transform.point(vec3(p)))
+                    .collect();
+                let mut mesh = TriangleMesh::new(positions, indices.clone(), material)
+                    .with_material_id(material_id);
+                if let Some(normals) = normals {
+                    count("normals", normals.len())?;
+                    mesh = mesh
+                        .with_normals(normals.iter().map(|&n| transform.normal(vec3(n))).collect());
+                }
+                if let Some(uvs) = uvs {
+                    count("uvs", uvs.len())?;
+                    mesh = mesh.with_uvs(uvs.iter().map(|&[u, v]| (u, v)).collect());
+                }
+                Box::new(mesh)
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightSpec {
+    Point {
+        position: [f32; 3],
+        intensity: [f32; 3],
+    },
+    Spot {
+        position: [f32; 3],
+        target: [f32; 3],
+        intensity: [f32; 3],
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+    Directional {
+        direction: [f32; 3],
+        irradiance: [f32; 3],
+        #[serde(default)]
+        angular_diameter: f32,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SkySpec {
+    elevation: f32,
+    #[serde(default)]
+    azimuth: f32,
+    #[serde(default = "default_turbidity")]
+    turbidity: f32,
+    ground_albedo: Option<[f32; 3]>,
+    exposure: Option<f32>,
+}
+
+fn default_turbidity() -> f32 {
+    3.
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::{interval::Interval, ray::Ray, utils::INFINITY};
+
+    const TWO_SPHERES: &str = include_str!("../../scenes/two_spheres.toml");
+
+    fn parse_str(text: &str) -> Result<SceneDescription, SceneError> {
+        parse(text, Path::new("."))
+    }
+
+    fn invalid_key(text: &str) -> String {
+        match parse_str(text) {
+            Err(SceneError::Invalid { key, .. }) => key,
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("loaded"),
+        }
+    }
+
+    #[test]
+    fn loads_the_example() {
+        let description = parse_str(TWO_SPHERES).unwrap();
+        assert_eq!(description.camera.image_width, 400);
+        assert_eq!(description.camera.image_height(), 225);
+        assert_eq!(description.scene.world.objects.len(), 3);
+        // The lamp is sampled as a light
+        assert_eq!(description.scene.lights.objects.len(), 1);
+        assert_eq!(description.render.integrator, IntegratorKind::Path);
+    }
+
+    #[test]
+    fn errors_name_the_line_or_key() {
+        let typo = TWO_SPHERES.replace("radius = 0.25", "raduis = 0.25");
+        let message = parse_str(&typo).err().unwrap().to_string();
+        assert!(
+            message.starts_with("objects[2]: unknown field `raduis`"),
+            "{message}"
+        );
+        assert!(message.ends_with("(line 42)"), "{message}");
+        let wrong_type = TWO_SPHERES.replace("radius = 0.25", "radius = \"small\"");
+        let message = parse_str(&wrong_type).err().unwrap().to_string();
+        assert!(message.ends_with("(line 42)"), "{message}");
+        // Nothing to point at when a key is missing, so the header
+        let missing_key = TWO_SPHERES.replace("radius = 0.25\n", "");
+        let message = parse_str(&missing_key).err().unwrap().to_string();
+        assert!(message.ends_with("(line 39)"), "{message}");
+
+        let bad_type = TWO_SPHERES.replace("max_depth = 50", "max_depth = \"deep\"");
+        let message = parse_str(&bad_type).err().unwrap().to_string();
+        assert!(
+            message.contains("line 12") && message.contains("expected i32"),
+            "{message}"
+        );
+
+        let missing = TWO_SPHERES.replace("material = \"lamp\"", "material = \"lmap\"");
+        assert_eq!(invalid_key(&missing), "objects[2].material");
+
+        assert_eq!(
+            invalid_key(&TWO_SPHERES.replace("version = 1", "version = 2")),
+            "version"
+        );
+        assert_eq!(invalid_key("[camera]\nimage_width = 10"), "version");
+    }
+
+    #[test]
+    fn material_cycles_are_caught() {
+        let text = r#"
+            version = 1
+            [materials.a]
+            type = "coated"
+            base = "b"
+            [materials.b]
+            type = "two_sided"
+            front = "a"
+            back = "a"
+            [[objects]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+            material = "a"
+        "#;
+        assert_eq!(invalid_key(text), "materials.b.front");
+    }
+
+    #[test]
+    fn materials_get_stable_ids() {
+        let scene = parse_str(TWO_SPHERES).unwrap().scene;
+        let id = |x: f32| {
+            let ray = Ray::new(Vec3::new(x, 0., 0.), Vec3::new(0., 0., -1.));
+            scene
+                .world
+                .hit(&ray, &Interval::new(0.001, INFINITY))
+                .unwrap()
+                .material_id
+        };
+        // Sorted names: blue, lamp, red
+        assert_eq!((id(-0.7), id(0.7)), (1, 3));
+    }
+
+    #[test]
+    fn transforms_are_baked_in() {
+        let text = r#"
+            version = 1
+            [materials.grey]
+            type = "lambertian"
+            albedo = [0.5, 0.5, 0.5]
+            [[objects]]
+            type = "sphere"
+            center = [1, 0, 0]
+            radius = 1
+            material = "grey"
+            transform = { scale = 2, rotate = [0, 0, 90], translate = [0, 0, -10] }
+        "#;
+        let scene = parse_str(text).unwrap().scene;
+        // Center lands at (0, 2, -10) with radius 2
+        let ray = Ray::new(Vec3::new(0., 2., 0.), Vec3::new(0., 0., -1.));
+        let hit = scene
+            .world
+            .hit(&ray, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        assert!((hit.t - 8.).abs() < 1e-4, "{}", hit.t);
+
+        let squashed = text.replace("scale = 2", "scale = [1, 2, 1]");
+        assert_eq!(invalid_key(&squashed), "objects[0].transform.scale");
+    }
+
+    #[test]
+    fn mesh_indices_are_checked() {
+        let text = r#"
+            version = 1
+            [materials.grey]
+            type = "lambertian"
+            albedo = [0.5, 0.5, 0.5]
+            [[objects]]
+            type = "mesh"
+            positions = [[0, 0, 0], [1, 0, 0], [0, 1, 0]]
+            indices = [[0, 1, 2], [0, 2, 3]]
+            material = "grey"
+        "#;
+        assert_eq!(invalid_key(text), "objects[0].indices[1]");
+    }
+}
@@ -0,0 +1,561 @@
+use super::{
+    camera::Camera,
+    coated::Coated,
+    hittable_list::HittableList,
+    material::{
+        Conductor, Dialectric, DiffuseLight, Ior, Lambertian, Material, Metal, RoughDielectric,
+        Subsurface, ThinFilm,
+    },
+    medium::Medium,
+    mesh::TriangleMesh,
+    principled::Principled,
+    scene::Scene,
+    scene_file::{RenderSettings, SceneDescription},
+    sphere::Sphere,
+    texture::{CheckerTexture, ImageTexture},
+    utils::{degrees_to_radians, random_f32, random_f32_custom, with_seed, PI},
+    vec3::Vec3,
+};
+
+/// Scenes built into the renderer, the same on every machine so benchmarks
+/// and regression images stay comparable.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BuiltinScene {
+    /// Two spheres under a small lamp.
+    TwoSpheres,
+    /// The final render of "Ray Tracing in One Weekend".
+    RandomSpheres,
+    /// The Cornell box of "Ray Tracing: The Next Week".
+    CornellBox,
+    /// One sphere per material over a checkered floor.
+    MaterialBoard,
+    /// A globe with a generated map, so no image file is needed.
+    Earth,
+}
+
+impl BuiltinScene {
+    pub const ALL: [BuiltinScene; 5] = [
+        BuiltinScene::TwoSpheres,
+        BuiltinScene::RandomSpheres,
+        BuiltinScene::CornellBox,
+        BuiltinScene::MaterialBoard,
+        BuiltinScene::Earth,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BuiltinScene::TwoSpheres => "two_spheres",
+            BuiltinScene::RandomSpheres => "random_spheres",
+            BuiltinScene::CornellBox => "cornell_box",
+            BuiltinScene::MaterialBoard => "material_board",
+            BuiltinScene::Earth => "earth",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BuiltinScene> {
+        BuiltinScene::ALL
+            .into_iter()
+            .find(|scene| scene.name() == name)
+    }
+
+    pub fn build(self) -> SceneDescription {
+        match self {
+            BuiltinScene::TwoSpheres => two_spheres(),
+            BuiltinScene::RandomSpheres => random_spheres(),
+            BuiltinScene::CornellBox => cornell_box(),
+            BuiltinScene::MaterialBoard => material_board(),
+            BuiltinScene::Earth => earth(),
+        }
+    }
+}
+
+/// Moves a scene modelled around `at` in front of our camera, which sits
+/// at the origin looking down -z.
+struct View {
+    from: Vec3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl View {
+    fn look_at(from: Vec3, at: Vec3, up: Vec3) -> Self {
+        let w = Vec3::unit_vector(from - at);
+        let u = Vec3::unit_vector(Vec3::cross(up, w));
+        let v = Vec3::cross(w, u);
+        Self { from, u, v, w }
+    }
+
+    fn vector(&self, d: Vec3) -> Vec3 {
+        Vec3::new(d.dot(self.u), d.dot(self.v), d.dot(self.w))
+    }
+
+    fn point(&self, p: Vec3) -> Vec3 {
+        self.vector(p - self.from)
+    }
+
+    fn sphere<M: Material>(&self, center: Vec3, radius: f32, material: M) -> Sphere<M> {
+        Sphere::new(self.point(center), radius, material)
+    }
+
+    fn quad<M: Material>(&self, q: Vec3, u: Vec3, v: Vec3, material: M) -> TriangleMesh<M> {
+        TriangleMesh::quad(self.point(q), self.vector(u), self.vector(v), material)
+    }
+}
+
+fn describe(camera: Camera, scene: Scene) -> SceneDescription {
+    SceneDescription {
+        camera,
+        scene,
+        render: RenderSettings::default(),
+    }
+}
+
+pub fn two_spheres() -> SceneDescription {
+    let mut world = HittableList::new();
+
+    let r = (PI / 4.).cos();
+    let left = Lambertian::new(Vec3::new(0., 0., 1.));
+    let right = Lambertian::new(Vec3::new(1., 0., 0.));
+
+    // Material ids as `scenes/two_spheres.toml` numbers its blue, lamp and red
+    world.push(Box::new(
+        Sphere::new(Vec3::new(-r, 0., -1.), r, left).with_material_id(1),
+    ));
+    world.push(Box::new(
+        Sphere::new(Vec3::new(r, 0., -1.), r, right).with_material_id(3),
+    ));
+
+    // Small lamp above the spheres, sampled directly through `lights`
+    let lamp = DiffuseLight::new(Vec3::new(15., 15., 15.));
+    let lamp_center = Vec3::new(0., 2., -1.);
+    world.push(Box::new(
+        Sphere::new(lamp_center, 0.25, lamp.clone()).with_material_id(2),
+    ));
+    let mut lights = HittableList::new();
+    lights.push(Box::new(
+        Sphere::new(lamp_center, 0.25, lamp).with_material_id(2),
+    ));
+
+    describe(Camera::new(16. / 9., 400, 100), Scene::new(world, lights))
+}
+
+/// The book's scene and framing, without its depth of field. The small
+/// spheres come from a fixed seed, and every sphere has a material id of
+/// its own.
+pub fn random_spheres() -> SceneDescription {
+    let view = View::look_at(
+        Vec3::new(13., 2., 3.),
+        Vec3::new(0., 0., 0.),
+        Vec3::new(0., 1., 0.),
+    );
+    let mut world = HittableList::new();
+    let ground = Lambertian::new(Vec3::new(0.5, 0.5, 0.5));
+    world.push(Box::new(
+        view.sphere(Vec3::new(0., -1000., 0.), 1000., ground)
+            .with_material_id(1),
+    ));
+    let mut id = 1;
+    let mut next_id = || {
+        id += 1;
+        id
+    };
+
+    // A generator of its own, so building the scene leaves the caller's alone
+    with_seed(1, || {
+        for a in -11..11 {
+            for b in -11..11 {
+                let choose_mat = random_f32();
+                let center = Vec3::new(
+                    a as f32 + 0.9 * random_f32(),
+                    0.2,
+                    b as f32 + 0.9 * random_f32(),
+                );
+                if (center - Vec3::new(4., 0.2, 0.)).length() <= 0.9 {
+                    continue;
+                }
+                let material: Box<dyn Material> = if choose_mat < 0.8 {
+                    Box::new(Lambertian::new(Vec3::random() * Vec3::random()))
+                } else if choose_mat < 0.95 {
+                    let albedo = Vec3::random_custom(0.5, 1.);
+                    Box::new(Metal::new(albedo, random_f32_custom(0., 0.5)))
+                } else {
+                    Box::new(Dialectric::new(1.5))
+                };
+                world.push(Box::new(
+                    view.sphere(center, 0.2, material)
+                        .with_material_id(next_id()),
+                ));
+            }
+        }
+    });
+
+    world.push(Box::new(
+        view.sphere(Vec3::new(0., 1., 0.), 1., Dialectric::new(1.5))
+            .with_material_id(next_id()),
+    ));
+    world.push(Box::new(
+        view.sphere(
+            Vec3::new(-4., 1., 0.),
+            1.,
+            Lambertian::new(Vec3::new(0.4, 0.2, 0.1)),
+        )
+        .with_material_id(next_id()),
+    ));
+    world.push(Box::new(
+        view.sphere(
+            Vec3::new(4., 1., 0.),
+            1.,
+            Metal::new(Vec3::new(0.7, 0.6, 0.5), 0.),
+        )
+        .with_material_id(next_id()),
+    ));
+
+    let camera = Camera::new(16. / 9., 400, 100).with_vfov(20.);
+    describe(camera, Scene::new(world, HittableList::new()))
+}
+
+/// Box from the origin to `max` turned `degrees` about y, then moved by `offset`.
+fn cuboid<M: Material>(
+    view: &View,
+    max: Vec3,
+    degrees: f32,
+    offset: Vec3,
+    material: M,
+) -> TriangleMesh<M> {
+    let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+    let corners = (0..8)
+        .map(|i| {
+            let x = if i & 1 == 0 { 0. } else { max.x() };
+            let y = if i & 2 == 0 { 0. } else { max.y() };
+            let z = if i & 4 == 0 { 0. } else { max.z() };
+            let turned = Vec3::new(cos * x + sin * z, y, -sin * x + cos * z);
+            view.point(turned + offset)
+        })
+        .collect();
+    let faces = [
+        [0, 2, 3, 1],
+        [4, 5, 7, 6],
+        [0, 1, 5, 4],
+        [2, 6, 7, 3],
+        [0, 4, 6, 2],
+        [1, 3, 7, 5],
+    ];
+    let indices = faces
+        .iter()
+        .flat_map(|&[a, b, c, d]| [[a, b, c], [a, c, d]])
+        .collect();
+    TriangleMesh::new(corners, indices, material)
+}
+
+pub fn cornell_box() -> SceneDescription {
+    let view = View::look_at(
+        Vec3::new(278., 278., -800.),
+        Vec3::new(278., 278., 0.),
+        Vec3::new(0., 1., 0.),
+    );
+    let red = || Lambertian::new(Vec3::new(0.65, 0.05, 0.05));
+    let white = || Lambertian::new(Vec3::new(0.73, 0.73, 0.73));
+    let green = || Lambertian::new(Vec3::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(Vec3::new(15., 15., 15.));
+
+    let mut world = HittableList::new();
+    let (x, y, z) = (
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        Vec3::new(0., 0., 555.),
+    );
+    let origin = Vec3::new(0., 0., 0.);
+    // Material ids: green 1, red 2, white 3 and the light 4
+    world.push(Box::new(view.quad(x, y, z, green()).with_material_id(1)));
+    world.push(Box::new(view.quad(origin, y, z, red()).with_material_id(2)));
+    for (q, u, v) in [(origin, x, z), (x + y + z, -x, -z), (z, x, y)] {
+        world.push(Box::new(view.quad(q, u, v, white()).with_material_id(3)));
+    }
+
+    // Facing down into the box
+    let lamp = |material| {
+        view.quad(
+            Vec3::new(343., 554., 332.),
+            Vec3::new(-130., 0., 0.),
+            Vec3::new(0., 0., -105.),
+            material,
+        )
+        .with_material_id(4)
+    };
+    world.push(Box::new(lamp(light.clone())));
+    let mut lights = HittableList::new();
+    lights.push(Box::new(lamp(light)));
+
+    world.push(Box::new(
+        cuboid(
+            &view,
+            Vec3::new(165., 330., 165.),
+            15.,
+            Vec3::new(265., 0., 295.),
+            white(),
+        )
+        .with_material_id(3),
+    ));
+    world.push(Box::new(
+        cuboid(
+            &view,
+            Vec3::new(165., 165., 165.),
+            -18.,
+            Vec3::new(130., 0., 65.),
+            white(),
+        )
+        .with_material_id(3),
+    ));
+
+    let camera = Camera::new(1., 400, 100).with_vfov(40.);
+    let scene = Scene::new(world, lights).with_background(Vec3::new(0., 0., 0.));
+    describe(camera, scene)
+}
+
+/// Two rows of five spheres, from simple to layered materials, under a
+/// large soft light.
+pub fn material_board() -> SceneDescription {
+    let view = View::look_at(
+        Vec3::new(0., 3., 7.),
+        Vec3::new(0., 0.5, 0.),
+        Vec3::new(0., 1., 0.),
+    );
+    let grey = Vec3::new(0.8, 0.8, 0.8);
+    let mut world = HittableList::new();
+    let floor = Lambertian::textured(CheckerTexture::new(
+        0.5,
+        Vec3::new(0.2, 0.2, 0.2),
+        Vec3::new(0.7, 0.7, 0.7),
+    ));
+    // Material ids: the floor 1, the spheres 2 to 13 and the light 14
+    world.push(Box::new(
+        view.quad(
+            Vec3::new(-10., 0., -10.),
+            Vec3::new(0., 0., 20.),
+            Vec3::new(20., 0., 0.),
+            floor,
+        )
+        .with_material_id(1),
+    ));
+
+    let mut skin = Principled::new(Vec3::new(0.8, 0.5, 0.4));
+    skin.roughness = 0.4;
+    let mut red_metal = Principled::new(Vec3::new(0.8, 0.1, 0.1));
+    red_metal.metallic = 1.;
+    red_metal.roughness = 0.3;
+    let materials: [Box<dyn Material>; 10] = [
+        Box::new(Lambertian::new(Vec3::new(0.7, 0.3, 0.2))),
+        Box::new(Metal::new(grey, 0.)),
+        Box::new(Metal::new(grey, 0.3)),
+        Box::new(Conductor::gold(0.3)),
+        Box::new(Conductor::copper(0.1)),
+        Box::new(Dialectric::new(1.5)),
+        Box::new(RoughDielectric::new(Ior::bk7(), 0.3)),
+        Box::new(Coated::new(
+            Lambertian::new(Vec3::new(0.1, 0.2, 0.6)),
+            1.5,
+            0.1,
+        )),
+        Box::new(ThinFilm::soap_bubble(400.)),
+        Box::new(Subsurface::new(
+            1.4,
+            Medium::from_albedo(Vec3::new(0.9, 0.6, 0.5), Vec3::new(0.3, 0.15, 0.1), 0.),
+        )),
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        let (row, column) = (i / 5, i % 5);
+        let center = Vec3::new(column as f32 * 1.1 - 2.2, 0.45, row as f32 * 1.2 - 0.6);
+        world.push(Box::new(
+            view.sphere(center, 0.45, material)
+                .with_material_id(i as u32 + 2),
+        ));
+    }
+    world.push(Box::new(
+        view.sphere(Vec3::new(3.5, 0.45, -1.8), 0.45, skin)
+            .with_material_id(12),
+    ));
+    world.push(Box::new(
+        view.sphere(Vec3::new(-3.5, 0.45, -1.8), 0.45, red_metal)
+            .with_material_id(13),
+    ));
+
+    let light = DiffuseLight::new(Vec3::new(4., 4., 4.));
+    let panel = |material| {
+        view.quad(
+            Vec3::new(-2., 5., -2.),
+            Vec3::new(4., 0., 0.),
+            Vec3::new(0., 0., 4.),
+            material,
+        )
+        .with_material_id(14)
+    };
+    world.push(Box::new(panel(light.clone())));
+    let mut lights = HittableList::new();
+    lights.push(Box::new(panel(light)));
+
+    let camera = Camera::new(16. / 9., 400, 100).with_vfov(40.);
+    let scene = Scene::new(world, lights).with_background(Vec3::new(0.1, 0.1, 0.12));
+    describe(camera, scene)
+}
+
+/// Hash of a lattice point to [0, 1).
+fn lattice(x: i32, y: i32, z: i32) -> f32 {
+    let mut h = (x as u32)
+        .wrapping_mul(0x8DA6_B343)
+        .wrapping_add((y as u32).wrapping_mul(0xD816_3841))
+        .wrapping_add((z as u32).wrapping_mul(0xCB1A_B31F));
+    h = (h ^ (h >> 13)).wrapping_mul(0x85EB_CA6B);
+    h ^= h >> 16;
+    (h >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Smooth 3D value noise in [0, 1).
+fn value_noise(p: Vec3) -> f32 {
+    let (x, y, z) = (p.x().floor(), p.y().floor(), p.z().floor());
+    let smooth = |t: f32| t * t * (3. - 2. * t);
+    let (fx, fy, fz) = (smooth(p.x() - x), smooth(p.y() - y), smooth(p.z() - z));
+    let (x, y, z) = (x as i32, y as i32, z as i32);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let plane = |dz: i32| {
+        lerp(
+            lerp(lattice(x, y, z + dz), lattice(x + 1, y, z + dz), fx),
+            lerp(lattice(x, y + 1, z + dz), lattice(x + 1, y + 1, z + dz), fx),
+            fy,
+        )
+    };
+    lerp(plane(0), plane(1), fz)
+}
+
+/// Equirectangular map of made up continents, evaluated on the sphere so
+/// it has no seam.
+fn planet_map(width: usize, height: usize) -> ImageTexture {
+    let mut pixels = Vec::with_capacity(width * height);
+    for j in 0..height {
+        // Rows run top down, from the north pole
+        let theta = PI * (j as f32 + 0.5) / height as f32;
+        for i in 0..width {
+            let phi = 2. * PI * (i as f32 + 0.5) / width as f32;
+            let direction = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            let height_field = (0..5)
+                .map(|octave| {
+                    let f = (1 << octave) as f32;
+                    value_noise(direction * (1.5 * f) + Vec3::new(7.3, 1.9, 4.1)) / f
+                })
+                .sum::<f32>()
+                / 1.9375;
+            let latitude = (90. - theta.to_degrees()).abs();
+            let color = if latitude > 72. - 8. * height_field {
+                Vec3::new(0.9, 0.92, 0.95)
+            } else if height_field < 0.52 {
+                Vec3::new(0.02, 0.06, 0.2) + Vec3::new(0.0, 0.1, 0.2) * (height_field / 0.52)
+            } else if latitude < 30. && height_field < 0.6 {
+                Vec3::new(0.55, 0.45, 0.28)
+            } else {
+                Vec3::new(0.12, 0.3, 0.08) * (1.4 - height_field)
+            };
+            pixels.push(color);
+        }
+    }
+    ImageTexture::new(width, height, pixels)
+}
+
+pub fn earth() -> SceneDescription {
+    let view = View::look_at(
+        Vec3::new(0., 0., 12.),
+        Vec3::new(0., 0., 0.),
+        Vec3::new(0., 1., 0.),
+    );
+    let surface = Lambertian::textured(planet_map(512, 256));
+    let mut world = HittableList::new();
+    world.push(Box::new(
+        view.sphere(Vec3::new(0., 0., 0.), 2., surface)
+            .with_material_id(1),
+    ));
+
+    let camera = Camera::new(16. / 9., 400, 100).with_vfov(20.);
+    describe(camera, Scene::new(world, HittableList::new()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::{
+        hittable::Hittable,
+        interval::Interval,
+        ray::Ray,
+        utils::{seed_random, INFINITY},
+    };
+
+    #[test]
+    fn names_round_trip() {
+        for scene in BuiltinScene::ALL {
+            assert_eq!(BuiltinScene::from_name(scene.name()), Some(scene));
+        }
+        assert_eq!(BuiltinScene::from_name("teapot"), None);
+    }
+
+    #[test]
+    fn scenes_are_reproducible() {
+        let centers = || {
+            let scene = random_spheres().scene;
+            let ray = Ray::new(Vec3::new(0., 0., 0.), Vec3::new(0.1, -0.05, -1.));
+            scene
+                .world
+                .hit(&ray, &Interval::new(0.001, INFINITY))
+                .map(|hit| hit.p)
+        };
+        let first = centers();
+        seed_random(99);
+        let expected = random_f32();
+        seed_random(99);
+        assert_eq!(first, centers());
+        assert!(first.is_some());
+        // and leave the caller's random sequence where it was
+        assert_eq!(random_f32(), expected);
+    }
+
+    #[test]
+    fn every_material_has_an_id() {
+        let scene = cornell_box().scene;
+        let id = |x: f32, y: f32| {
+            let ray = Ray::new(Vec3::new(0., 0., 0.), Vec3::new(x, y, -1.));
+            scene
+                .world
+                .hit(&ray, &Interval::new(0.001, INFINITY))
+                .unwrap()
+                .material_id
+        };
+        // Green wall on the left of the image, red on the right, white
+        // boxes in the middle and the light overhead
+        assert_eq!(
+            (id(-0.3, 0.), id(0.3, 0.), id(0., 0.), id(0., 0.25)),
+            (1, 2, 3, 4)
+        );
+    }
+
+    #[test]
+    fn every_scene_is_in_view() {
+        for scene in BuiltinScene::ALL {
+            let description = scene.build();
+            let hits = (0..16)
+                .filter(|i| {
+                    let ray = description.camera.get_ray(
+                        (i % 4) as f32 * description.camera.image_width as f32 / 4.,
+                        (i / 4) as f32 * description.camera.image_height() as f32 / 4.,
+                    );
+                    description
+                        .scene
+                        .world
+                        .hit(&ray, &Interval::new(0.001, INFINITY))
+                        .is_some()
+                })
+                .count();
+            assert!(hits > 0, "{}", scene.name());
+        }
+    }
+}
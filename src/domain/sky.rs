@@ -0,0 +1,228 @@
+use super::{
+    light::DirectionalLight,
+    spectrum::xyz_to_linear_srgb,
+    utils::{degrees_to_radians, PI},
+    vec3::Vec3,
+};
+
+/// Angular diameter of the sun, in degrees.
+const SUN_DIAMETER: f32 = 0.53;
+/// Solar illuminance above the atmosphere, in klux.
+const SOLAR_ILLUMINANCE: f32 = 128.;
+
+/// Preetham daylight sky for a sun at some elevation and azimuth. Radiance
+/// is in kcd/m² times `exposure`, the matching `sun` light in klux times
+/// `exposure`. Below the horizon is a diffuse ground lit by both.
+///
+/// The sun disk is not part of `radiance`, only of `sun`, so rays escaping
+/// towards it don't count it twice.
+#[derive(Clone, Debug)]
+pub struct Sky {
+    sun_direction: Vec3,
+    turbidity: f32,
+    ground_albedo: Vec3,
+    exposure: f32,
+    /// Zenith luminance and chromaticity (Y, x, y).
+    zenith: [f32; 3],
+    /// Perez distribution coefficients A to E for Y, x and y.
+    perez: [[f32; 5]; 3],
+    /// Unexposed sky and sun illuminance on the ground.
+    ground_illuminance: Vec3,
+}
+
+impl Sky {
+    /// Sun `elevation` above the horizon and `azimuth` from -z towards +x,
+    /// both in degrees. `turbidity` runs from 2 (very clear) to 10 (hazy).
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32) -> Self {
+        let elevation = degrees_to_radians(elevation.clamp(0., 90.));
+        let azimuth = degrees_to_radians(azimuth);
+        let t = turbidity.clamp(1.7, 10.);
+        let theta_s = PI / 2. - elevation;
+
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_s);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[f32; 4]; 3]| {
+            let thetas = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.];
+            let row = |r: [f32; 4]| r.iter().zip(thetas).map(|(a, b)| a * b).sum::<f32>();
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let mut sky = Self {
+            sun_direction: Vec3::new(
+                elevation.cos() * azimuth.sin(),
+                elevation.sin(),
+                -elevation.cos() * azimuth.cos(),
+            ),
+            turbidity: t,
+            ground_albedo: Vec3::new(0.2, 0.2, 0.2),
+            exposure: 0.05,
+            zenith: [luminance.max(0.), x, y],
+            perez,
+            ground_illuminance: Vec3::default(),
+        };
+        sky.ground_illuminance = sky.sky_illuminance() + sky.sun_illuminance() * elevation.sin();
+        sky
+    }
+
+    /// Diffuse reflectance of the ground below the horizon.
+    pub fn with_ground_albedo(mut self, albedo: Vec3) -> Self {
+        self.ground_albedo = albedo;
+        self
+    }
+
+    /// Scale from photometric units to scene radiance.
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Unit direction towards the sun.
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    /// Radiance of the sky arriving from `direction`.
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let w = Vec3::unit_vector(direction);
+        if w.y() <= 0. {
+            return self.ground_albedo * self.ground_illuminance * (self.exposure / PI);
+        }
+        self.sky_radiance(w) * self.exposure
+    }
+
+    /// Sun disk light matching the sky.
+    pub fn sun(&self) -> DirectionalLight {
+        DirectionalLight::new(-self.sun_direction, self.sun_illuminance() * self.exposure)
+            .with_angular_diameter(SUN_DIAMETER)
+    }
+
+    /// Perez model for a direction above the horizon, unexposed.
+    fn sky_radiance(&self, w: Vec3) -> Vec3 {
+        let cos_theta = w.y().max(1e-3);
+        let cos_gamma = w.dot(self.sun_direction).clamp(-1., 1.);
+        let gamma = cos_gamma.acos();
+        let theta_s = self.sun_direction.y().clamp(-1., 1.).acos();
+
+        let perez = |[a, b, c, d, e]: [f32; 5], cos_theta: f32, gamma: f32, cos_gamma: f32| {
+            (1. + a * (b / cos_theta).exp())
+                * (1. + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+        };
+        let [yy, x, y] = std::array::from_fn(|i| {
+            let zenith = perez(self.perez[i], 1., theta_s, theta_s.cos());
+            self.zenith[i] * perez(self.perez[i], cos_theta, gamma, cos_gamma) / zenith
+        });
+        if y <= 0. {
+            return Vec3::default();
+        }
+        let xyz = Vec3::new(x / y * yy, yy, (1. - x - y) / y * yy);
+        let rgb = xyz_to_linear_srgb(xyz);
+        Vec3::new(rgb.x().max(0.), rgb.y().max(0.), rgb.z().max(0.))
+    }
+
+    /// Illuminance the sky alone gives a horizontal surface, unexposed.
+    fn sky_illuminance(&self) -> Vec3 {
+        let (n_theta, n_phi) = (32, 64);
+        let mut sum = Vec3::default();
+        for i in 0..n_theta {
+            // Uniform in cos²θ, the cosine weighted solid angle
+            let cos_theta = ((i as f32 + 0.5) / n_theta as f32).sqrt();
+            let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+            for j in 0..n_phi {
+                let phi = 2. * PI * (j as f32 + 0.5) / n_phi as f32;
+                let w = Vec3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                sum += self.sky_radiance(w);
+            }
+        }
+        sum * (PI / (n_theta * n_phi) as f32)
+    }
+
+    /// Sunlight on a surface facing the sun after the atmosphere's Rayleigh
+    /// and aerosol extinction, unexposed.
+    fn sun_illuminance(&self) -> Vec3 {
+        let elevation = self.sun_direction.y().asin();
+        if elevation <= 0. {
+            return Vec3::default();
+        }
+        // Kasten's relative optical mass
+        let zenith_deg = 90. - elevation.to_degrees();
+        let m = 1. / (elevation.sin() + 0.15 * (93.885 - zenith_deg).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let tau = |lambda_um: f32| {
+            let rayleigh = (-m * 0.008735 * lambda_um.powf(-4.08)).exp();
+            let aerosol = (-m * beta * lambda_um.powf(-1.3)).exp();
+            rayleigh * aerosol
+        };
+        Vec3::new(tau(0.630), tau(0.532), tau(0.465)) * SOLAR_ILLUMINANCE
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn zenith_matches_zenith_luminance() {
+        let sky = Sky::new(40., 0., 3.);
+        let zenith = sky.sky_radiance(Vec3::new(0., 1., 0.));
+        // Luminance of linear sRGB
+        let luminance = 0.2126 * zenith.x() + 0.7152 * zenith.y() + 0.0722 * zenith.z();
+        assert!(
+            (luminance - sky.zenith[0]).abs() < 1e-2 * sky.zenith[0],
+            "{luminance} vs {}",
+            sky.zenith[0]
+        );
+        // Clear skies are blue
+        assert!(zenith.z() > zenith.x());
+    }
+
+    #[test]
+    fn brighter_near_the_sun() {
+        let sky = Sky::new(30., 90., 3.);
+        let towards = sky.radiance(Vec3::new(1., 0.7, 0.));
+        let away = sky.radiance(Vec3::new(-1., 0.7, 0.));
+        assert!(towards.y() > away.y());
+    }
+
+    #[test]
+    fn low_sun_is_red_and_dim() {
+        let noon = Sky::new(80., 0., 3.).sun_illuminance();
+        let dusk = Sky::new(3., 0., 3.).sun_illuminance();
+        assert!(dusk.y() < noon.y());
+        assert!(dusk.x() / dusk.z() > noon.x() / noon.z());
+        assert_eq!(Sky::new(0., 0., 3.).sun_illuminance(), Vec3::default());
+    }
+}
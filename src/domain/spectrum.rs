@@ -0,0 +1,195 @@
+use std::sync::OnceLock;
+
+use super::vec3::Vec3;
+
+pub const LAMBDA_MIN: f32 = 380.;
+pub const LAMBDA_MAX: f32 = 720.;
+
+/// Wavelength in nm for a uniform sample, with pdf `1 / (LAMBDA_MAX - LAMBDA_MIN)`.
+pub fn sample_wavelength(u: f32) -> f32 {
+    LAMBDA_MIN + (LAMBDA_MAX - LAMBDA_MIN) * u
+}
+
+/// `rgb` as seen at `wavelength`. A wavelength of zero means RGB rendering
+/// and returns `rgb` untouched, otherwise every channel holds the value of
+/// the upsampled spectrum.
+pub fn rgb_at(rgb: Vec3, wavelength: f32) -> Vec3 {
+    if wavelength <= 0. {
+        return rgb;
+    }
+    let s = rgb_to_spectrum(rgb, wavelength);
+    Vec3::new(s, s, s)
+}
+
+/// Smits' upsampling: a smooth reflectance spectrum built from white plus
+/// the primaries needed to reach `rgb`.
+pub fn rgb_to_spectrum(rgb: Vec3, wavelength: f32) -> f32 {
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    let bin = (((wavelength - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * 10.) as usize).min(9);
+    let [white, cyan, magenta, yellow, red, green, blue] = SMITS.map(|s| s[bin]);
+
+    if r <= g && r <= b {
+        let base = r * white;
+        if g <= b {
+            base + (g - r) * cyan + (b - g) * blue
+        } else {
+            base + (b - r) * cyan + (g - b) * green
+        }
+    } else if g <= r && g <= b {
+        let base = g * white;
+        if r <= b {
+            base + (r - g) * magenta + (b - r) * blue
+        } else {
+            base + (b - g) * magenta + (r - b) * red
+        }
+    } else {
+        let base = b * white;
+        if r <= g {
+            base + (r - b) * yellow + (g - r) * green
+        } else {
+            base + (g - b) * yellow + (r - g) * red
+        }
+    }
+}
+
+/// Linear sRGB estimate of a radiance `value` carried at a uniformly
+/// sampled `wavelength`. A constant spectrum averages to white.
+pub fn to_rgb(value: f32, wavelength: f32) -> Vec3 {
+    let xyz = cie_xyz(wavelength) * (value * (LAMBDA_MAX - LAMBDA_MIN));
+    xyz_to_linear_srgb(xyz) / white_rgb()
+}
+
+/// CIE 1931 color matching functions, multi-lobe fit by Wyman et al. 2013.
+pub fn cie_xyz(wavelength: f32) -> Vec3 {
+    let g = |mu: f32, s1: f32, s2: f32| {
+        let t = (wavelength - mu) / if wavelength < mu { s1 } else { s2 };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+/// CIE XYZ to linear sRGB with a D65 white point.
+pub fn xyz_to_linear_srgb(xyz: Vec3) -> Vec3 {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    Vec3::new(
+        3.240_454 * x - 1.537_138 * y - 0.498_531 * z,
+        -0.969_266 * x + 1.876_011 * y + 0.041_556 * z,
+        0.055_643 * x - 0.204_026 * y + 1.057_225 * z,
+    )
+}
+
+/// Linear sRGB color of a black body at `kelvin`, scaled to unit luminance.
+pub fn blackbody(kelvin: f32) -> Vec3 {
+    // Planck's law, constants folded for λ in nm (only the shape matters)
+    let planck = |nm: f32| {
+        let um = nm / 1000.;
+        1. / (um.powi(5) * ((14_387.77 / (um * kelvin)).exp() - 1.))
+    };
+    let steps = 340;
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f32;
+    let mut xyz = Vec3::new(0., 0., 0.);
+    for i in 0..steps {
+        let nm = LAMBDA_MIN + (i as f32 + 0.5) * step;
+        xyz += cie_xyz(nm) * planck(nm);
+    }
+    if kelvin <= 0. || xyz.y() <= 0. {
+        return Vec3::new(0., 0., 0.);
+    }
+    let rgb = xyz_to_linear_srgb(xyz / xyz.y());
+    Vec3::new(rgb.x().max(0.), rgb.y().max(0.), rgb.z().max(0.))
+}
+
+/// sRGB of the constant unit spectrum, used to keep grey surfaces grey.
+fn white_rgb() -> Vec3 {
+    static WHITE: OnceLock<Vec3> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let steps = 1000;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f32;
+        let mut xyz = Vec3::new(0., 0., 0.);
+        for i in 0..steps {
+            xyz += cie_xyz(LAMBDA_MIN + (i as f32 + 0.5) * step) * step;
+        }
+        xyz_to_linear_srgb(xyz)
+    })
+}
+
+/// Smits 1999 basis spectra over ten equal bins of 380-720nm, in the order
+/// white, cyan, magenta, yellow, red, green, blue.
+const SMITS: [[f32; 10]; 7] = [
+    [
+        1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+    ],
+    [
+        0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+    ],
+    [
+        1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+    ],
+    [
+        0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+    ],
+    [
+        0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+    ],
+    [
+        0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+    ],
+    [
+        1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+    ],
+];
+
+#[cfg(test)]
+mod test {
+    use super::{blackbody, rgb_to_spectrum, to_rgb, LAMBDA_MAX, LAMBDA_MIN};
+    use crate::domain::vec3::Vec3;
+
+    /// Average of `to_rgb` over the visible range for a spectrum `s`.
+    fn integrate(s: impl Fn(f32) -> f32) -> Vec3 {
+        let steps = 2000;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f32;
+        let mut rgb = Vec3::new(0., 0., 0.);
+        for i in 0..steps {
+            let lambda = LAMBDA_MIN + (i as f32 + 0.5) * step;
+            rgb += to_rgb(s(lambda), lambda);
+        }
+        rgb / steps as f32
+    }
+
+    #[test]
+    fn white_round_trip() {
+        let rgb = integrate(|l| rgb_to_spectrum(Vec3::new(1., 1., 1.), l));
+        for c in [rgb.x(), rgb.y(), rgb.z()] {
+            assert!((c - 1.).abs() < 0.01, "{:?}", rgb);
+        }
+    }
+
+    #[test]
+    fn primaries_keep_their_hue() {
+        let red = integrate(|l| rgb_to_spectrum(Vec3::new(1., 0., 0.), l));
+        assert!(red.x() > red.y() && red.x() > red.z(), "{:?}", red);
+        let green = integrate(|l| rgb_to_spectrum(Vec3::new(0., 1., 0.), l));
+        assert!(
+            green.y() > green.x() && green.y() > green.z(),
+            "{:?}",
+            green
+        );
+        let blue = integrate(|l| rgb_to_spectrum(Vec3::new(0., 0., 1.), l));
+        assert!(blue.z() > blue.x() && blue.z() > blue.y(), "{:?}", blue);
+    }
+
+    #[test]
+    fn blackbody_colors() {
+        // Near D65 white, warm below it and blue above
+        let d65 = blackbody(6504.);
+        assert!((d65.x() - d65.z()).abs() < 0.15, "{d65:?}");
+        let tungsten = blackbody(2700.);
+        assert!(tungsten.x() > tungsten.y() && tungsten.y() > tungsten.z());
+        let sky = blackbody(12000.);
+        assert!(sky.z() > sky.x());
+    }
+}
@@ -1,8 +1,12 @@
 use super::{
+    aabb::Aabb,
     hittable::{Hit, Hittable},
     interval::Interval,
     material::Material,
+    onb::Onb,
     ray::Ray,
+    stats::{self, Counter},
+    utils::{INFINITY, PI},
     vec3::Vec3,
 };
 
@@ -10,6 +14,7 @@ pub struct Sphere<M: Material> {
     center: Vec3,
     radius: f32,
     material: M,
+    material_id: u32,
 }
 
 impl<M: Material> Sphere<M> {
@@ -18,12 +23,39 @@ impl<M: Material> Sphere<M> {
             center,
             radius,
             material,
+            material_id: 0,
         }
     }
+
+    /// Tags hits with `material_id` for the material id AOV.
+    pub fn with_material_id(mut self, material_id: u32) -> Self {
+        self.material_id = material_id;
+        self
+    }
+
+    /// Spherical (u, v) in [0, 1] of a point on the unit sphere.
+    fn get_sphere_uv(p: Vec3) -> (f32, f32) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2. * PI), theta / PI)
+    }
+
+    /// dp/du and dp/dv of `get_sphere_uv` at unit sphere point `p`.
+    fn get_sphere_partials(&self, p: Vec3) -> (Vec3, Vec3) {
+        let dpdu = Vec3::new(p.z(), 0., -p.x()) * (2. * PI * self.radius);
+        let sin_theta = (1. - p.y() * p.y()).max(1e-6).sqrt();
+        let dpdv = Vec3::new(
+            -p.x() * p.y() / sin_theta,
+            sin_theta,
+            -p.z() * p.y() / sin_theta,
+        ) * (PI * self.radius);
+        (dpdu, dpdv)
+    }
 }
 
 impl<M: Material> Hittable for Sphere<M> {
-    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<Hit> {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<Hit<'_>> {
+        stats::count(Counter::SphereTests);
         let oc = r.origin() - self.center;
         let a = r.direction().length_squared();
         let half_b = Vec3::dot(oc, r.direction());
@@ -44,15 +76,112 @@ impl<M: Material> Hittable for Sphere<M> {
         let t = root;
         let p = r.at(t);
         let outward_normal = (p - self.center) / self.radius;
+        let (u, v) = Self::get_sphere_uv(outward_normal);
+        let (dpdu, dpdv) = self.get_sphere_partials(outward_normal);
 
         let mut hit = Hit {
             p,
             normal: Vec3::new(0., 0., 0.),
+            geometric_normal: Vec3::new(0., 0., 0.),
             t,
+            u,
+            v,
+            dpdu,
+            dpdv,
             material: &self.material,
             front_face: false,
+            object_id: 0,
+            material_id: self.material_id,
         };
         hit.set_face_normal(r, outward_normal);
         Some(hit)
     }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let distance_squared = (self.center - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 0.;
+        }
+        if self
+            .hit(
+                &Ray::new(origin, direction),
+                &Interval::new(0.001, INFINITY),
+            )
+            .is_none()
+        {
+            return 0.;
+        }
+        let cos_theta_max = (1. - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2. * PI * (1. - cos_theta_max);
+        1. / solid_angle
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Vec3::random_unit_vector();
+        }
+        let uvw = Onb::build_from_w(direction);
+        uvw.local(Vec3::random_to_sphere(self.radius, distance_squared))
+    }
+
+    fn sample_surface(&self) -> Option<(Hit<'_>, f32)> {
+        let outward_normal = Vec3::random_unit_vector();
+        let (u, v) = Self::get_sphere_uv(outward_normal);
+        let (dpdu, dpdv) = self.get_sphere_partials(outward_normal);
+        let hit = Hit {
+            p: self.center + outward_normal * self.radius,
+            normal: outward_normal,
+            geometric_normal: outward_normal,
+            t: 0.,
+            u,
+            v,
+            dpdu,
+            dpdv,
+            material: &self.material,
+            front_face: true,
+            object_id: 0,
+            material_id: self.material_id,
+        };
+        Some((hit, 1. / (4. * PI * self.radius * self.radius)))
+    }
+
+    fn surface_pdf(&self, p: Vec3) -> f32 {
+        let distance = (p - self.center).length();
+        if (distance - self.radius).abs() > 1e-3 * self.radius.max(1.) {
+            return 0.;
+        }
+        1. / (4. * PI * self.radius * self.radius)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::material::Lambertian;
+
+    #[test]
+    fn partials_match_uv_mapping() {
+        let sphere = Sphere::new(
+            Vec3::new(0., 0., 0.),
+            2.,
+            Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+        );
+        let ray = Ray::new(Vec3::new(5., 0.7, 0.4), Vec3::new(-1., 0., 0.));
+        let hit = sphere.hit(&ray, &Interval::new(0.001, INFINITY)).unwrap();
+
+        // Step along the partials, the uv should move by the step
+        let d = 1e-3;
+        let (u, v) = Sphere::<Lambertian>::get_sphere_uv(Vec3::unit_vector(hit.p + hit.dpdu * d));
+        assert!((u - hit.u - d).abs() < 1e-4 && (v - hit.v).abs() < 1e-4);
+        let (u, v) = Sphere::<Lambertian>::get_sphere_uv(Vec3::unit_vector(hit.p + hit.dpdv * d));
+        assert!((u - hit.u).abs() < 1e-4 && (v - hit.v - d).abs() < 1e-4);
+        assert!(Vec3::cross(hit.dpdu, hit.dpdv).dot(hit.normal) > 0.);
+    }
 }
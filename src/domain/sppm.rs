@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use super::{
+    camera::Camera,
+    framebuffer::Framebuffer,
+    hittable::{Hit, Hittable},
+    integrator::{direct_light, Integrator},
+    interval::Interval,
+    light_bvh::estimate_power,
+    onb::Onb,
+    ray::Ray,
+    sampler::Sampler,
+    scene::Scene,
+    stats::{self, Counter, Counts},
+    utils::{random_f32, seed_random, with_seed, INFINITY, PI},
+    vec3::Vec3,
+};
+
+/// Stochastic progressive photon mapping. Each of the camera's
+/// `samples_per_pixel` passes finds one visible point per pixel, scatters
+/// `photons_per_pass` photons from the lights through specular surfaces and
+/// gathers them at the visible points with a shrinking radius. Photons
+/// leave area lights and analytic ones, the sun included, but not the sky.
+pub struct SppmIntegrator {
+    pub photons_per_pass: i32,
+    pub initial_radius: f32,
+    /// Share of each pass' photons kept when shrinking the radius.
+    pub alpha: f32,
+    pub max_depth: i32,
+}
+
+/// Photons per task of the photon pass, each traced from its own random
+/// stream.
+const PHOTON_CHUNK: i32 = 1024;
+
+/// Seed of task `index` of pass `pass`, so the image doesn't depend on the
+/// number of threads.
+fn stream(seed: u64, pass: i32, index: usize) -> u64 {
+    seed.wrapping_add((pass as u64 + 1) << 32)
+        .wrapping_add(index as u64)
+}
+
+/// Where a camera path came to rest on a non-specular surface this pass.
+struct VisiblePoint<'a> {
+    hit: Hit<'a>,
+    ray_in: Ray,
+    beta: Vec3,
+}
+
+/// Where photons leave from: the area lights as one source, then every
+/// analytic light, picked by power like `LightBvh` picks area lights.
+struct PhotonSources {
+    /// Running sum of power, area lights first.
+    cdf: Vec<f32>,
+    /// Sphere around the world that distant lights shine on, none when the
+    /// world is unbounded.
+    center: Vec3,
+    radius: f32,
+}
+
+impl PhotonSources {
+    fn new(scene: &Scene) -> Self {
+        let (center, radius) = scene
+            .world
+            .bounding_box()
+            .map_or((Vec3::default(), 0.), |b| {
+                (b.centroid(), b.diagonal().length() / 2.)
+            });
+        let area = estimate_power(&scene.lights).map_or(0., |(phi, _)| phi);
+        let analytic = scene.analytic_lights.iter().map(|light| {
+            let power = light.power(radius);
+            (power.x() + power.y() + power.z()) / 3.
+        });
+
+        let mut total = 0.;
+        let cdf = std::iter::once(area)
+            .chain(analytic)
+            .map(|phi| {
+                total += phi.max(0.);
+                total
+            })
+            .collect();
+        Self {
+            cdf,
+            center,
+            radius,
+        }
+    }
+
+    /// Picks a source, 0 for the area lights and `i + 1` for analytic light
+    /// `i`, with its probability. Area lights alone need no choice.
+    fn pick(&self) -> Option<(usize, f32)> {
+        if self.cdf.len() == 1 {
+            return Some((0, 1.));
+        }
+        let total = *self.cdf.last()?;
+        if total <= 0. {
+            return None;
+        }
+        let target = random_f32() * total;
+        let source = self
+            .cdf
+            .partition_point(|&c| c <= target)
+            .min(self.cdf.len() - 1);
+        let below = if source == 0 {
+            0.
+        } else {
+            self.cdf[source - 1]
+        };
+        Some((source, (self.cdf[source] - below) / total))
+    }
+}
+
+/// Statistics one pixel carries between passes.
+struct SppmPixel<'a> {
+    radius: f32,
+    /// Emitted and direct light summed over all passes.
+    ld: Vec3,
+    vp: Option<VisiblePoint<'a>>,
+    /// Photon flux gathered this pass and how many photons it came from.
+    phi: Vec3,
+    m: i32,
+    n: f32,
+    tau: Vec3,
+}
+
+impl<'a> SppmPixel<'a> {
+    fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            ld: Vec3::new(0., 0., 0.),
+            vp: None,
+            phi: Vec3::new(0., 0., 0.),
+            m: 0,
+            n: 0.,
+            tau: Vec3::new(0., 0., 0.),
+        }
+    }
+
+    /// Folds this pass' photons into `tau` and shrinks the radius.
+    fn update(&mut self, alpha: f32) {
+        if let (Some(vp), true) = (&self.vp, self.m > 0) {
+            let n_new = self.n + alpha * self.m as f32;
+            let radius_new = self.radius * (n_new / (self.n + self.m as f32)).sqrt();
+            let shrink = (radius_new * radius_new) / (self.radius * self.radius);
+            self.tau = (self.tau + vp.beta * self.phi) * shrink;
+            self.n = n_new;
+            self.radius = radius_new;
+        }
+        self.phi = Vec3::new(0., 0., 0.);
+        self.m = 0;
+        self.vp = None;
+    }
+}
+
+/// Uniform hash grid over the visible points of a pass. Each point is
+/// stored in every cell its gather sphere overlaps.
+struct VisiblePointGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32, i32), Vec<usize>>,
+}
+
+impl VisiblePointGrid {
+    fn new(pixels: &[SppmPixel]) -> Self {
+        let cell_size = pixels
+            .iter()
+            .filter(|px| px.vp.is_some())
+            .map(|px| px.radius)
+            .fold(0., f32::max)
+            .max(1e-4);
+        let mut grid = Self {
+            cell_size,
+            cells: HashMap::new(),
+        };
+
+        for (i, px) in pixels.iter().enumerate() {
+            let Some(vp) = &px.vp else {
+                continue;
+            };
+            let r = Vec3::new(px.radius, px.radius, px.radius);
+            let (x0, y0, z0) = grid.cell(vp.hit.p - r);
+            let (x1, y1, z1) = grid.cell(vp.hit.p + r);
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    for z in z0..=z1 {
+                        grid.cells.entry((x, y, z)).or_default().push(i);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    fn cell(&self, p: Vec3) -> (i32, i32, i32) {
+        (
+            (p.x() / self.cell_size).floor() as i32,
+            (p.y() / self.cell_size).floor() as i32,
+            (p.z() / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn lookup(&self, p: Vec3) -> &[usize] {
+        self.cells
+            .get(&self.cell(p))
+            .map(|c| c.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+impl SppmIntegrator {
+    pub fn new(photons_per_pass: i32, initial_radius: f32, max_depth: i32) -> Self {
+        Self {
+            photons_per_pass,
+            initial_radius,
+            alpha: 2. / 3.,
+            max_depth,
+        }
+    }
+
+    /// Follows `ray` through specular bounces. Returns the emitted and direct
+    /// light found on the way and the visible point it stopped at, if any.
+    fn camera_path<'a>(&self, ray: Ray, scene: &'a Scene) -> (Vec3, Option<VisiblePoint<'a>>) {
+        let mut ray = ray;
+        let mut beta = Vec3::new(1., 1., 1.);
+        let mut ld = Vec3::new(0., 0., 0.);
+        stats::count(Counter::Paths);
+
+        for _ in 0..self.max_depth {
+            let Some(h) = scene.hit(&ray, &Interval::new(0.001, INFINITY)) else {
+                return (ld + beta * scene.background(&ray), None);
+            };
+            ld += beta * h.material.emitted(&ray, &h);
+
+            let Some((att, scatt)) = h.material.scatter(&ray, &h) else {
+                return (ld, None);
+            };
+            if h.material.scattering_pdf(&ray, &h, &scatt) > 0. {
+                ld += beta * direct_light(&ray, &h, scene);
+                let vp = VisiblePoint {
+                    hit: h,
+                    ray_in: ray,
+                    beta,
+                };
+                return (ld, Some(vp));
+            }
+            beta = beta * att;
+            ray = scatt;
+            stats::count(Counter::Bounces);
+        }
+        (ld, None)
+    }
+
+    /// Starts a photon on the area lights, as its power divided by the pdf
+    /// and the ray leaving the light.
+    fn area_photon(scene: &Scene) -> Option<(Vec3, Ray)> {
+        let (light, pdf_pos) = scene.lights.sample_surface()?;
+        let normal = light.outward_normal();
+        let direction = Onb::build_from_w(normal).local(Vec3::random_cosine_direction());
+        let cosine = normal.dot(direction);
+        let pdf_dir = cosine / PI;
+        let le = light
+            .material
+            .emitted(&Ray::new(light.p + direction, -direction), &light);
+        if le == Vec3::default() || pdf_pos <= 0. || pdf_dir <= 0. {
+            return None;
+        }
+        Some((
+            le * (cosine / (pdf_pos * pdf_dir)),
+            Ray::new(light.p, direction),
+        ))
+    }
+
+    /// Emits one photon and records the flux it leaves at every visible
+    /// point near its hits as `(pixel, flux)`. The first hit is skipped, the
+    /// camera pass already has direct light.
+    fn trace_photon(
+        &self,
+        scene: &Scene,
+        sources: &PhotonSources,
+        grid: &VisiblePointGrid,
+        pixels: &[SppmPixel],
+        deposits: &mut Vec<(usize, Vec3)>,
+    ) {
+        let Some((source, pmf)) = sources.pick() else {
+            return;
+        };
+        let photon = match source {
+            0 => Self::area_photon(scene),
+            i => scene.analytic_lights[i - 1].sample_le(sources.center, sources.radius),
+        };
+        let Some((beta, mut ray)) = photon else {
+            return;
+        };
+        let mut beta = beta / pmf;
+        stats::count(Counter::Paths);
+        for depth in 0..self.max_depth {
+            let Some(h) = scene.hit(&ray, &Interval::new(0.001, INFINITY)) else {
+                break;
+            };
+
+            if depth > 0 {
+                let wi = -Vec3::unit_vector(ray.direction());
+                for &i in grid.lookup(h.p) {
+                    let px = &pixels[i];
+                    let Some(vp) = &px.vp else {
+                        continue;
+                    };
+                    if (vp.hit.p - h.p).length_squared() > px.radius * px.radius {
+                        continue;
+                    }
+                    let cos_vp = vp.hit.normal.dot(wi);
+                    if cos_vp <= 0. {
+                        continue;
+                    }
+                    // Density estimation wants the bare BSDF, eval includes the cosine
+                    let f = vp
+                        .hit
+                        .material
+                        .eval(&vp.ray_in, &vp.hit, &Ray::new(vp.hit.p, wi))
+                        / cos_vp;
+                    deposits.push((i, beta * f));
+                }
+            }
+
+            let Some((att, scatt)) = h.material.scatter(&ray, &h) else {
+                break;
+            };
+            let beta_new = beta * att;
+            let survive = (beta_new.max_component() / beta.max_component()).min(1.);
+            if survive <= 0. || random_f32() >= survive {
+                stats::count(Counter::RouletteKills);
+                break;
+            }
+            beta = beta_new / survive;
+            ray = scatt;
+            stats::count(Counter::Bounces);
+        }
+    }
+}
+
+impl Integrator for SppmIntegrator {
+    /// Only the camera pass (emission plus direct light), photons need the
+    /// whole image and are gathered in `render_image`.
+    fn li(&self, ray: Ray, scene: &Scene, _sampler: &mut dyn Sampler) -> Vec3 {
+        self.camera_path(ray, scene).0
+    }
+
+    fn render_image(&self, camera: &Camera, scene: &Scene) -> Option<Framebuffer> {
+        let width = camera.image_width;
+        let height = camera.image_height();
+        let passes = camera.samples_per_pixel.max(1);
+        let mut pixels: Vec<SppmPixel> = (0..width * height)
+            .map(|_| SppmPixel::new(self.initial_radius))
+            .collect();
+
+        // Sampled power would take from the caller's random sequence
+        let sources = with_seed(camera.seed, || PhotonSources::new(scene));
+
+        // Both passes run on the current rayon pool, the counts of every
+        // task go back to the calling thread
+        let mut counts = stats::take();
+        for pass in 0..passes {
+            let rows: Vec<Counts> = pixels
+                .par_chunks_mut(width as usize)
+                .enumerate()
+                .map(|(j, row)| {
+                    seed_random(stream(camera.seed, pass, j));
+                    stats::take();
+                    for (i, px) in row.iter_mut().enumerate() {
+                        let ray = camera.get_ray(i as f32, j as f32);
+                        stats::count(Counter::PrimaryRays);
+                        let (ld, vp) = self.camera_path(ray, scene);
+                        px.ld += ld;
+                        px.vp = vp;
+                    }
+                    stats::take()
+                })
+                .collect();
+            rows.iter().for_each(|row| counts.merge(row));
+
+            // Photons deposit into per task lists, added in task order
+            let grid = VisiblePointGrid::new(&pixels);
+            let tasks: Vec<(Vec<(usize, Vec3)>, Counts)> = (0..self.photons_per_pass.max(0))
+                .step_by(PHOTON_CHUNK as usize)
+                .enumerate()
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map(|(task, first)| {
+                    seed_random(stream(camera.seed, pass, height as usize + task));
+                    stats::take();
+                    let mut deposits = vec![];
+                    for _ in first..(first + PHOTON_CHUNK).min(self.photons_per_pass) {
+                        self.trace_photon(scene, &sources, &grid, &pixels, &mut deposits);
+                    }
+                    (deposits, stats::take())
+                })
+                .collect();
+            for (deposits, task_counts) in tasks {
+                counts.merge(&task_counts);
+                for (i, flux) in deposits {
+                    pixels[i].phi += flux;
+                    pixels[i].m += 1;
+                }
+            }
+            for px in pixels.iter_mut() {
+                px.update(self.alpha);
+            }
+        }
+        stats::add_counts(&counts);
+
+        // Camera sums every sample, so hand back `passes` times the estimate
+        let photons = passes as f32 * self.photons_per_pass as f32;
+        let mut image = Framebuffer::new(width, height);
+        for j in 0..height {
+            for i in 0..width {
+                let px = &pixels[(j * width + i) as usize];
+                if photons <= 0. {
+                    image.add(i, j, px.ld);
+                    continue;
+                }
+                let indirect = px.tau / (photons * PI * px.radius * px.radius);
+                image.add(i, j, px.ld + indirect * passes as f32);
+            }
+        }
+        Some(image)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::{
+        hittable_list::HittableList,
+        light::PointLight,
+        material::{Dialectric, Lambertian},
+        mesh::TriangleMesh,
+        scenes,
+        sphere::Sphere,
+    };
+
+    #[test]
+    fn passes_are_the_same_on_any_number_of_threads() {
+        let description = scenes::cornell_box();
+        let camera = Camera::new(1., 12, 3).with_vfov(40.);
+        let integrator = SppmIntegrator::new(3000, 20., 5);
+        let render = |threads: usize| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| camera.render_frame(&description.scene, &integrator))
+        };
+        let (one, four) = (render(1), render(4));
+        for y in 0..12 {
+            for x in 0..12 {
+                assert_eq!(
+                    one.beauty.get(x, y),
+                    four.beauty.get(x, y),
+                    "pixel {x}, {y}"
+                );
+            }
+        }
+        assert_eq!(one.stats.counts, four.stats.counts);
+        assert_eq!(one.stats.counts[Counter::PrimaryRays], 12 * 12 * 3);
+    }
+
+    #[test]
+    fn point_lights_cast_caustics() {
+        // A glass ball between a point light and the wall behind it, with
+        // the camera looking through both. No area lights at all.
+        let mut world = HittableList::new();
+        world.push(Box::new(Sphere::new(
+            Vec3::new(0., 0., -2.),
+            0.5,
+            Dialectric::new(1.5),
+        )));
+        world.push(Box::new(TriangleMesh::quad(
+            Vec3::new(-4., -4., -4.),
+            Vec3::new(8., 0., 0.),
+            Vec3::new(0., 8., 0.),
+            Lambertian::new(Vec3::new(0.8, 0.8, 0.8)),
+        )));
+        let scene = Scene::new(world, HittableList::new())
+            .with_background(Vec3::default())
+            .with_light(PointLight::new(
+                Vec3::new(0., 0., -0.5),
+                Vec3::new(5., 5., 5.),
+            ));
+        let camera = Camera::new(1., 9, 4).with_vfov(10.);
+        let center = |photons: i32| {
+            let frame = camera.render_frame(&scene, &SppmIntegrator::new(photons, 0.2, 8));
+            frame.beauty.get(4, 4).x()
+        };
+
+        // The ball shadows the wall from direct light, so only photons
+        // focused through it light the middle
+        assert_eq!(center(0), 0.);
+        assert!(center(20_000) > 1., "{}", center(20_000));
+    }
+}
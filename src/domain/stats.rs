@@ -0,0 +1,284 @@
+use std::{cell::Cell, fmt, ops::Index, time::Duration};
+
+/// Events counted while rendering.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Counter {
+    /// Rays leaving the camera, one per sample plus one per pixel for AOVs.
+    PrimaryRays,
+    /// Every ray traced through `Scene::hit`, primary ones included.
+    Rays,
+    SphereTests,
+    TriangleTests,
+    /// Slab tests against bounding boxes.
+    BoxTests,
+    /// Nodes of a `LightBvh` or mesh BVH visited while sampling or tracing.
+    BvhNodes,
+    /// Camera and light paths started by the integrators, one per `li` call
+    /// for those that stop at the first hits.
+    Paths,
+    /// Scattering events along those paths.
+    Bounces,
+    /// Paths ended by Russian roulette.
+    RouletteKills,
+}
+
+const COUNTERS: usize = Counter::ALL.len();
+
+impl Counter {
+    pub const ALL: [Counter; 9] = [
+        Counter::PrimaryRays,
+        Counter::Rays,
+        Counter::SphereTests,
+        Counter::TriangleTests,
+        Counter::BoxTests,
+        Counter::BvhNodes,
+        Counter::Paths,
+        Counter::Bounces,
+        Counter::RouletteKills,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Counter::PrimaryRays => "primary_rays",
+            Counter::Rays => "rays",
+            Counter::SphereTests => "sphere_tests",
+            Counter::TriangleTests => "triangle_tests",
+            Counter::BoxTests => "box_tests",
+            Counter::BvhNodes => "bvh_nodes",
+            Counter::Paths => "paths",
+            Counter::Bounces => "bounces",
+            Counter::RouletteKills => "roulette_kills",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Counter> {
+        Counter::ALL
+            .into_iter()
+            .find(|counter| counter.name() == name)
+    }
+}
+
+thread_local! {
+    static COUNTS: [Cell<u64>; COUNTERS] = const { [const { Cell::new(0) }; COUNTERS] };
+}
+
+/// Adds one to `counter` on this thread.
+pub fn count(counter: Counter) {
+    add(counter, 1);
+}
+
+pub fn add(counter: Counter, n: u64) {
+    COUNTS.with(|counts| {
+        let cell = &counts[counter as usize];
+        cell.set(cell.get() + n);
+    });
+}
+
+/// Adds `counts` to this thread's, such as what helper threads counted on
+/// its behalf.
+pub fn add_counts(counts: &Counts) {
+    for counter in Counter::ALL {
+        add(counter, counts[counter]);
+    }
+}
+
+/// This thread's counts so far, leaving it at zero. Renders take them
+/// around each piece of work and merge the results, so counts of other
+/// renders on the same threads don't leak in.
+pub fn take() -> Counts {
+    COUNTS.with(|counts| Counts(counts.each_ref().map(|cell| cell.take())))
+}
+
+/// A snapshot of every counter.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counts([u64; COUNTERS]);
+
+impl Counts {
+    pub fn merge(&mut self, other: &Counts) {
+        for (a, b) in self.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+    }
+
+    /// Shadow rays and bounces, everything but the camera's rays.
+    pub fn secondary_rays(&self) -> u64 {
+        self[Counter::Rays].saturating_sub(self[Counter::PrimaryRays])
+    }
+
+    /// Bounces per path, 0 when no paths were walked.
+    pub fn average_path_length(&self) -> f32 {
+        self[Counter::Bounces] as f32 / self[Counter::Paths].max(1) as f32
+    }
+}
+
+impl Index<Counter> for Counts {
+    type Output = u64;
+
+    fn index(&self, counter: Counter) -> &u64 {
+        &self.0[counter as usize]
+    }
+}
+
+/// What a render did and how long it took, returned with the `Frame`.
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    /// Counters of every thread, merged.
+    pub counts: Counts,
+    /// Wall-clock time of each phase, in order.
+    pub phases: Vec<(&'static str, Duration)>,
+    /// Threads the work was spread over.
+    pub threads: usize,
+}
+
+impl RenderStats {
+    pub fn total_time(&self) -> Duration {
+        self.phases.iter().map(|(_, time)| *time).sum()
+    }
+
+    /// Rays traced per second of the whole render.
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.total_time().as_secs_f64();
+        if seconds <= 0. {
+            return 0.;
+        }
+        self.counts[Counter::Rays] as f64 / seconds
+    }
+}
+
+/// Multi-line summary for the terminal.
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = &self.counts;
+        writeln!(
+            f,
+            "rays       {} primary, {} secondary, {:.2}M/s on {} thread{}",
+            c[Counter::PrimaryRays],
+            c.secondary_rays(),
+            self.rays_per_second() / 1e6,
+            self.threads,
+            if self.threads == 1 { "" } else { "s" }
+        )?;
+        writeln!(
+            f,
+            "tests      {} spheres, {} triangles, {} boxes, {} bvh nodes",
+            c[Counter::SphereTests],
+            c[Counter::TriangleTests],
+            c[Counter::BoxTests],
+            c[Counter::BvhNodes]
+        )?;
+        // AOV renders don't walk paths
+        if c[Counter::Paths] > 0 {
+            writeln!(
+                f,
+                "paths      {}, {:.2} bounces on average, {} ended by roulette",
+                c[Counter::Paths],
+                c.average_path_length(),
+                c[Counter::RouletteKills]
+            )?;
+        }
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .map(|(name, time)| format!("{name} {time:.2?}"))
+            .collect();
+        write!(
+            f,
+            "time       {:.2?}: {}",
+            self.total_time(),
+            phases.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::{
+        aov::Aov,
+        camera::Camera,
+        integrator::{
+            AmbientOcclusion, AovIntegrator, DirectLighting, Integrator, PathIntegrator,
+            WhittedIntegrator,
+        },
+        scenes,
+    };
+
+    #[test]
+    fn counts_are_taken_per_thread() {
+        take();
+        count(Counter::Paths);
+        add(Counter::Bounces, 5);
+        let elsewhere = std::thread::spawn(|| {
+            count(Counter::Paths);
+            take()
+        })
+        .join()
+        .unwrap();
+
+        let mut counts = take();
+        assert_eq!(take(), Counts::default());
+        assert_eq!(counts[Counter::Paths], 1);
+        counts.merge(&elsewhere);
+        assert_eq!(counts[Counter::Paths], 2);
+        assert_eq!(counts.average_path_length(), 2.5);
+        for counter in Counter::ALL {
+            assert_eq!(Counter::from_name(counter.name()), Some(counter));
+        }
+    }
+
+    #[test]
+    fn render_counts_match_the_work() {
+        let description = scenes::two_spheres();
+        let camera = Camera::new(2., 16, 3);
+        let render = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| camera.render_frame(&description.scene, &PathIntegrator::new(8)))
+                .stats
+        };
+        let stats = render(1);
+        let c = &stats.counts;
+
+        assert_eq!(c[Counter::PrimaryRays], 16 * 8 * 3);
+        assert_eq!(c[Counter::Paths], c[Counter::PrimaryRays]);
+        assert!(c.secondary_rays() > 0 && c[Counter::RouletteKills] > 0);
+        // The world is three spheres, tested once each per ray, and the
+        // lamp's pdf tests its own sphere on top
+        assert!(c[Counter::SphereTests] > 3 * c[Counter::Rays]);
+        assert!(stats.phases.iter().any(|(name, _)| *name == "pixels"));
+        assert_eq!(render(4).counts, stats.counts);
+        assert!(stats.to_string().starts_with("rays       384 primary"));
+    }
+
+    #[test]
+    fn every_integrator_counts_its_paths() {
+        let description = scenes::two_spheres();
+        let camera = Camera::new(2., 16, 2);
+        let integrators: [(&str, Box<dyn Integrator>); 4] = [
+            ("whitted", Box::new(WhittedIntegrator::new(8))),
+            ("direct", Box::new(DirectLighting::new(8))),
+            ("ao", Box::new(AmbientOcclusion::new(2, 1.))),
+            ("aov", Box::new(AovIntegrator::new(Aov::ShadingNormal))),
+        ];
+        for (name, integrator) in integrators {
+            let stats = camera
+                .render_frame(&description.scene, integrator.as_ref())
+                .stats;
+            let c = &stats.counts;
+            let summary = stats.to_string();
+            if name == "aov" {
+                assert_eq!(c[Counter::Paths], 0);
+                assert!(!summary.contains("paths"), "{summary}");
+            } else {
+                assert_eq!(c[Counter::Paths], c[Counter::PrimaryRays], "{name}");
+                assert!(summary.contains("\npaths      "), "{name}: {summary}");
+            }
+            if name == "direct" {
+                // Every sample that lands on a sphere takes its MIS bounce
+                assert!(c[Counter::Bounces] > 0);
+            }
+        }
+    }
+}
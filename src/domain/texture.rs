@@ -0,0 +1,135 @@
+use std::{io, path::Path};
+
+use super::{color::gamma_to_linear, vec3::Vec3};
+
+/// Color (or scalar, read from any channel) varying over a surface.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3;
+}
+
+#[derive(Clone)]
+pub struct SolidColor {
+    color: Vec3,
+}
+
+impl SolidColor {
+    pub fn new(color: Vec3) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _p: Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+/// 3D checkerboard of cubes `scale` wide.
+#[derive(Clone)]
+pub struct CheckerTexture {
+    inv_scale: f32,
+    even: Vec3,
+    odd: Vec3,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f32, even: Vec3, odd: Vec3) -> Self {
+        Self {
+            inv_scale: 1. / scale,
+            even,
+            odd,
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, _u: f32, _v: f32, p: Vec3) -> Vec3 {
+        let x = (self.inv_scale * p.x()).floor() as i32;
+        let y = (self.inv_scale * p.y()).floor() as i32;
+        let z = (self.inv_scale * p.z()).floor() as i32;
+        if (x + y + z) % 2 == 0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+/// Bitmap looked up by (u, v), v = 0 at the bottom row.
+#[derive(Clone)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count");
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Loads a color image (PNG or JPEG), converted to linear.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(path.as_ref(), gamma_to_linear)
+    }
+
+    /// Loads an image holding data rather than color, e.g. a normal or
+    /// height map, keeping the stored values.
+    pub fn load_raw(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(path.as_ref(), |c| c)
+    }
+
+    /// Loads the alpha channel of an image as a grey texture, for cutout
+    /// masks. Images without alpha are fully opaque.
+    pub fn load_alpha(path: impl AsRef<Path>) -> io::Result<Self> {
+        let image = image::open(path).map_err(io::Error::other)?.into_rgba8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let pixels = image
+            .pixels()
+            .map(|px| {
+                let a = px.0[3] as f32 / 255.;
+                Vec3::new(a, a, a)
+            })
+            .collect();
+        Ok(Self::new(width, height, pixels))
+    }
+
+    fn read(path: &Path, decode: fn(f32) -> f32) -> io::Result<Self> {
+        let image = image::open(path).map_err(io::Error::other)?.into_rgb8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let pixels = image
+            .pixels()
+            .map(|px| {
+                let [r, g, b] = px.0.map(|c| decode(c as f32 / 255.));
+                Vec3::new(r, g, b)
+            })
+            .collect();
+        Ok(Self::new(width, height, pixels))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _p: Vec3) -> Vec3 {
+        if self.pixels.is_empty() {
+            return Vec3::new(0., 1., 1.);
+        }
+        let u = u.clamp(0., 1.);
+        let v = 1. - v.clamp(0., 1.);
+        let i = ((u * self.width as f32) as usize).min(self.width - 1);
+        let j = ((v * self.height as f32) as usize).min(self.height - 1);
+        self.pixels[j * self.width + i]
+    }
+}
@@ -0,0 +1,52 @@
+use super::{hittable::Hit, material::Material, ray::Ray, vec3::Vec3};
+
+/// Different materials on either side of a surface, picked by
+/// `Hit::front_face`.
+pub struct TwoSided<F: Material, B: Material> {
+    front: F,
+    back: B,
+}
+
+impl<F: Material, B: Material> TwoSided<F, B> {
+    pub fn new(front: F, back: B) -> Self {
+        Self { front, back }
+    }
+
+    fn side(&self, hit: &Hit) -> &dyn Material {
+        if hit.front_face {
+            &self.front
+        } else {
+            &self.back
+        }
+    }
+}
+
+impl<F: Material, B: Material> Material for TwoSided<F, B> {
+    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Vec3, Ray)> {
+        self.side(hit).scatter(ray, hit)
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        self.side(hit).emitted(ray, hit)
+    }
+
+    fn alpha(&self, hit: &Hit) -> f32 {
+        self.side(hit).alpha(hit)
+    }
+
+    fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        self.side(hit).shading_normal(hit)
+    }
+
+    fn albedo(&self, hit: &Hit) -> Vec3 {
+        self.side(hit).albedo(hit)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> Vec3 {
+        self.side(hit).eval(ray, hit, scattered)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        self.side(hit).scattering_pdf(ray, hit, scattered)
+    }
+}
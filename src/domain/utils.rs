@@ -1,12 +1,59 @@
+use std::cell::Cell;
+
 pub const INFINITY: f32 = f32::INFINITY;
-pub const PI: f32 = 3.1415926535897932385;
+pub const PI: f32 = std::f32::consts::PI;
 
 pub fn degrees_to_radians(degrees: f32) -> f32 {
     degrees * PI / 180.
 }
 
+/// Power heuristic (beta = 2) weight for a sample drawn from `f_pdf` when
+/// the same direction could also have been drawn from `g_pdf`.
+pub fn power_heuristic(f_pdf: f32, g_pdf: f32) -> f32 {
+    let f = f_pdf * f_pdf;
+    let g = g_pdf * g_pdf;
+    if f + g == 0. {
+        return 0.;
+    }
+    f / (f + g)
+}
+
+thread_local! {
+    static RNG: Cell<u64> = const { Cell::new(0x853C_49E6_748F_EA9B) };
+}
+
+/// Restarts this thread's generator, so a render seeded the same way per
+/// row gives the same image on any number of threads.
+pub fn seed_random(seed: u64) {
+    // SplitMix64, so nearby seeds start far apart
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    RNG.with(|state| state.set(z ^ (z >> 31)));
+}
+
+/// Runs `f` on this thread's generator seeded with `seed`, then puts the
+/// previous state back, so the caller's sequence goes on undisturbed.
+pub fn with_seed<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+    let saved = RNG.with(|state| state.get());
+    seed_random(seed);
+    let result = f();
+    RNG.with(|state| state.set(saved));
+    result
+}
+
+/// Uniform in [0, 1) from a per thread PCG32 generator.
 pub fn random_f32() -> f32 {
-    (unsafe { libc::rand() as f32 } / (libc::RAND_MAX as f32))
+    RNG.with(|state| {
+        let old = state.get();
+        state.set(
+            old.wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407),
+        );
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let bits = xorshifted.rotate_right((old >> 59) as u32);
+        (bits >> 8) as f32 / (1 << 24) as f32
+    })
 }
 
 pub fn random_f32_custom(min: f32, max: f32) -> f32 {
@@ -17,7 +64,7 @@ pub fn random_f32_custom(min: f32, max: f32) -> f32 {
 mod test {
     use crate::domain::utils::random_f32_custom;
 
-    use super::random_f32;
+    use super::{power_heuristic, random_f32, seed_random, with_seed};
 
     #[test]
     fn random() {
@@ -36,4 +83,31 @@ mod test {
             assert!(r >= 0.);
         }
     }
+
+    #[test]
+    fn seeding_repeats_the_sequence() {
+        seed_random(7);
+        let a: Vec<f32> = (0..8).map(|_| random_f32()).collect();
+        seed_random(8);
+        let b: Vec<f32> = (0..8).map(|_| random_f32()).collect();
+        seed_random(7);
+        let c: Vec<f32> = (0..8).map(|_| random_f32()).collect();
+        assert_eq!(a, c);
+        assert_ne!(a, b);
+
+        seed_random(7);
+        let first = random_f32();
+        let inner = with_seed(8, random_f32);
+        assert_eq!((first, inner, random_f32()), (a[0], b[0], a[1]));
+    }
+
+    #[test]
+    fn power_heuristic_weights() {
+        assert_eq!(power_heuristic(1., 1.), 0.5);
+        assert_eq!(power_heuristic(2., 0.), 1.);
+        assert_eq!(power_heuristic(0., 2.), 0.);
+        assert_eq!(power_heuristic(0., 0.), 0.);
+        let w = power_heuristic(3., 1.) + power_heuristic(1., 3.);
+        assert!((w - 1.).abs() < 1e-6);
+    }
 }
@@ -1,6 +1,6 @@
 use std::ops;
 
-use super::utils::{random_f32, random_f32_custom};
+use super::utils::{random_f32, random_f32_custom, PI};
 
 #[derive(Debug, PartialEq, Clone, Copy, Default)]
 pub struct Vec3 {
@@ -38,6 +38,10 @@ impl Vec3 {
         }
     }
 
+    pub fn max_component(self) -> f32 {
+        self.e[0].max(self.e[1]).max(self.e[2])
+    }
+
     pub fn length(self) -> f32 {
         self.length_squared().sqrt()
     }
@@ -89,6 +93,32 @@ impl Vec3 {
         }
     }
 
+    /// Cosine weighted direction around +z.
+    pub fn random_cosine_direction() -> Vec3 {
+        Self::cosine_direction(random_f32(), random_f32())
+    }
+
+    /// Cosine weighted direction around +z from two uniform samples.
+    pub fn cosine_direction(r1: f32, r2: f32) -> Vec3 {
+        let phi = 2. * PI * r1;
+        let x = phi.cos() * r2.sqrt();
+        let y = phi.sin() * r2.sqrt();
+        let z = (1. - r2).sqrt();
+        Vec3::new(x, y, z)
+    }
+
+    /// Uniform direction around +z inside the cone subtended by a sphere
+    /// of `radius` at `distance_squared` away.
+    pub fn random_to_sphere(radius: f32, distance_squared: f32) -> Vec3 {
+        let r1 = random_f32();
+        let r2 = random_f32();
+        let z = 1. + r2 * ((1. - radius * radius / distance_squared).sqrt() - 1.);
+        let phi = 2. * PI * r1;
+        let x = phi.cos() * (1. - z * z).sqrt();
+        let y = phi.sin() * (1. - z * z).sqrt();
+        Vec3::new(x, y, z)
+    }
+
     pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
         v - n * 2 * Vec3::dot(v, n)
     }
@@ -167,6 +197,20 @@ impl ops::Mul<i32> for Vec3 {
     }
 }
 
+impl ops::Div for Vec3 {
+    type Output = Self;
+
+    fn div(self, rhs: Vec3) -> Vec3 {
+        Vec3 {
+            e: [
+                self.e[0] / rhs.e[0],
+                self.e[1] / rhs.e[1],
+                self.e[2] / rhs.e[2],
+            ],
+        }
+    }
+}
+
 impl ops::Div<f32> for Vec3 {
     type Output = Self;
 
@@ -189,6 +233,14 @@ impl ops::AddAssign for Vec3 {
     }
 }
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f32;
+
+    fn index(&self, i: usize) -> &f32 {
+        &self.e[i]
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::Vec3;
@@ -234,6 +286,7 @@ mod test {
         assert_eq!(vec_zero() / 2., vec_zero());
         assert_eq!(inc() / 2., Vec3::new(0.5, 1., 3. / 2.,));
         assert_eq!(inc() / 1., Vec3::new(1., 2., 3.,));
+        assert_eq!(inc() / inc(), ones());
     }
 
     #[test]
@@ -254,9 +307,15 @@ mod test {
         assert_eq!(Vec3::cross(ones(), ones()), vec_zero());
     }
 
+    #[test]
+    fn max_component() {
+        assert_eq!(inc().max_component(), 3.);
+        assert_eq!(Vec3::new(-1., -5., -4.).max_component(), -1.);
+    }
+
     #[test]
     fn unit_vector() {
-        let x = 1.0 / (3 as f32).sqrt();
+        let x = 1.0 / 3_f32.sqrt();
         let unit = Vec3::new(x, x, x);
         assert_eq!(Vec3::unit_vector(ones()), unit);
     }
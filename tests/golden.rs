@@ -0,0 +1,157 @@
+//! Renders the built-in scenes small and with a fixed seed, and compares
+//! them with the references in `tests/golden`. Failures leave the render
+//! and a heat map per metric in `target/tmp/golden`.
+//!
+//! After an intended change, `RSTRACER_BLESS=1 cargo test --test golden`
+//! rewrites the references.
+
+use std::{env, fs, path::PathBuf};
+
+use rstracer::domain::{
+    camera::Camera,
+    framebuffer::Framebuffer,
+    image_diff::{heat_map, Metric},
+    scenes::BuiltinScene,
+};
+
+const SEED: u64 = 7;
+
+/// A scene, how to render it and the largest score allowed per metric.
+struct Case {
+    scene: BuiltinScene,
+    width: i32,
+    spp: i32,
+    tolerances: &'static [(Metric, f32)],
+}
+
+/// Loose enough for another platform's float rounding, which reroutes a
+/// few paths, but under the noise between two seeds: drawing the random
+/// numbers differently counts as a change and needs a bless.
+const DEFAULT: &[(Metric, f32)] = &[(Metric::RelMse, 0.02), (Metric::Flip, 0.05)];
+
+fn render(case: &Case) -> Framebuffer {
+    let description = case.scene.build();
+    let mut camera = Camera::new(description.camera.aspect_ratio, case.width, case.spp)
+        .with_vfov(description.camera.vfov);
+    camera.seed = SEED;
+    let integrator = description.render.integrator(&camera);
+    let frame = camera.render_frame(&description.scene, integrator.as_ref());
+    frame.beauty.scaled(1. / case.spp as f32)
+}
+
+fn check(case: Case) {
+    let name = case.scene.name();
+    let reference_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.pfm"));
+    let image = render(&case);
+
+    if env::var_os("RSTRACER_BLESS").is_some() {
+        fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        image
+            .write_pfm(&reference_path.to_string_lossy(), 1)
+            .unwrap();
+        return;
+    }
+    let reference = Framebuffer::read_pfm(&reference_path.to_string_lossy()).unwrap_or_else(|e| {
+        panic!(
+            "{}: {e}, render it with RSTRACER_BLESS=1",
+            reference_path.display()
+        )
+    });
+    assert_eq!(
+        (image.width, image.height),
+        (reference.width, reference.height),
+        "{name} changed size"
+    );
+
+    let failures: Vec<String> = case
+        .tolerances
+        .iter()
+        .filter_map(|&(metric, tolerance)| {
+            let score = metric.score(&image, &reference);
+            (score > tolerance).then(|| format!("{} {score:.4} > {tolerance}", metric.name()))
+        })
+        .collect();
+    if failures.is_empty() {
+        return;
+    }
+
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    fs::create_dir_all(&out).unwrap();
+    let path = |file: String| out.join(file).to_string_lossy().into_owned();
+    image.write_pfm(&path(format!("{name}.pfm")), 1).unwrap();
+    image
+        .write_image(&path(format!("{name}.png")), 1, image::ImageFormat::Png)
+        .unwrap();
+    for &(metric, tolerance) in case.tolerances {
+        let errors = metric.error_map(&image, &reference);
+        // White at ten times the tolerance
+        let max = match metric {
+            Metric::Rmse => tolerance * tolerance,
+            Metric::RelMse | Metric::Flip => tolerance,
+        };
+        heat_map(image.width, image.height, &errors, max * 10.)
+            .write_image(
+                &path(format!("{name}_{}_diff.png", metric.name())),
+                1,
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+    }
+    panic!(
+        "{name} differs from its reference: {}, see {}",
+        failures.join(", "),
+        out.display()
+    );
+}
+
+#[test]
+fn two_spheres() {
+    check(Case {
+        scene: BuiltinScene::TwoSpheres,
+        width: 64,
+        spp: 16,
+        tolerances: DEFAULT,
+    });
+}
+
+#[test]
+fn random_spheres() {
+    check(Case {
+        scene: BuiltinScene::RandomSpheres,
+        width: 64,
+        spp: 8,
+        tolerances: DEFAULT,
+    });
+}
+
+#[test]
+fn cornell_box() {
+    check(Case {
+        scene: BuiltinScene::CornellBox,
+        width: 48,
+        spp: 16,
+        tolerances: DEFAULT,
+    });
+}
+
+#[test]
+fn material_board() {
+    check(Case {
+        scene: BuiltinScene::MaterialBoard,
+        width: 64,
+        spp: 8,
+        tolerances: DEFAULT,
+    });
+}
+
+#[test]
+fn earth() {
+    check(Case {
+        scene: BuiltinScene::Earth,
+        width: 64,
+        spp: 4,
+        tolerances: &[(Metric::Rmse, 0.01), (Metric::Flip, 0.02)],
+    });
+}
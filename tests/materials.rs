@@ -0,0 +1,504 @@
+//! Statistical checks of every `Material::scatter`: white furnace tests for
+//! energy conservation and chi-square tests of the sampled directions
+//! against `scattering_pdf`. The random sequence is seeded, so the outcome
+//! is the same on every run.
+
+use rstracer::domain::{
+    camera::Camera,
+    coated::Coated,
+    hittable::Hit,
+    hittable_list::HittableList,
+    integrator::PathIntegrator,
+    material::{
+        fr_dielectric, Conductor, Dialectric, Ior, Lambertian, Material, Metal, RoughDielectric,
+        Subsurface, ThinFilm,
+    },
+    medium::Medium,
+    principled::Principled,
+    ray::Ray,
+    scene::Scene,
+    sphere::Sphere,
+    two_sided::TwoSided,
+    utils::{random_f32, seed_random, PI},
+    vec3::Vec3,
+};
+
+const ANGLES: [f32; 5] = [0., 30., 60., 80., 89.];
+
+fn white() -> Vec3 {
+    Vec3::new(1., 1., 1.)
+}
+
+/// Hit at the origin on a surface facing +z, the side the ray comes from.
+/// `front_face` false puts the ray inside the object.
+fn hit(material: &dyn Material, front_face: bool) -> Hit<'_> {
+    let normal = Vec3::new(0., 0., 1.);
+    Hit {
+        p: Vec3::new(0., 0., 0.),
+        normal,
+        geometric_normal: normal,
+        t: 1.,
+        u: 0.5,
+        v: 0.5,
+        dpdu: Vec3::new(1., 0., 0.),
+        dpdv: Vec3::new(0., 1., 0.),
+        material,
+        front_face,
+        object_id: 1,
+        material_id: 1,
+    }
+}
+
+/// Ray arriving `degrees` away from the normal.
+fn incoming(degrees: f32) -> Ray {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let from = Vec3::new(sin, 0., cos);
+    Ray::new(from, -from)
+}
+
+/// Mean and standard error of the weight `scatter` returns, zero when it
+/// absorbs the ray.
+fn directional_albedo(material: &dyn Material, ray: &Ray, front_face: bool) -> (Vec3, Vec3) {
+    const SAMPLES: usize = 20_000;
+    let hit = hit(material, front_face);
+    let (mut sum, mut sum_sq) = (Vec3::default(), Vec3::default());
+    for _ in 0..SAMPLES {
+        let weight = material
+            .scatter(ray, &hit)
+            .map_or(Vec3::default(), |(w, _)| w);
+        sum += weight;
+        sum_sq += weight * weight;
+    }
+    let n = SAMPLES as f32;
+    let mean = sum / n;
+    let variance = sum_sq / n - mean * mean;
+    let error = Vec3::new(
+        variance.x().max(0.).sqrt(),
+        variance.y().max(0.).sqrt(),
+        variance.z().max(0.).sqrt(),
+    ) / n.sqrt();
+    (mean, error)
+}
+
+/// Every material, and whether rays may hit its back face: it lets light
+/// through or has two sides. Opaque closed objects have no inside.
+fn materials() -> Vec<(&'static str, Box<dyn Material>, bool)> {
+    let mut metallic = Principled::new(white());
+    metallic.metallic = 1.;
+    metallic.roughness = 0.3;
+    let mut glass = Principled::new(white());
+    glass.transmission = 1.;
+    glass.roughness = 0.2;
+    let mut layered = Principled::new(white());
+    layered.roughness = 0.6;
+    layered.sheen = 1.;
+    layered.clearcoat = 1.;
+    let subsurface = Subsurface::new(1.3, Medium::from_albedo(white(), white(), 0.));
+    let two_sided = TwoSided::new(Lambertian::new(white()), Metal::new(white(), 0.2));
+    vec![
+        ("lambertian", Box::new(Lambertian::new(white())), false),
+        ("metal", Box::new(Metal::new(white(), 0.)), false),
+        ("fuzzy metal", Box::new(Metal::new(white(), 0.8)), false),
+        ("dielectric", Box::new(Dialectric::new(1.5)), true),
+        (
+            "dispersive",
+            Box::new(Dialectric::with_dispersion(Ior::bk7())),
+            true,
+        ),
+        ("thin film", Box::new(ThinFilm::soap_bubble(350.)), true),
+        ("subsurface", Box::new(subsurface), true),
+        ("gold", Box::new(Conductor::gold(0.)), false),
+        ("rough aluminum", Box::new(Conductor::aluminum(0.7)), false),
+        (
+            "rough dielectric",
+            Box::new(RoughDielectric::new(Ior::Constant(1.5), 0.4)),
+            true,
+        ),
+        (
+            "coated",
+            Box::new(Coated::new(Lambertian::new(white()), 1.5, 0.2)),
+            false,
+        ),
+        ("principled", Box::new(Principled::new(white())), false),
+        ("principled metal", Box::new(metallic), false),
+        ("principled glass", Box::new(glass), true),
+        ("principled layered", Box::new(layered), false),
+        ("two sided", Box::new(two_sided), true),
+    ]
+}
+
+#[test]
+fn no_material_creates_energy() {
+    seed_random(1);
+    for (name, material, back_face) in materials() {
+        for degrees in ANGLES {
+            for front_face in [true, false] {
+                if !front_face && !back_face {
+                    continue;
+                }
+                let (mean, error) =
+                    directional_albedo(material.as_ref(), &incoming(degrees), front_face);
+                for c in 0..3 {
+                    assert!(
+                        mean[c] <= 1. + 4. * error[c] + 1e-3,
+                        "{name} at {degrees}° (front {front_face}) reflects {mean:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn lossless_materials_keep_all_energy() {
+    seed_random(2);
+    let lossless: [(&str, Box<dyn Material>); 5] = [
+        ("lambertian", Box::new(Lambertian::new(white()))),
+        ("metal", Box::new(Metal::new(white(), 0.))),
+        ("dielectric", Box::new(Dialectric::new(1.5))),
+        ("thin film", Box::new(ThinFilm::soap_bubble(350.))),
+        (
+            "subsurface",
+            Box::new(Subsurface::new(
+                1.3,
+                Medium::from_albedo(white(), white(), 0.),
+            )),
+        ),
+    ];
+    for (name, material) in lossless {
+        for degrees in ANGLES {
+            for front_face in [true, false] {
+                let (mean, error) =
+                    directional_albedo(material.as_ref(), &incoming(degrees), front_face);
+                for c in 0..3 {
+                    assert!(
+                        (mean[c] - 1.).abs() <= 4. * error[c] + 1e-4,
+                        "{name} at {degrees}° (front {front_face}) reflects {mean:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Spheres that neither absorb nor emit vanish in a uniform white
+/// environment, any pixel off one is energy lost or made.
+#[test]
+fn white_furnace_renders_flat() {
+    let mut world = HittableList::new();
+    world.push(Box::new(Sphere::new(
+        Vec3::new(-1.1, 0., -4.),
+        0.5,
+        Lambertian::new(white()),
+    )));
+    world.push(Box::new(Sphere::new(
+        Vec3::new(0., 0., -4.),
+        0.5,
+        Dialectric::new(1.5),
+    )));
+    world.push(Box::new(Sphere::new(
+        Vec3::new(1.1, 0., -4.),
+        0.5,
+        Subsurface::new(1.3, Medium::from_albedo(white(), white() * 0.2, 0.)),
+    )));
+    let scene = Scene::new(world, HittableList::new()).with_background(white());
+
+    let mut camera = Camera::new(3., 48, 8).with_vfov(40.);
+    camera.seed = 3;
+    let frame = camera.render_frame(&scene, &PathIntegrator::new(1000));
+    let image = frame.beauty.scaled(1. / camera.samples_per_pixel as f32);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let pixel = image.get(x, y);
+            assert!(
+                (pixel - white()).length() < 1e-3,
+                "pixel ({x}, {y}) is {pixel:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn dielectric_reflects_by_fresnel() {
+    seed_random(4);
+    const SAMPLES: usize = 20_000;
+    let glass = Dialectric::new(1.5);
+    for front_face in [true, false] {
+        let eta = if front_face { 1.5 } else { 1. / 1.5 };
+        // 45° is one past the critical angle from inside, where every ray
+        // must reflect
+        for degrees in ANGLES.into_iter().chain([40., 45.]) {
+            let ray = incoming(degrees);
+            let hit = hit(&glass, front_face);
+            let (sin_i, cos_i) = degrees.to_radians().sin_cos();
+            let mut reflected = 0;
+            for _ in 0..SAMPLES {
+                let (_, scattered) = glass.scatter(&ray, &hit).unwrap();
+                let d = Vec3::unit_vector(scattered.direction());
+                if d.z() > 0. {
+                    reflected += 1;
+                    assert!((d - Vec3::new(-sin_i, 0., cos_i)).length() < 1e-4);
+                } else {
+                    // Snell's law
+                    assert!((-d.x() * eta - sin_i).abs() < 1e-4, "{d:?}");
+                }
+            }
+
+            let expected = fr_dielectric(cos_i, eta);
+            let share = reflected as f32 / SAMPLES as f32;
+            let error = (expected * (1. - expected) / SAMPLES as f32).sqrt();
+            assert!(
+                (share - expected).abs() <= 4. * error + 1e-6,
+                "{degrees}° (front {front_face}) reflects {share}, expected {expected}"
+            );
+        }
+    }
+}
+
+/// Fuzz pushes the mirror direction into a ball of radius `fuzz`; the
+/// part of the ball under the surface is absorbed. That is a spherical cap
+/// of height 1 - h with h = cos / fuzz, `(1 - h)^2 (2 + h) / 4` of the ball.
+#[test]
+fn metal_fuzz_absorbs_below_the_surface() {
+    seed_random(5);
+    for fuzz in [0.2, 0.5, 1.] {
+        let metal = Metal::new(white(), fuzz);
+        for degrees in ANGLES {
+            let h = (degrees.to_radians().cos() / fuzz).min(1.);
+            let expected = 1. - (1. - h).powi(2) * (2. + h) / 4.;
+            let (mean, error) = directional_albedo(&metal, &incoming(degrees), true);
+            assert!(
+                (mean.x() - expected).abs() <= 4. * error.x() + 1e-4,
+                "fuzz {fuzz} at {degrees}° keeps {}, expected {expected}",
+                mean.x()
+            );
+        }
+    }
+}
+
+/// Upper tail of the chi-square distribution by the Wilson-Hilferty cube
+/// root approximation, as a standard normal deviate.
+fn chi_square_z(statistic: f64, dof: f64) -> f64 {
+    let scale = 2. / (9. * dof);
+    ((statistic / dof).cbrt() - (1. - scale)) / scale.sqrt()
+}
+
+/// Integral of `f` over a rectangle of (cos θ, φ), the solid angle
+/// measure. Splits into quarters wherever the rule over the quarters
+/// disagrees with the whole, after a few splits everywhere so narrow lobes
+/// aren't missed.
+fn integrate(f: &dyn Fn(f32, f32) -> f64, cos: (f32, f32), phi: (f32, f32), depth: u32) -> f64 {
+    const MIN_DEPTH: u32 = 2;
+    const MAX_DEPTH: u32 = 10;
+    // 3 by 3 Gauss-Legendre rule
+    const NODES: [(f32, f64); 3] = [
+        (-0.774_596_7, 5. / 18.),
+        (0., 8. / 18.),
+        (0.774_596_7, 5. / 18.),
+    ];
+    let grid = |cos: (f32, f32), phi: (f32, f32)| {
+        let (hc, hp) = ((cos.1 - cos.0) / 2., (phi.1 - phi.0) / 2.);
+        let sum: f64 = NODES
+            .iter()
+            .flat_map(|&(x, wx)| NODES.iter().map(move |&(y, wy)| (x, y, wx * wy)))
+            .map(|(x, y, w)| w * f(cos.0 + hc * (1. + x), phi.0 + hp * (1. + y)))
+            .sum();
+        sum * (4. * hc * hp) as f64
+    };
+    let (mc, mp) = ((cos.0 + cos.1) / 2., (phi.0 + phi.1) / 2.);
+    let quarters = [
+        ((cos.0, mc), (phi.0, mp)),
+        ((cos.0, mc), (mp, phi.1)),
+        ((mc, cos.1), (phi.0, mp)),
+        ((mc, cos.1), (mp, phi.1)),
+    ];
+    let fine: f64 = quarters.iter().map(|&(c, p)| grid(c, p)).sum();
+    let converged = depth >= MIN_DEPTH && (fine - grid(cos, phi)).abs() < 1e-3 * fine + 1e-9;
+    if converged || depth == MAX_DEPTH {
+        return fine;
+    }
+    quarters
+        .iter()
+        .map(|&(c, p)| integrate(f, c, p, depth + 1))
+        .sum()
+}
+
+/// Bins `scatter`'s directions over the sphere, equal solid angle each, and
+/// compares the counts with `scattering_pdf` integrated over the bins.
+/// Absorbed samples count against the pdf's missing mass.
+fn chi_square(material: &dyn Material, ray: &Ray, front_face: bool) -> f64 {
+    const THETA: usize = 8;
+    const PHI: usize = 16;
+    const SAMPLES: usize = 100_000;
+    let hit = hit(material, front_face);
+    let bin = |cos: f32, phi: f32| {
+        let i = (((1. - cos) / 2. * THETA as f32) as usize).min(THETA - 1);
+        let j = ((phi.rem_euclid(2. * PI) / (2. * PI) * PHI as f32) as usize).min(PHI - 1);
+        i * PHI + j
+    };
+
+    let mut observed = vec![0.; THETA * PHI + 1];
+    for _ in 0..SAMPLES {
+        match material.scatter(ray, &hit) {
+            Some((_, scattered)) => {
+                let d = Vec3::unit_vector(scattered.direction());
+                observed[bin(d.z(), d.y().atan2(d.x()))] += 1.;
+            }
+            None => observed[THETA * PHI] += 1.,
+        }
+    }
+
+    let pdf = |cos: f32, phi: f32| {
+        let sin = (1. - cos * cos).max(0.).sqrt();
+        let d = Vec3::new(sin * phi.cos(), sin * phi.sin(), cos);
+        material.scattering_pdf(ray, &hit, &Ray::new(hit.p, d)) as f64
+    };
+    let mut expected = vec![0.; THETA * PHI + 1];
+    for i in 0..THETA {
+        let cos = (
+            1. - 2. * (i + 1) as f32 / THETA as f32,
+            1. - 2. * i as f32 / THETA as f32,
+        );
+        for j in 0..PHI {
+            let phi = (
+                2. * PI * j as f32 / PHI as f32,
+                2. * PI * (j + 1) as f32 / PHI as f32,
+            );
+            expected[i * PHI + j] = integrate(&pdf, cos, phi, 0) * SAMPLES as f64;
+        }
+    }
+    let sampled: f64 = expected.iter().sum();
+    expected[THETA * PHI] = (SAMPLES as f64 - sampled).max(0.);
+
+    // Pool sparse bins so every expected count is at least 5
+    let (mut statistic, mut dof) = (0., 0.);
+    let (mut pooled_observed, mut pooled_expected) = (0., 0.);
+    for (o, e) in observed.into_iter().zip(expected) {
+        if e < 5. {
+            pooled_observed += o;
+            pooled_expected += e;
+        } else {
+            statistic += (o - e) * (o - e) / e;
+            dof += 1.;
+        }
+    }
+    if pooled_expected > 0. {
+        statistic += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+        dof += 1.;
+    }
+    chi_square_z(statistic, dof - 1.)
+}
+
+#[test]
+fn sampling_matches_pdf() {
+    seed_random(6);
+    let mut metal = Principled::new(Vec3::new(0.9, 0.6, 0.3));
+    metal.metallic = 1.;
+    metal.roughness = 0.4;
+    let mut glass = Principled::new(white());
+    glass.transmission = 1.;
+    glass.roughness = 0.5;
+    let mut sheen = Principled::new(Vec3::new(0.3, 0.5, 0.8));
+    sheen.roughness = 0.5;
+    sheen.sheen = 0.5;
+    // Coated's pdf only approximates its random walk, it has its own check
+    // in `coated_eval_matches_scatter`. Specular ones have none and the
+    // clear coat lobe is too narrow for the bins. Only the transmissive ones
+    // are tested from inside.
+    let sampled: [(&str, Box<dyn Material>, bool); 8] = [
+        ("lambertian", Box::new(Lambertian::new(white())), false),
+        ("rough gold", Box::new(Conductor::gold(0.3)), false),
+        ("rough aluminum", Box::new(Conductor::aluminum(0.7)), false),
+        (
+            "rough dielectric",
+            Box::new(RoughDielectric::new(Ior::Constant(1.5), 0.4)),
+            true,
+        ),
+        ("principled", Box::new(Principled::new(white())), false),
+        ("principled metal", Box::new(metal), false),
+        ("principled glass", Box::new(glass), true),
+        ("principled sheen", Box::new(sheen), false),
+    ];
+    for (name, material, transmits) in sampled {
+        for degrees in [0., 45., 80.] {
+            for front_face in [true, false] {
+                if !front_face && !transmits {
+                    continue;
+                }
+                // One in ten thousand for a correct sampler
+                let z = chi_square(material.as_ref(), &incoming(degrees), front_face);
+                assert!(
+                    z < 3.72,
+                    "{name} at {degrees}° (front {front_face}): z = {z:.2}"
+                );
+            }
+        }
+    }
+}
+
+/// `Coated` is left out of `sampling_matches_pdf`, but light sampling and
+/// MIS only see it through `eval` and `scattering_pdf`. Over the hemisphere
+/// `eval` has to add up to the albedo of `scatter`. The pdf only follows
+/// the first way out of the layer, so it adds up to less than one, but it
+/// must not be zero where `eval` isn't or light sampling would be skipped.
+/// `eval` walks the layer at random, so it is integrated over uniformly
+/// sampled directions rather than with `integrate`.
+#[test]
+fn coated_eval_matches_scatter() {
+    seed_random(7);
+    const SAMPLES: usize = 100_000;
+    let coated: [(&str, Box<dyn Material>); 2] = [
+        (
+            "coated lambertian",
+            Box::new(Coated::new(Lambertian::new(white()), 1.5, 0.2)),
+        ),
+        (
+            "coated gold",
+            Box::new(Coated::new(Conductor::gold(0.3), 1.5, 0.2)),
+        ),
+    ];
+    for (name, material) in coated {
+        let hit = hit(material.as_ref(), true);
+        for degrees in [0., 30., 60., 80.] {
+            let ray = incoming(degrees);
+            let direction = |cos: f32, phi: f32| {
+                let sin = (1. - cos * cos).max(0.).sqrt();
+                Ray::new(hit.p, Vec3::new(sin * phi.cos(), sin * phi.sin(), cos))
+            };
+
+            let (mut sum, mut sum_sq) = (0., 0.);
+            for _ in 0..SAMPLES {
+                let scattered = direction(random_f32(), 2. * PI * random_f32());
+                let f = material.eval(&ray, &hit, &scattered).x() * 2. * PI;
+                if f > 0. {
+                    assert!(
+                        material.scattering_pdf(&ray, &hit, &scattered) > 0.,
+                        "{name} at {degrees}° has no pdf along {:?}",
+                        scattered.direction()
+                    );
+                }
+                sum += f;
+                sum_sq += f * f;
+            }
+            let n = SAMPLES as f32;
+            let eval = sum / n;
+            let eval_error = ((sum_sq / n - eval * eval).max(0.) / n).sqrt();
+            let (albedo, albedo_error) = directional_albedo(material.as_ref(), &ray, true);
+            let error = (eval_error * eval_error + albedo_error.x() * albedo_error.x()).sqrt();
+            assert!(
+                (eval - albedo.x()).abs() <= 4. * error + 1e-3,
+                "{name} at {degrees}° evaluates to {eval}, scatters {}",
+                albedo.x()
+            );
+
+            let pdf = |cos: f32, phi: f32| {
+                material.scattering_pdf(&ray, &hit, &direction(cos, phi)) as f64
+            };
+            let total = integrate(&pdf, (0., 1.), (0., 2. * PI), 0);
+            assert!(
+                total > 0. && total <= 1. + 1e-3,
+                "{name} at {degrees}°: pdf adds up to {total}"
+            );
+        }
+    }
+}